@@ -0,0 +1,299 @@
+use std::f64::consts::PI;
+
+use crate::{angle::Angle, Color, Image, Vec3};
+
+/// The light that reaches the scene from infinitely far away, which is seen wherever a ray
+/// doesn't hit anything.
+pub trait Background: Send + Sync {
+    /// Gets the light arriving from infinitely far away in `direction`.
+    fn value(&self, direction: &Vec3) -> Color;
+
+    /// The name of the background.
+    fn name(&self) -> &'static str;
+}
+
+/// A background that blends vertically between two colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Gradient {
+    /// Creates a new background that is `bottom` straight down, `top` straight up, and
+    /// interpolated linearly by height in between.
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+/// The white to light blue sky from the book.
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::new(Color::new(1., 1., 1.), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for Gradient {
+    fn value(&self, direction: &Vec3) -> Color {
+        let t = 0.5 * (direction.normalized().y() + 1.0);
+        self.bottom.interpolate(&self.top, t)
+    }
+
+    fn name(&self) -> &'static str {
+        "gradient"
+    }
+}
+
+/// A background that is the same color in every direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Uniform {
+    color: Color,
+}
+
+impl Uniform {
+    /// Creates a new background that is `color` in every direction.
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Background for Uniform {
+    fn value(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+
+    fn name(&self) -> &'static str {
+        "uniform"
+    }
+}
+
+/// An image of the distant surroundings of a scene in the equirectangular projection, where the
+/// horizontal axis of the image is longitude and the vertical axis is latitude. The top row of the
+/// image is straight up and the left edge is in the direction of the negative x-axis, which
+/// matches the surface coordinates of a [`Sphere`](crate::object::Sphere).
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvironmentMap {
+    image: Image,
+}
+
+impl EnvironmentMap {
+    /// Creates a new environment map from an image whose pixels are radiance in linear color
+    /// space, such as one read by [`Image::read_hdr()`].
+    pub fn new(image: Image) -> Self {
+        Self { image }
+    }
+
+    /// Gets the image that the environment map samples from.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+}
+
+/// Neighboring pixels are blended so that magnified environments don't look blocky.
+impl Background for EnvironmentMap {
+    fn value(&self, direction: &Vec3) -> Color {
+        let (width, height) = (self.image.width(), self.image.height());
+        if width == 0 || height == 0 {
+            return Color::default();
+        }
+        let d = direction.normalized();
+        let u = ((-d.z()).atan2(d.x()) + PI) / (2. * PI);
+        let v = (-d.y()).clamp(-1., 1.).acos() / PI;
+        let x = u * width as f64 - 0.5;
+        let y = (1. - v) * height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        // Longitude wraps around but latitude stops at the poles.
+        let column = |x: f64| (x as i64).rem_euclid(i64::from(width)) as u32;
+        let row = |y: f64| (y.max(0.) as u32).min(height - 1);
+        let (left, right) = (column(x0), column(x0 + 1.));
+        let (top, bottom) = (row(y0), row(y0 + 1.));
+        let upper = self
+            .image
+            .get(left, top)
+            .interpolate(&self.image.get(right, top), tx);
+        let lower = self
+            .image
+            .get(left, bottom)
+            .interpolate(&self.image.get(right, bottom), tx);
+        upper.interpolate(&lower, ty)
+    }
+
+    fn name(&self) -> &'static str {
+        "environment map"
+    }
+}
+
+/// The clear daytime sky from the analytic model of Preetham, Shirley, and Smits (1999), with a
+/// disk for the sun.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sky {
+    sun_direction: Vec3,
+    sun_radiance: f64,
+    /// The cosine of the angle between the sun and straight up.
+    cos_theta_sun: f64,
+    /// The luminance at the zenith and the coefficients of the Perez distribution for luminance.
+    luminance: Perez,
+    /// The chromaticity at the zenith and the coefficients of the Perez distribution for `x`.
+    x: Perez,
+    /// The chromaticity at the zenith and the coefficients of the Perez distribution for `y`.
+    y: Perez,
+    /// The color of sunlight after it passes through the atmosphere.
+    sun_color: Color,
+}
+
+/// One channel of the sky in the Perez luminance distribution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Perez {
+    zenith: f64,
+    coefficients: [f64; 5],
+}
+
+impl Perez {
+    /// Evaluates the unnormalized distribution for a direction at angle `theta` from the zenith
+    /// and angle `gamma` from the sun.
+    fn distribution(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.coefficients;
+        (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    /// Evaluates the channel for a direction at angle `theta` from the zenith and angle `gamma`
+    /// from the sun, which is at angle `theta_sun` from the zenith.
+    fn value(&self, cos_theta: f64, gamma: f64, theta_sun: f64) -> f64 {
+        self.zenith * self.distribution(cos_theta, gamma) / self.distribution(1., theta_sun)
+    }
+}
+
+impl Sky {
+    /// The angular radius of the sun as seen from the ground.
+    const SUN_ANGULAR_RADIUS: Angle = Angle::Degrees(0.27);
+
+    /// The scale from the luminance of the model in kilocandelas per square meter to the units of
+    /// the renderer.
+    const LUMINANCE_SCALE: f64 = 1000. / Color::WHITE_LUMINANCE;
+
+    /// Creates a new sky lit by a sun in the direction `sun_direction` whose disk has a radiance
+    /// of `sun_radiance` before passing through the atmosphere. `turbidity` is the haziness of
+    /// the air, from about `2` for a very clear sky to about `10` for a hazy one. A sun below the
+    /// horizon is treated as though it were on the horizon.
+    pub fn new(sun_direction: Vec3, turbidity: f64, sun_radiance: f64) -> Self {
+        let sun_direction = sun_direction.normalized();
+        let cos_theta_sun = sun_direction.y().max(0.);
+        let theta_sun = cos_theta_sun.acos();
+        let t = turbidity;
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let powers = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.];
+        let zenith_chromaticity = |coefficients: [[f64; 4]; 3]| {
+            let [quadratic, linear, constant] = coefficients.map(|row| {
+                row.iter()
+                    .zip(&powers)
+                    .map(|(coefficient, power)| coefficient * power)
+                    .sum::<f64>()
+            });
+            t * t * quadratic + t * linear + constant
+        };
+        let linear = |coefficients: [[f64; 2]; 5]| coefficients.map(|[m, b]| m * t + b);
+        Self {
+            sun_direction,
+            sun_radiance,
+            cos_theta_sun,
+            luminance: Perez {
+                zenith: zenith_luminance,
+                coefficients: linear([
+                    [0.1787, -1.4630],
+                    [-0.3554, 0.4275],
+                    [-0.0227, 5.3251],
+                    [0.1206, -2.5771],
+                    [-0.0670, 0.3703],
+                ]),
+            },
+            x: Perez {
+                zenith: zenith_chromaticity([
+                    [0.00166, -0.00375, 0.00209, 0.],
+                    [-0.02903, 0.06377, -0.03202, 0.00394],
+                    [0.11693, -0.21196, 0.06052, 0.25886],
+                ]),
+                coefficients: linear([
+                    [-0.0193, -0.2592],
+                    [-0.0665, 0.0008],
+                    [-0.0004, 0.2125],
+                    [-0.0641, -0.8989],
+                    [-0.0033, 0.0452],
+                ]),
+            },
+            y: Perez {
+                zenith: zenith_chromaticity([
+                    [0.00275, -0.00610, 0.00317, 0.],
+                    [-0.04214, 0.08970, -0.04153, 0.00516],
+                    [0.15346, -0.26756, 0.06670, 0.26688],
+                ]),
+                coefficients: linear([
+                    [-0.0167, -0.2608],
+                    [-0.0950, 0.0092],
+                    [-0.0079, 0.2102],
+                    [-0.0441, -1.6537],
+                    [-0.0109, 0.0529],
+                ]),
+            },
+            sun_color: Self::transmittance(t, cos_theta_sun),
+        }
+    }
+
+    /// Gets the direction toward the sun.
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    /// Computes how much of the light of the sun at each of the wavelengths of red, green, and
+    /// blue light reaches the ground through Rayleigh and aerosol scattering, using the
+    /// approximations from the appendix of Preetham, Shirley, and Smits.
+    fn transmittance(turbidity: f64, cos_theta_sun: f64) -> Color {
+        // The relative optical mass of the air along the path of the sunlight, from Kasten
+        // (1966).
+        let theta_degrees = cos_theta_sun.acos().to_degrees();
+        let air_mass = 1. / (cos_theta_sun + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+        // Wavelengths in micrometers.
+        let channel = |lambda: f64| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * air_mass).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * air_mass).exp();
+            rayleigh * aerosol
+        };
+        Color::new(channel(0.65), channel(0.55), channel(0.45))
+    }
+}
+
+impl Background for Sky {
+    fn value(&self, direction: &Vec3) -> Color {
+        let d = direction.normalized();
+        let cos_gamma = d.dot(&self.sun_direction).clamp(-1., 1.);
+        let sun = if self.sun_radiance > 0. && cos_gamma >= Self::SUN_ANGULAR_RADIUS.cos() {
+            self.sun_radiance * self.sun_color
+        } else {
+            Color::default()
+        };
+        // The model only describes the sky above the horizon, so it is extended downward by
+        // looking at the horizon instead.
+        let cos_theta = d.y().max(1e-3);
+        let gamma = cos_gamma.acos();
+        let theta_sun = self.cos_theta_sun.acos();
+        let luminance = Self::LUMINANCE_SCALE * self.luminance.value(cos_theta, gamma, theta_sun);
+        let x = self.x.value(cos_theta, gamma, theta_sun);
+        let y = self.y.value(cos_theta, gamma, theta_sun);
+        // Convert from CIE xyY to XYZ and then to linear sRGB.
+        let big_x = x / y * luminance;
+        let big_z = (1. - x - y) / y * luminance;
+        let sky = Color::new(
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+        );
+        sky + sun
+    }
+
+    fn name(&self) -> &'static str {
+        "sky"
+    }
+}
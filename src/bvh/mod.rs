@@ -0,0 +1,457 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    ray::{Hittable, RayHit},
+    Material, Point3, Ray,
+};
+
+/// A box whose faces are perpendicular to the coordinate axes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    min: Point3,
+    max: Point3,
+}
+
+impl Aabb {
+    /// Creates the smallest box that has `a` and `b` as corners.
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Self {
+            min: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    /// Creates the smallest box that contains all of `points`, or `None` if there are no points.
+    pub fn around_points(points: impl IntoIterator<Item = Point3>) -> Option<Self> {
+        points
+            .into_iter()
+            .map(|p| Self::new(p, p))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Gets the corner of the box nearest negative infinity.
+    pub fn min(&self) -> Point3 {
+        self.min
+    }
+
+    /// Gets the corner of the box nearest positive infinity.
+    pub fn max(&self) -> Point3 {
+        self.max
+    }
+
+    /// Gets the center of the box.
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) / 2.
+    }
+
+    /// Gets the eight corners of the box.
+    pub fn corners(&self) -> [Point3; 8] {
+        let (min, max) = (self.min, self.max);
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            Point3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() },
+            )
+        })
+    }
+
+    /// Creates the smallest box that contains both this box and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    /// Grows the box by `amount` in every direction.
+    pub fn padded(&self, amount: f64) -> Self {
+        let padding = Point3::new(amount, amount, amount);
+        Self::new(self.min - padding, self.max + padding)
+    }
+
+    /// The total area of the faces of the box.
+    pub fn surface_area(&self) -> f64 {
+        let size = self.max - self.min;
+        2. * (size.x() * size.y() + size.y() * size.z() + size.z() * size.x())
+    }
+
+    /// Finds the times within `valid_t` at which `ray` enters and leaves the box, if it passes
+    /// through the box at all.
+    pub fn hit_range(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<(f64, f64)> {
+        let (mut start, mut end) = valid_t.into_inner();
+        for axis in 0..3 {
+            let inverse = ray.direction()[axis].recip();
+            let t0 = (self.min[axis] - ray.origin()[axis]) * inverse;
+            let t1 = (self.max[axis] - ray.origin()[axis]) * inverse;
+            let (t0, t1) = if inverse < 0. { (t1, t0) } else { (t0, t1) };
+            // `NaN`s from rays that travel within a face of the box are ignored by `max`/`min`.
+            start = start.max(t0);
+            end = end.min(t1);
+            if end < start {
+                return None;
+            }
+        }
+        Some((start, end))
+    }
+
+    /// The axis along which the box is longest.
+    fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x() >= size.y() && size.x() >= size.z() {
+            0
+        } else if size.y() >= size.z() {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+/// How a [`Bvh`] decides where to divide the objects in a node between its two children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// Split at the middle of the longest axis of the centers of the objects. This builds the
+    /// fastest but handles unevenly spread objects poorly.
+    Middle,
+    /// Split so that each child gets half of the objects, sorted along the longest axis of their
+    /// centers. This always builds a balanced tree.
+    Median,
+    /// Split where the surface area heuristic predicts that rays will do the least work, trying
+    /// this many evenly spaced candidate splits along the longest axis. This builds the slowest
+    /// but usually renders the fastest.
+    SurfaceArea {
+        /// The number of bins that the centers of the objects are sorted into.
+        buckets: usize,
+    },
+}
+
+/// The order in which a [`Bvh`] visits the two children of a node that a ray passes through.
+/// Visiting the nearer child first finds close hits sooner, which lets more of the farther child
+/// be skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraversalOrder {
+    /// Always visit the first child first.
+    Fixed,
+    /// Visit first the child that comes first along the axis the node was split on, in the
+    /// direction the ray travels. This costs nothing but guesses wrong for some rays.
+    SplitAxis,
+    /// Visit first the child whose bounding box the ray enters first. This always picks the
+    /// nearer child but tests both boxes before visiting either.
+    NearestBox,
+}
+
+/// The settings used to build a [`Bvh`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BvhBuildOptions {
+    /// The most objects that a leaf of the tree may hold. Nodes with more objects are split
+    /// unless all of their objects share the same center. Larger leaves build faster and use less
+    /// memory but test more objects per ray.
+    pub max_leaf_size: usize,
+    /// How nodes are split.
+    pub split: SplitStrategy,
+    /// The order in which the children of a node are visited.
+    pub traversal: TraversalOrder,
+}
+
+impl Default for BvhBuildOptions {
+    fn default() -> Self {
+        Self {
+            max_leaf_size: 4,
+            split: SplitStrategy::SurfaceArea { buckets: 12 },
+            traversal: TraversalOrder::SplitAxis,
+        }
+    }
+}
+
+/// A node of a [`Bvh`], stored in depth-first order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Node {
+    /// A node whose objects are tested directly.
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    /// A node whose first child immediately follows it.
+    Interior {
+        bounds: Aabb,
+        second: usize,
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Self::Leaf { bounds, .. } | Self::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// A bounding volume hierarchy, which sorts objects into a tree of nested boxes so that a ray
+/// only has to be tested against the objects in the boxes it passes through. Objects without a
+/// [bounding box](Hittable::bounding_box()), such as infinite planes, are tested against every
+/// ray.
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    objects: Vec<Arc<dyn Hittable>>,
+    unbounded: Vec<Arc<dyn Hittable>>,
+    traversal: TraversalOrder,
+}
+
+impl Bvh {
+    /// Builds a hierarchy over `objects` that is valid for rays cast at any moment in `time`.
+    pub fn new(
+        objects: Vec<Arc<dyn Hittable>>,
+        time: RangeInclusive<f64>,
+        options: &BvhBuildOptions,
+    ) -> Self {
+        let (mut bounded, mut unbounded) = (vec![], vec![]);
+        for object in objects {
+            match object.bounding_box(time.clone()) {
+                Some(bounds) => bounded.push((bounds, object)),
+                None => unbounded.push(object),
+            }
+        }
+        let mut nodes = Vec::with_capacity(2 * bounded.len());
+        if !bounded.is_empty() {
+            build(&mut nodes, &mut bounded, 0, options);
+        }
+        Self {
+            nodes,
+            objects: bounded.into_iter().map(|(_, object)| object).collect(),
+            unbounded,
+            traversal: options.traversal,
+        }
+    }
+
+    /// Gets the number of nodes in the tree.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+/// Appends the subtree for `items`, which start at index `first` of the objects of the tree, to
+/// `nodes`.
+fn build(
+    nodes: &mut Vec<Node>,
+    items: &mut [(Aabb, Arc<dyn Hittable>)],
+    first: usize,
+    options: &BvhBuildOptions,
+) {
+    let bounds = items
+        .iter()
+        .map(|(bounds, _)| *bounds)
+        .reduce(|a, b| a.union(&b))
+        .expect("Nodes should never be empty");
+    let index = nodes.len();
+    nodes.push(Node::Leaf {
+        bounds,
+        first,
+        count: items.len(),
+    });
+    if items.len() <= options.max_leaf_size.max(1) {
+        return;
+    }
+    let centroids = Aabb::around_points(items.iter().map(|(bounds, _)| bounds.centroid()))
+        .expect("Nodes should never be empty");
+    let axis = centroids.longest_axis();
+    let (low, high) = (centroids.min()[axis], centroids.max()[axis]);
+    if low == high {
+        return;
+    }
+    let center = |(bounds, _): &(Aabb, Arc<dyn Hittable>)| bounds.centroid()[axis];
+    let median = |items: &mut [(Aabb, Arc<dyn Hittable>)]| {
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |a, b| {
+            center(a).partial_cmp(&center(b)).unwrap_or(Ordering::Equal)
+        });
+        middle
+    };
+    let split = match options.split {
+        SplitStrategy::Middle => {
+            let middle = (low + high) / 2.;
+            partition(items, |item| center(item) < middle)
+        }
+        SplitStrategy::Median => median(items),
+        SplitStrategy::SurfaceArea { buckets } => {
+            let buckets = buckets.max(2);
+            let bucket_of = |item: &(Aabb, Arc<dyn Hittable>)| {
+                (((center(item) - low) / (high - low) * buckets as f64) as usize).min(buckets - 1)
+            };
+            let mut bins: Vec<Option<(Aabb, usize)>> = vec![None; buckets];
+            for item in items.iter() {
+                let bin = &mut bins[bucket_of(item)];
+                *bin = Some(match bin {
+                    Some((bounds, count)) => (bounds.union(&item.0), *count + 1),
+                    None => (item.0, 1),
+                });
+            }
+            // Sweep from both ends to find the cost of each candidate split.
+            let sweep = |bins: &mut dyn Iterator<Item = &Option<(Aabb, usize)>>| {
+                let mut total: Option<(Aabb, usize)> = None;
+                bins.map(|bin| {
+                    if let Some((bounds, count)) = bin {
+                        total = Some(match total {
+                            Some((total_bounds, total_count)) => {
+                                (total_bounds.union(bounds), total_count + count)
+                            }
+                            None => (*bounds, *count),
+                        });
+                    }
+                    total.map_or(0., |(bounds, count)| bounds.surface_area() * count as f64)
+                })
+                .collect::<Vec<_>>()
+            };
+            let below = sweep(&mut bins.iter());
+            let mut above = sweep(&mut bins.iter().rev());
+            above.reverse();
+            let best = (1..buckets)
+                .min_by(|&a, &b| {
+                    (below[a - 1] + above[a])
+                        .partial_cmp(&(below[b - 1] + above[b]))
+                        .unwrap_or(Ordering::Equal)
+                })
+                .expect("There should be at least two buckets");
+            partition(items, |item| bucket_of(item) < best)
+        }
+    };
+    // Fall back to an even split if every object landed on the same side.
+    let split = if split == 0 || split == items.len() {
+        median(items)
+    } else {
+        split
+    };
+    let (below, above) = items.split_at_mut(split);
+    build(nodes, below, first, options);
+    let second = nodes.len();
+    build(nodes, above, first + split, options);
+    nodes[index] = Node::Interior {
+        bounds,
+        second,
+        axis,
+    };
+}
+
+/// Reorders `items` so that all of the items for which `predicate` is `true` come first and
+/// returns how many there are.
+fn partition<T>(items: &mut [T], mut predicate: impl FnMut(&T) -> bool) -> usize {
+    let mut split = 0;
+    for i in 0..items.len() {
+        if predicate(&items[i]) {
+            items.swap(i, split);
+            split += 1;
+        }
+    }
+    split
+}
+
+impl Debug for Bvh {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bvh")
+            .field("nodes", &self.nodes.len())
+            .field("objects", &self.objects.len())
+            .field("unbounded", &self.unbounded.len())
+            .field("traversal", &self.traversal)
+            .finish()
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let start = *valid_t.start();
+        let mut closest: Option<RayHit> = None;
+        let mut end = *valid_t.end();
+        for object in &self.unbounded {
+            if let Some(hit) = object.hit_by(ray, start..=end) {
+                end = hit.t;
+                closest = Some(hit);
+            }
+        }
+        if self.nodes.is_empty() {
+            return closest;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds().hit_range(ray, start..=end).is_none() {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for object in &self.objects[first..first + count] {
+                        if let Some(hit) = object.hit_by(ray, start..=end) {
+                            end = hit.t;
+                            closest = Some(hit);
+                        }
+                    }
+                }
+                Node::Interior { second, axis, .. } => {
+                    let first = index + 1;
+                    let first_is_nearer = match self.traversal {
+                        TraversalOrder::Fixed => true,
+                        TraversalOrder::SplitAxis => ray.direction()[axis] >= 0.,
+                        TraversalOrder::NearestBox => {
+                            let entry = |child: usize| {
+                                self.nodes[child]
+                                    .bounds()
+                                    .hit_range(ray, start..=end)
+                                    .map(|(entry, _)| entry)
+                            };
+                            match (entry(first), entry(second)) {
+                                (Some(a), Some(b)) => a <= b,
+                                (Some(_), None) => {
+                                    stack.push(first);
+                                    continue;
+                                }
+                                (None, Some(_)) => {
+                                    stack.push(second);
+                                    continue;
+                                }
+                                (None, None) => continue,
+                            }
+                        }
+                    };
+                    // The child pushed last is visited first.
+                    if first_is_nearer {
+                        stack.extend([second, first]);
+                    } else {
+                        stack.extend([first, second]);
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.nodes.first().map(|node| *node.bounds())
+        } else {
+            None
+        }
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.objects
+            .iter()
+            .chain(&self.unbounded)
+            .flat_map(|object| object.materials())
+            .collect()
+    }
+}
@@ -1,4 +1,13 @@
-use crate::{angle::Angle, Point3, Ray, Vec3};
+use std::{f64::consts::PI, ops::RangeInclusive};
+
+use rayon::prelude::*;
+
+use crate::{
+    angle::Angle,
+    rng,
+    sampler::{self, SampleIndex, Sampler, LENS_DIMENSION, PIXEL_DIMENSION, TIME_DIMENSION},
+    Point3, Ray, Vec3,
+};
 
 /// The point that the image is seen from.
 #[derive(Clone, Copy, Debug)]
@@ -11,10 +20,36 @@ pub struct Camera {
     u: Vec3,
     /// The unit vector along the positive y-axis in the final image.
     v: Vec3,
-    /// The unit vector that is directly forward from the camera.
-    #[allow(unused)]
+    /// The unit vector that is directly backward from the camera.
     w: Vec3,
     lens_radius: f64,
+    near_clip: f64,
+    far_clip: f64,
+    shutter_open: f64,
+    shutter_close: f64,
+    projection: Projection,
+    /// The distance from the center of the image to its top or bottom edge on the plane that the
+    /// stereographic projection maps directions onto.
+    stereographic_height: f64,
+}
+
+/// How the directions around the camera are laid out on the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Projection {
+    /// Straight lines stay straight, as through an ordinary lens.
+    #[default]
+    Perspective,
+    /// Every direction around the camera, with longitude along the width of the image and
+    /// latitude along its height. The direction directly behind the camera is at the left and
+    /// right edges, and the field of view is ignored. Images in this projection usually have an
+    /// aspect ratio of `2`.
+    Equirectangular,
+    /// Directions are mapped to the image through the stereographic projection, which keeps
+    /// small shapes from being distorted. The direction in front of the camera is at the center of
+    /// the image, and the field of view may be wider than 180 degrees. Pointing the camera
+    /// straight down with a field of view of about 300 degrees wraps the ground around into a
+    /// "little planet".
+    Stereographic,
 }
 
 impl Camera {
@@ -42,18 +77,311 @@ impl Camera {
             v,
             w,
             lens_radius: structure.aperture_width / 2.,
+            near_clip: structure.near_clip,
+            far_clip: structure.far_clip,
+            shutter_open: structure.shutter_open,
+            shutter_close: structure.shutter_close,
+            projection: Projection::Perspective,
+            stereographic_height: (structure.vertical_fov / 4.).tan(),
         }
     }
 
-    /// Gets a ray from the camera to the viewport coordinates `(u, v)`.
+    /// Lays the directions around the camera out on the image by `projection` instead of the
+    /// default [`Projection::Perspective`].
+    pub fn with_projection(self, projection: Projection) -> Self {
+        Self { projection, ..self }
+    }
+
+    /// Gets how the directions around the camera are laid out on the image.
+    pub const fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Gets the unit vector from the center of the lens toward the viewport coordinates `(u, v)`
+    /// in one of the panoramic projections.
+    fn panorama_direction(&self, u: f64, v: f64) -> Vec3 {
+        let (right, up, forward) = match self.projection {
+            Projection::Perspective => unreachable!("The perspective projection uses a viewport"),
+            Projection::Equirectangular => {
+                let longitude = 2. * PI * (u - 0.5);
+                let latitude = PI * (v - 0.5);
+                (
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                )
+            }
+            Projection::Stereographic => {
+                let aspect_ratio = self.horizontal.length() / self.vertical.length();
+                let x = 2. * self.stereographic_height * aspect_ratio * (u - 0.5);
+                let y = 2. * self.stereographic_height * (v - 0.5);
+                let r_squared = x * x + y * y;
+                (
+                    2. * x / (1. + r_squared),
+                    2. * y / (1. + r_squared),
+                    (1. - r_squared) / (1. + r_squared),
+                )
+            }
+        };
+        right * self.u + up * self.v - forward * self.w
+    }
+
+    /// Finds the viewport coordinates `(u, v)` that the unit vector `direction` from the center
+    /// of the lens appears at in one of the panoramic projections.
+    fn panorama_coordinates(&self, direction: &Vec3) -> Option<(f64, f64)> {
+        let (right, up, forward) = (
+            direction.dot(&self.u),
+            direction.dot(&self.v),
+            -direction.dot(&self.w),
+        );
+        match self.projection {
+            Projection::Perspective => unreachable!("The perspective projection uses a viewport"),
+            Projection::Equirectangular => Some((
+                right.atan2(forward) / (2. * PI) + 0.5,
+                up.clamp(-1., 1.).asin() / PI + 0.5,
+            )),
+            Projection::Stereographic => {
+                if forward <= -1. + 1e-12 {
+                    return None;
+                }
+                let aspect_ratio = self.horizontal.length() / self.vertical.length();
+                let (x, y) = (right / (1. + forward), up / (1. + forward));
+                Some((
+                    x / (2. * self.stereographic_height * aspect_ratio) + 0.5,
+                    y / (2. * self.stereographic_height) + 0.5,
+                ))
+            }
+        }
+    }
+
+    /// Gets a ray from the camera to the viewport coordinates `(u, v)` cast at a random moment
+    /// while the shutter is open.
     pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let fuzzed = self.lens_radius * Vec3::random_in_unit_disk();
-        let offset = self.u * fuzzed.x() + self.v * fuzzed.y();
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
+        let lens = Vec3::random_in_unit_disk();
+        self.get_sampled_ray(u, v, (lens.x(), lens.y()), rng::random())
+    }
+
+    /// Gets a ray from the point `lens` of the unit disk scaled to the aperture to the viewport
+    /// coordinates `(u, v)`, cast at the moment that is the fraction `shutter` of the way from
+    /// when the shutter opens to when it closes. [`get_ray()`] picks both points at random.
+    ///
+    /// [`get_ray()`]: Self::get_ray()
+    pub fn get_sampled_ray(&self, u: f64, v: f64, lens: (f64, f64), shutter: f64) -> Ray {
+        let offset = self.lens_radius * (self.u * lens.0 + self.v * lens.1);
+        let time = self.shutter_open + shutter * (self.shutter_close - self.shutter_open);
+        let target = match self.projection {
+            Projection::Perspective => {
+                self.lower_left_corner + u * self.horizontal + v * self.vertical
+            }
+            // Panoramas are in focus on a sphere around the lens instead of on a plane.
+            _ => self.origin + self.focus_distance() * self.panorama_direction(u, v),
+        };
+        Ray::with_time(self.origin + offset, target - self.origin - offset, time)
+    }
+
+    /// Gets a ray from the center of the lens to the viewport coordinates `(u, v)` cast at the
+    /// moment `time`. Unlike [`get_ray()`], this ignores depth of field and motion blur.
+    ///
+    /// [`get_ray()`]: Self::get_ray()
+    pub fn get_pinhole_ray(&self, u: f64, v: f64, time: f64) -> Ray {
+        let direction = match self.projection {
+            Projection::Perspective => {
+                self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin
+            }
+            _ => self.focus_distance() * self.panorama_direction(u, v),
+        };
+        Ray::with_time(self.origin, direction, time)
+    }
+
+    /// Finds the viewport coordinates `(u, v)` that `p` appears at as seen through the center of
+    /// the lens. Returns `None` if `p` is not in front of the camera.
+    pub fn project(&self, p: &Point3) -> Option<(f64, f64)> {
+        let offset = *p - self.origin;
+        if self.projection != Projection::Perspective {
+            return (!offset.near_zero())
+                .then(|| self.panorama_coordinates(&offset.normalized()))
+                .flatten();
+        }
+        let depth = -offset.dot(&self.w);
+        if depth <= 0. {
+            return None;
+        }
+        // Slide `p` toward the camera until it lies on the viewport.
+        let focus_distance = self.focus_distance();
+        let on_viewport = self.origin + offset * (focus_distance / depth) - self.lower_left_corner;
+        Some((
+            on_viewport.dot(&self.horizontal) / self.horizontal.length_squared(),
+            on_viewport.dot(&self.vertical) / self.vertical.length_squared(),
+        ))
+    }
+
+    /// Gets the distance from the lens to the plane that is in perfect focus.
+    pub fn focus_distance(&self) -> f64 {
+        -(self.lower_left_corner - self.origin).dot(&self.w)
+    }
+
+    /// Gets the width of the camera's aperture.
+    pub fn aperture_width(&self) -> f64 {
+        2. * self.lens_radius
+    }
+
+    /// Computes the diameter of the circle that `p` is blurred into by the depth of field, as a
+    /// fraction of the height of the image. Returns `None` if `p` is not in front of the camera.
+    pub fn blur_diameter(&self, p: &Point3) -> Option<f64> {
+        let depth = -(*p - self.origin).dot(&self.w);
+        if depth <= 0. {
+            return None;
+        }
+        let focus_distance = self.focus_distance();
+        // The blur measured on the plane of focus, which is where the viewport is.
+        let diameter = self.aperture_width() * (depth - focus_distance).abs() / depth;
+        Some(diameter / self.vertical.length())
+    }
+
+    /// Gets the moment at which the shutter opens.
+    pub const fn shutter_open(&self) -> f64 {
+        self.shutter_open
+    }
+
+    /// Gets the moment at which the shutter closes.
+    pub const fn shutter_close(&self) -> f64 {
+        self.shutter_close
+    }
+
+    /// Gets the times at which `ray`, which must have been produced by [`get_ray()`], is between
+    /// the near and far clipping planes. Panoramas see in every direction, so they are clipped by
+    /// spheres around the lens instead of by planes.
+    ///
+    /// [`get_ray()`]: Self::get_ray()
+    pub fn clip_range(&self, ray: &Ray) -> RangeInclusive<f64> {
+        let speed = match self.projection {
+            Projection::Perspective => -ray.direction().dot(&self.w),
+            _ => ray.direction().length(),
+        };
+        (self.near_clip / speed)..=(self.far_clip / speed)
+    }
+
+    /// Gets a ray from the camera through the point `offset` of the pixel in column `x` and row
+    /// `y` of an image that is `width` pixels wide and `height` pixels tall, as described by
+    /// [`get_ray()`]. Columns are numbered from the left edge of the image and rows from the
+    /// bottom, and each component of `offset` is between `0` and `1`.
+    ///
+    /// [`get_ray()`]: Self::get_ray()
+    pub fn get_pixel_ray(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        offset: (f64, f64),
+    ) -> Ray {
+        let (u, v) = Self::viewport_coordinates(x, y, width, height, offset);
+        self.get_ray(u, v)
+    }
+
+    /// Gets the ray for `sample` of an image that is `width` pixels wide and `height` pixels
+    /// tall, as described by [`get_pixel_ray()`], with its position within the pixel, its
+    /// position on the lens, and its moment while the shutter is open all chosen by `sampler`.
+    ///
+    /// [`get_pixel_ray()`]: Self::get_pixel_ray()
+    pub fn get_sampler_ray(
+        &self,
+        sampler: &dyn Sampler,
+        sample: SampleIndex,
+        width: u32,
+        height: u32,
+    ) -> Ray {
+        let (x, y) = sample.pixel;
+        let offset = sampler.get_2d(sample, PIXEL_DIMENSION);
+        let (u, v) = Self::viewport_coordinates(x, y, width, height, offset);
+        let lens = sampler::concentric_disk(sampler.get_2d(sample, LENS_DIMENSION));
+        self.get_sampled_ray(u, v, lens, sampler.get_1d(sample, TIME_DIMENSION))
+    }
+
+    /// Finds the viewport coordinates of the point `offset` of the pixel in column `x` and row `y`
+    /// of an image that is `width` pixels wide and `height` pixels tall.
+    fn viewport_coordinates(
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        offset: (f64, f64),
+    ) -> (f64, f64) {
+        (
+            (x as f64 + offset.0) / (width.max(2) - 1) as f64,
+            (y as f64 + offset.1) / (height.max(2) - 1) as f64,
         )
     }
+
+    /// Iterates over the pixels of an image that is `width` pixels wide and `height` pixels tall
+    /// in the order that they are written: row by row from the top of the image, and from left to
+    /// right within each row. `sampler` is given the column and row of each pixel, numbered as by
+    /// [`get_pixel_ray()`], and chooses the offsets within it through which rays are cast.
+    ///
+    /// [`get_pixel_ray()`]: Self::get_pixel_ray()
+    pub fn rays<'a, S, I>(
+        &'a self,
+        width: u32,
+        height: u32,
+        mut sampler: S,
+    ) -> impl Iterator<Item = PixelRays> + 'a
+    where
+        S: FnMut(u32, u32) -> I + 'a,
+        I: IntoIterator<Item = (f64, f64)>,
+    {
+        (0..height)
+            .rev()
+            .flat_map(move |y| (0..width).map(move |x| (x, y)))
+            .map(move |(x, y)| PixelRays {
+                x,
+                y,
+                rays: sampler(x, y)
+                    .into_iter()
+                    .map(|offset| self.get_pixel_ray(x, y, width, height, offset))
+                    .collect(),
+            })
+    }
+
+    /// Like [`rays()`], but the pixels are produced in parallel. They are still collected in the
+    /// order that they are written.
+    ///
+    /// [`rays()`]: Self::rays()
+    pub fn par_rays<'a, S, I>(
+        &'a self,
+        width: u32,
+        height: u32,
+        sampler: S,
+    ) -> impl IndexedParallelIterator<Item = PixelRays> + 'a
+    where
+        S: Fn(u32, u32) -> I + Send + Sync + 'a,
+        I: IntoIterator<Item = (f64, f64)>,
+    {
+        (0..width as usize * height as usize)
+            .into_par_iter()
+            .map(move |index| {
+                let x = (index % width as usize) as u32;
+                let y = height - 1 - (index / width as usize) as u32;
+                PixelRays {
+                    x,
+                    y,
+                    rays: sampler(x, y)
+                        .into_iter()
+                        .map(|offset| self.get_pixel_ray(x, y, width, height, offset))
+                        .collect(),
+                }
+            })
+    }
+}
+
+/// The rays that [`Camera::rays()`] casts through one pixel of an image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelRays {
+    /// The column of the pixel, counted from the left edge of the image.
+    pub x: u32,
+    /// The row of the pixel, counted from the bottom edge of the image.
+    pub y: u32,
+    /// The rays cast through the pixel.
+    pub rays: Vec<Ray>,
 }
 
 /// The location and orientation of the camera.
@@ -79,4 +407,12 @@ pub struct Structure {
     pub aperture_width: f64,
     /// The distance from the camera's lens to the plane that is in perfect focus.
     pub focus_distance: f64,
+    /// The distance from the camera's lens to the plane in front of which nothing is seen.
+    pub near_clip: f64,
+    /// The distance from the camera's lens to the plane behind which nothing is seen.
+    pub far_clip: f64,
+    /// The moment at which the shutter opens.
+    pub shutter_open: f64,
+    /// The moment at which the shutter closes.
+    pub shutter_close: f64,
 }
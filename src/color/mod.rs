@@ -1,6 +1,6 @@
 use std::{
     fmt::{self, Display, Formatter},
-    ops::{Div, DivAssign, Index, Mul, MulAssign, Range},
+    ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Range},
 };
 
 use rand::{
@@ -10,9 +10,11 @@ use rand::{
 };
 use rayon::prelude::ParallelIterator;
 
-use crate::Vec3;
+use crate::{rng, Vec3};
 
-/// An RGB color. The intensity of each component is in the range `[0.0, 1.0]`.
+/// An RGB color. The intensity of each component is non-negative. Components greater than `1.0`
+/// represent light that is brighter than can be displayed and are clamped when the color is
+/// written out.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Color {
     r: f64,
@@ -21,12 +23,58 @@ pub struct Color {
 }
 
 impl Color {
+    /// The luminance in candelas per square meter of light whose channels are all `1.0`. This is
+    /// chosen so that a clear sky at midday is about as bright as the default
+    /// [`Gradient`](crate::background::Gradient).
+    pub const WHITE_LUMINANCE: f64 = 20_000.;
+
     /// Create a new color with the specified components.
     pub fn new(r: f64, g: f64, b: f64) -> Self {
         Self {
-            r: r.clamp(0., 1.),
-            g: g.clamp(0., 1.),
-            b: b.clamp(0., 1.),
+            r: r.max(0.),
+            g: g.max(0.),
+            b: b.max(0.),
+        }
+    }
+
+    /// Computes the color of the light emitted by an ideal blackbody at a temperature of `kelvin`
+    /// degrees Kelvin. The color is scaled so that its brightest channel is `1.0`.
+    pub fn from_temperature(kelvin: f64) -> Self {
+        // The second radiation constant, `hc/k`, in meter-Kelvins.
+        const C2: f64 = 1.4388e-2;
+        // Piecewise Gaussian fits of the CIE 1931 color matching functions from Wyman, Sloan, and
+        // Shirley (2013).
+        fn gaussian(lambda: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+            let sigma = if lambda < mu { sigma_low } else { sigma_high };
+            (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+        }
+        let (x, y, z) = (380..=780)
+            .step_by(5)
+            .map(f64::from)
+            .map(|lambda| {
+                let meters = lambda * 1e-9;
+                let radiance = 1. / (meters.powi(5) * ((C2 / (meters * kelvin)).exp() - 1.));
+                let x = 1.056 * gaussian(lambda, 599.8, 37.9, 31.0)
+                    + 0.362 * gaussian(lambda, 442.0, 16.0, 26.7)
+                    - 0.065 * gaussian(lambda, 501.1, 20.4, 26.2);
+                let y = 0.821 * gaussian(lambda, 568.8, 46.9, 40.5)
+                    + 0.286 * gaussian(lambda, 530.9, 16.3, 31.1);
+                let z = 1.217 * gaussian(lambda, 437.0, 11.8, 36.0)
+                    + 0.681 * gaussian(lambda, 459.0, 26.0, 13.8);
+                (radiance * x, radiance * y, radiance * z)
+            })
+            .fold((0., 0., 0.), |(x0, y0, z0), (x1, y1, z1)| {
+                (x0 + x1, y0 + y1, z0 + z1)
+            });
+        // Convert from CIE XYZ to linear sRGB.
+        let r = (3.2406 * x - 1.5372 * y - 0.4986 * z).max(0.);
+        let g = (-0.9689 * x + 1.8758 * y + 0.0415 * z).max(0.);
+        let b = (0.0557 * x - 0.2040 * y + 1.0570 * z).max(0.);
+        let brightest = r.max(g).max(b);
+        if brightest > 0. {
+            Self::new(r / brightest, g / brightest, b / brightest)
+        } else {
+            Self::default()
         }
     }
 
@@ -59,17 +107,17 @@ impl Color {
 
     /// Sets the red part of the color.
     pub fn set_red(&mut self, r: f64) {
-        self.r = r.clamp(0., 1.);
+        self.r = r.max(0.);
     }
 
     /// Sets the green part of the color.
     pub fn set_green(&mut self, g: f64) {
-        self.g = g.clamp(0., 1.);
+        self.g = g.max(0.);
     }
 
     /// Sets the blue part of the color.
     pub fn set_blue(&mut self, b: f64) {
-        self.b = b.clamp(0., 1.);
+        self.b = b.max(0.);
     }
 
     /// Interpolates linearly from `self` to `other`. If `t <= 0.0`, returns `self`. If `t >= 1.0`,
@@ -84,6 +132,12 @@ impl Color {
         )
     }
 
+    /// Gets the relative luminance of the color, which weights each channel by how bright it
+    /// appears.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     /// Multiplies the corresponding channels of the two colors together.
     pub fn attenuate(&self, rhs: &Self) -> Self {
         Self::new(
@@ -95,7 +149,66 @@ impl Color {
 
     /// Generates a random color where each channel is uniformly-distributed in the given range.
     pub fn random(range: Range<f64>) -> Self {
-        Uniform::from(range).sample(&mut rand::thread_rng())
+        rng::with_rng(|rng| Uniform::from(range).sample(rng))
+    }
+}
+
+/// The primaries of an RGB color space with a D65 white point. Colors are rendered in linear
+/// [`Srgb`](Self::Srgb), which has the same primaries as Rec.709, and can be converted to a wider
+/// gamut when they are written out so that they are shown correctly on displays that expect it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Primaries {
+    /// The primaries of sRGB and Rec.709, which is the working space of the renderer.
+    #[default]
+    Srgb,
+    /// The primaries of DCI-P3 with the D65 white point, used by many recent displays.
+    DisplayP3,
+    /// The primaries of Rec.2020, used for HDR and ultra high definition video.
+    Rec2020,
+}
+
+impl Primaries {
+    /// The matrix that converts linear sRGB to linear RGB with these primaries.
+    fn matrix(self) -> [[f64; 3]; 3] {
+        match self {
+            Self::Srgb => [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            Self::DisplayP3 => [
+                [0.822_462, 0.177_538, 0.],
+                [0.033_194, 0.966_806, 0.],
+                [0.017_083, 0.072_397, 0.910_520],
+            ],
+            Self::Rec2020 => [
+                [0.627_404, 0.329_283, 0.043_313],
+                [0.069_097, 0.919_540, 0.011_362],
+                [0.016_392, 0.088_013, 0.895_595],
+            ],
+        }
+    }
+
+    /// Converts `color` from linear sRGB to linear RGB with these primaries. Every color in the
+    /// sRGB gamut is also in the gamut of the other primaries, so no channel becomes negative.
+    pub fn convert(self, color: Color) -> Color {
+        let [r, g, b] = self
+            .matrix()
+            .map(|row| row[0] * color.r + row[1] * color.g + row[2] * color.b);
+        Color::new(r, g, b)
+    }
+}
+
+impl Add for Color {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        self.set_red(self.r + rhs.r);
+        self.set_green(self.g + rhs.g);
+        self.set_blue(self.b + rhs.b);
     }
 }
 
@@ -104,9 +217,9 @@ impl Display for Color {
         write!(
             f,
             "{} {} {}",
-            (self.r * 255.999) as u32,
-            (self.g * 255.999) as u32,
-            (self.b * 255.999) as u32,
+            (self.r.min(1.) * 255.999) as u32,
+            (self.g.min(1.) * 255.999) as u32,
+            (self.b.min(1.) * 255.999) as u32,
         )
     }
 }
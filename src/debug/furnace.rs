@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::{ray::RayHit, Color, Material, Point3, Ray, Vec3};
+
+/// The fraction of the light arriving at a material from one direction that it scatters, as
+/// measured by [`white_furnace()`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FurnaceResult {
+    /// The angle in degrees between the normal of the surface and the direction that the light
+    /// arrives from.
+    pub angle: f64,
+    /// The estimated fraction of the light that is scattered in each channel. A material that
+    /// conserves energy never scatters more than all of it.
+    pub albedo: Color,
+    /// The standard error of `albedo` in each channel.
+    pub error: Color,
+}
+
+impl FurnaceResult {
+    /// The number of standard errors by which the albedo must exceed `1` to be considered a gain
+    /// of energy rather than noise.
+    const SIGNIFICANCE: f64 = 4.;
+    /// The amount by which the albedo may exceed `1` to allow for rounding.
+    const TOLERANCE: f64 = 1e-6;
+
+    /// Checks whether the material scattered significantly more light than arrived in any
+    /// channel.
+    pub fn gains_energy(&self) -> bool {
+        [
+            (self.albedo.red(), self.error.red()),
+            (self.albedo.green(), self.error.green()),
+            (self.albedo.blue(), self.error.blue()),
+        ]
+        .into_iter()
+        .any(|(albedo, error)| albedo - Self::SIGNIFICANCE * error > 1. + Self::TOLERANCE)
+    }
+}
+
+/// The angles in degrees from the normal that [`white_furnace()`] sends light in at.
+const FURNACE_ANGLES: [f64; 7] = [0., 15., 30., 45., 60., 75., 89.];
+
+/// Runs a white furnace test on `material`: a flat surface made of it is surrounded by uniform
+/// white light, so every ray that it scatters carries back as much light as arrived, and the
+/// light seen along a ray is the fraction of it that the material scatters. Light is sent in at
+/// several angles from the normal, with `samples` rays scattered at each, and the estimated
+/// albedo at each angle is returned in order of increasing angle. The light that the material
+/// emits is ignored.
+pub fn white_furnace(material: &Arc<dyn Material>, samples: usize) -> Vec<FurnaceResult> {
+    let normal = Vec3::new(0., 1., 0.);
+    let hit_record = RayHit {
+        p: Point3::default(),
+        normal,
+        tangent: Vec3::new(1., 0., 0.),
+        material: Arc::clone(material),
+        object: 0,
+        t: 1.,
+        u: 0.5,
+        v: 0.5,
+        velocity: Vec3::default(),
+        curvature: 0.,
+    };
+    FURNACE_ANGLES
+        .iter()
+        .map(|&angle| {
+            let radians = angle.to_radians();
+            let direction = Vec3::new(radians.sin(), -radians.cos(), 0.);
+            let ray = Ray::new(hit_record.p - direction, direction);
+            let (mut sum, mut sum_of_squares) = ([0.; 3], [0.; 3]);
+            for _ in 0..samples {
+                if let Some(scattered) = material.scatter(&ray, &hit_record) {
+                    let attenuation = scattered.attenuation;
+                    let channels = [attenuation.red(), attenuation.green(), attenuation.blue()];
+                    for (channel, value) in channels.into_iter().enumerate() {
+                        sum[channel] += value;
+                        sum_of_squares[channel] += value * value;
+                    }
+                }
+            }
+            let count = samples.max(1) as f64;
+            let mean = sum.map(|sum| sum / count);
+            let error = [0, 1, 2].map(|channel| {
+                let variance = (sum_of_squares[channel] / count - mean[channel].powi(2)).max(0.);
+                (variance / count).sqrt()
+            });
+            FurnaceResult {
+                angle,
+                albedo: Color::new(mean[0], mean[1], mean[2]),
+                error: Color::new(error[0], error[1], error[2]),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,202 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    camera::Camera,
+    material::DiffuseLight,
+    object::{Cylinder, Sphere},
+    ray::Hittable,
+    rng, Color, Image, Point3, Scene, Vec3,
+};
+
+mod furnace;
+pub use furnace::{white_furnace, FurnaceResult};
+
+/// Records a random sample of the paths traced through a scene so that they can be inspected in
+/// an external 3D viewer.
+#[derive(Debug)]
+pub struct PathRecorder {
+    probability: f64,
+    max_paths: usize,
+    paths: Mutex<Vec<Vec<Point3>>>,
+}
+
+impl PathRecorder {
+    /// Creates a new recorder that keeps each traced path with probability `probability` until
+    /// `max_paths` paths have been kept.
+    pub fn new(probability: f64, max_paths: usize) -> Self {
+        Self {
+            probability: probability.clamp(0., 1.),
+            max_paths,
+            paths: Mutex::new(vec![]),
+        }
+    }
+
+    /// Decides whether the next path should be recorded.
+    pub fn should_record(&self) -> bool {
+        rng::random::<f64>() < self.probability
+            && self.paths.lock().expect("Path recorder was poisoned").len() < self.max_paths
+    }
+
+    /// Saves the vertices of a single path, starting with the point where it left the camera.
+    /// Paths with fewer than two vertices are discarded.
+    pub fn record(&self, path: Vec<Point3>) {
+        if path.len() < 2 {
+            return;
+        }
+        let mut paths = self.paths.lock().expect("Path recorder was poisoned");
+        if paths.len() < self.max_paths {
+            paths.push(path);
+        }
+    }
+
+    /// The number of paths that have been recorded so far.
+    pub fn len(&self) -> usize {
+        self.paths.lock().expect("Path recorder was poisoned").len()
+    }
+
+    /// Whether no paths have been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the recorded paths as a Wavefront OBJ file containing one polyline per path.
+    pub fn write_obj(&self, out: &mut dyn Write) -> io::Result<()> {
+        let paths = self.paths.lock().expect("Path recorder was poisoned");
+        writeln!(out, "# {} traced paths", paths.len())?;
+        for vertex in paths.iter().flatten() {
+            writeln!(out, "v {} {} {}", vertex.x(), vertex.y(), vertex.z())?;
+        }
+        let mut first_index = 1;
+        for path in paths.iter() {
+            write!(out, "l")?;
+            for index in first_index..first_index + path.len() {
+                write!(out, " {index}")?;
+            }
+            writeln!(out)?;
+            first_index += path.len();
+        }
+        Ok(())
+    }
+}
+
+/// Thin glowing guides that can be added to a scene to check its orientation and scale. The
+/// guides emit light, so they brighten nearby surfaces slightly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overlay {
+    /// Whether to draw unit-length axes from the origin. The x-axis is red, the y-axis is green,
+    /// and the z-axis is blue.
+    pub axes: bool,
+    /// If present, a grid of unit squares is drawn on the plane `y = 0` out to this distance from
+    /// the origin along the x- and z-axes.
+    pub grid_extent: Option<u32>,
+    /// The points at which to draw markers for light sources.
+    pub lights: Vec<Point3>,
+    /// The radius of the lines and half the radius of the light markers.
+    pub thickness: f64,
+}
+
+impl Overlay {
+    /// Adds the guides to `scene`.
+    pub fn add_to(&self, scene: &mut Scene) {
+        if self.axes {
+            for (axis, color) in [
+                (Vec3::new(1., 0., 0.), Color::new(1., 0., 0.)),
+                (Vec3::new(0., 1., 0.), Color::new(0., 1., 0.)),
+                (Vec3::new(0., 0., 1.), Color::new(0., 0., 1.)),
+            ] {
+                self.add_line(scene, Point3::default(), axis, 1., color);
+            }
+        }
+        if let Some(extent) = self.grid_extent {
+            let extent = extent as f64;
+            let length = 2. * extent;
+            let color = Color::new(0.5, 0.5, 0.5);
+            for offset in (-(extent as i64)..=extent as i64).map(|offset| offset as f64) {
+                let x_line_start = Point3::new(-extent, 0., offset);
+                self.add_line(scene, x_line_start, Vec3::new(1., 0., 0.), length, color);
+                let z_line_start = Point3::new(offset, 0., -extent);
+                self.add_line(scene, z_line_start, Vec3::new(0., 0., 1.), length, color);
+            }
+        }
+        let light_material = Arc::new(DiffuseLight::new(Color::new(1., 1., 0.)));
+        for &light in &self.lights {
+            scene.push_light(Sphere::new(
+                light,
+                2. * self.thickness,
+                Arc::clone(&light_material),
+            ));
+        }
+    }
+
+    fn add_line(&self, scene: &mut Scene, start: Point3, axis: Vec3, length: f64, color: Color) {
+        scene.push(Arc::new(Cylinder::new(
+            start,
+            axis,
+            self.thickness,
+            length,
+            true,
+            Arc::new(DiffuseLight::new(color)),
+        )));
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            axes: false,
+            grid_extent: None,
+            lights: vec![],
+            thickness: 0.01,
+        }
+    }
+}
+
+/// Highlights the parts of a preview image that the depth of field of a camera keeps sharp, so
+/// that the focus can be checked before spending time on a final render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FocusPreview {
+    /// The color that in-focus pixels are tinted toward.
+    pub tint: Color,
+    /// How far in-focus pixels are tinted, from `0` for not at all to `1` for entirely.
+    pub strength: f64,
+    /// The largest diameter in pixels that a point may be blurred into and still count as in
+    /// focus.
+    pub tolerance: f64,
+}
+
+impl FocusPreview {
+    /// Tints the pixels of `image` through which `camera` sees an in-focus surface of `scene`.
+    /// Row `0` of `image` must be the top of the view.
+    pub fn apply(&self, image: &mut Image, camera: &Camera, scene: &Scene) {
+        let (width, height) = (image.width(), image.height());
+        let time = 0.5 * (camera.shutter_open() + camera.shutter_close());
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f64 + 0.5) / width as f64;
+                let v = 1. - (y as f64 + 0.5) / height as f64;
+                let ray = camera.get_pinhole_ray(u, v, time);
+                let in_focus = scene
+                    .hit_by(&ray, camera.clip_range(&ray))
+                    .and_then(|hit| camera.blur_diameter(&hit.p))
+                    .is_some_and(|diameter| diameter * height as f64 <= self.tolerance);
+                if in_focus {
+                    let color = image.get(x, y).interpolate(&self.tint, self.strength);
+                    image.set(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+impl Default for FocusPreview {
+    fn default() -> Self {
+        Self {
+            tint: Color::new(0., 1., 0.),
+            strength: 0.5,
+            tolerance: 1.,
+        }
+    }
+}
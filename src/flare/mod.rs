@@ -0,0 +1,202 @@
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+use rayon::prelude::*;
+
+use crate::{Color, Image};
+
+/// The ghosts and streaks that a camera lens scatters from the bright lights in its view, drawn
+/// from a simple model of the lens. Light reflected back and forth between pairs of lens elements
+/// lands as dim, tinted copies of the lights along the line through the center of the image, and
+/// light diffracted by the edges of the aperture blades spreads each light into a star.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensFlare {
+    /// The brightness above which a pixel casts flares. Only the light above it is scattered.
+    pub threshold: f64,
+    /// The brightness of the flares relative to the light that casts them.
+    pub strength: f64,
+    /// The number of ghosts cast by each light, one for each pair of lens elements that reflect
+    /// it.
+    pub ghosts: u32,
+    /// How far apart the ghosts are, as a fraction of the distance from the light to the center
+    /// of the image. Ghosts past the center are flipped to the opposite side of it.
+    pub ghost_spacing: f64,
+    /// The number of blades of the aperture. An even number of blades casts that many streaks
+    /// and an odd number casts twice as many. With fewer than three the aperture is round and
+    /// casts no streaks.
+    pub aperture_blades: u32,
+    /// The length of each streak as a fraction of the height of the image.
+    pub streak_length: f64,
+}
+
+impl LensFlare {
+    /// Adds the flares cast by the bright pixels of `image`, which must hold linear colors.
+    pub fn apply(&self, image: &mut Image) {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let bright: Vec<Color> = image
+            .pixels()
+            .iter()
+            .map(|color| self.bright_part(color))
+            .collect();
+        // Ghosts are out of focus, so they are drawn from a blurred copy of the lights.
+        let blurred = blur(&bright, width, height, (height / 40).max(1));
+        let mut flare = self.ghosts(&blurred, width, height);
+        self.add_streaks(&mut flare, &bright, width, height);
+        for (index, flare) in flare.into_iter().enumerate() {
+            let (x, y) = ((index % width) as u32, (index / width) as u32);
+            image.set(x, y, image.get(x, y) + self.strength * flare);
+        }
+    }
+
+    /// Gets the part of `color` that is bright enough to cast flares.
+    fn bright_part(&self, color: &Color) -> Color {
+        let luminance = color.luminance();
+        if luminance > self.threshold {
+            (1. - self.threshold / luminance) * *color
+        } else {
+            Color::default()
+        }
+    }
+
+    /// Draws the ghosts of `lights` into a new image. Each pixel gathers the light of every ghost
+    /// that lands on it from the point that the ghost is a reflection of.
+    fn ghosts(&self, lights: &[Color], width: usize, height: usize) -> Vec<Color> {
+        let center = (width as f64 / 2., height as f64 / 2.);
+        let reach = center.0.hypot(center.1);
+        (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let (x, y) = ((index % width) as f64 + 0.5, (index / width) as f64 + 0.5);
+                (1..=self.ghosts).fold(Color::default(), |total, ghost| {
+                    // The ghost is a copy of the image scaled by this much around its center.
+                    let scale = 1. - f64::from(ghost) * self.ghost_spacing;
+                    if scale.abs() < 1e-3 {
+                        return total;
+                    }
+                    let source = (
+                        center.0 + (x - center.0) / scale,
+                        center.1 + (y - center.1) / scale,
+                    );
+                    // Light from near the edges of the view passes through the edges of the
+                    // elements, which reflect less of it back.
+                    let falloff = (1. - (source.0 - center.0).hypot(source.1 - center.1) / reach)
+                        .max(0.)
+                        .powi(2);
+                    let light = sample(lights, width, height, source);
+                    total + falloff * light.attenuate(&ghost_tint(ghost, self.ghosts))
+                })
+            })
+            .collect()
+    }
+
+    /// Spreads each pixel of `lights` along the streaks of the aperture and adds the streaks to
+    /// `flare`.
+    fn add_streaks(&self, flare: &mut [Color], lights: &[Color], width: usize, height: usize) {
+        let streaks = match self.aperture_blades {
+            0..=2 => return,
+            blades if blades % 2 == 0 => blades,
+            blades => 2 * blades,
+        };
+        let length = (self.streak_length * height as f64).max(1.) as usize;
+        let falloff: Vec<f64> = (1..=length)
+            .map(|step| (1. - step as f64 / (length + 1) as f64).powi(2))
+            .collect();
+        let total = falloff.iter().sum::<f64>() * f64::from(streaks);
+        let directions: Vec<(f64, f64)> = (0..streaks)
+            .map(|streak| {
+                let angle = FRAC_PI_2 + TAU * f64::from(streak) / f64::from(streaks);
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        for (index, light) in lights.iter().enumerate() {
+            if *light == Color::default() {
+                continue;
+            }
+            let (x, y) = ((index % width) as f64 + 0.5, (index / width) as f64 + 0.5);
+            for &(dx, dy) in &directions {
+                for (step, weight) in falloff.iter().enumerate() {
+                    let distance = (step + 1) as f64;
+                    let (sx, sy) = (x + distance * dx, y - distance * dy);
+                    if sx < 0. || sy < 0. || sx >= width as f64 || sy >= height as f64 {
+                        break;
+                    }
+                    flare[sy as usize * width + sx as usize] += (weight / total) * *light;
+                }
+            }
+        }
+    }
+}
+
+impl Default for LensFlare {
+    fn default() -> Self {
+        Self {
+            threshold: 1.,
+            strength: 0.1,
+            ghosts: 4,
+            ghost_spacing: 0.4,
+            aperture_blades: 6,
+            streak_length: 0.2,
+        }
+    }
+}
+
+/// Gets the tint that the coatings of the lens give to the ghost numbered `ghost` out of
+/// `ghosts`. The tints run through the hues so that neighboring ghosts differ.
+fn ghost_tint(ghost: u32, ghosts: u32) -> Color {
+    let hue = f64::from(ghost) / f64::from(ghosts.max(1));
+    let channel = |offset: f64| 0.5 + 0.5 * (TAU * (hue - offset)).cos();
+    Color::new(channel(0.), channel(1. / 3.), channel(2. / 3.))
+}
+
+/// Interpolates the color of `pixels` at the point `(x, y)` measured in pixels from the top-left
+/// corner of the image. Everything outside of the image is black.
+fn sample(pixels: &[Color], width: usize, height: usize, (x, y): (f64, f64)) -> Color {
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (left, top) = (x.floor(), y.floor());
+    let (tx, ty) = (x - left, y - top);
+    let pixel = |column: f64, row: f64| {
+        if column < 0. || row < 0. || column >= width as f64 || row >= height as f64 {
+            Color::default()
+        } else {
+            pixels[row as usize * width + column as usize]
+        }
+    };
+    let top_color = pixel(left, top).interpolate(&pixel(left + 1., top), tx);
+    let bottom_color = pixel(left, top + 1.).interpolate(&pixel(left + 1., top + 1.), tx);
+    top_color.interpolate(&bottom_color, ty)
+}
+
+/// Blurs `pixels` with three passes of a box filter that reaches `radius` pixels in each
+/// direction, which approximates a Gaussian blur.
+fn blur(pixels: &[Color], width: usize, height: usize, radius: usize) -> Vec<Color> {
+    let pass = |pixels: &[Color], horizontal: bool| -> Vec<Color> {
+        let (length, stride) = if horizontal {
+            (width, 1)
+        } else {
+            (height, width)
+        };
+        let scale = 1. / (2 * radius + 1) as f64;
+        (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let position = if horizontal {
+                    index % width
+                } else {
+                    index / width
+                };
+                let start = index - position * stride;
+                let range = position.saturating_sub(radius)..(position + radius + 1).min(length);
+                range.fold(Color::default(), |total, i| {
+                    total + scale * pixels[start + i * stride]
+                })
+            })
+            .collect()
+    };
+    let mut pixels = pixels.to_vec();
+    for _ in 0..3 {
+        pixels = pass(&pass(&pixels, true), false);
+    }
+    pixels
+}
@@ -0,0 +1,270 @@
+use std::{
+    io::{self, Write},
+    ops::RangeInclusive,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use ray_tracing::{
+    angle::Angle,
+    camera::{Camera, Orientation, Structure},
+    integrator::{Integrator, PathTracer},
+    material::{
+        Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal,
+        ScatterRecord,
+    },
+    object::{
+        Cylinder, DensityGrid, GridVolume, MovingSphere, Quadric, Rotate, Sphere, Torus, Translate,
+    },
+    ray::{Hittable, RayHit},
+    texture::Checker,
+    Color, Ray, Scene, Vec3,
+};
+
+/// The width and height of each fuzzed render in pixels.
+const RESOLUTION: u32 = 8;
+/// The number of rays traced through each pixel of a fuzzed render.
+const SAMPLES_PER_PIXEL: usize = 4;
+/// The maximum number of times that a ray can scatter in a fuzzed render.
+const MAX_DEPTH: usize = 8;
+
+/// Wraps a scene and records the first invalid hit that it reports.
+struct Checked<'a> {
+    scene: &'a Scene,
+    problem: Mutex<Option<String>>,
+}
+
+impl Checked<'_> {
+    fn report(&self, problem: String) {
+        self.problem
+            .lock()
+            .expect("Fuzzing problem was poisoned")
+            .get_or_insert(problem);
+    }
+}
+
+impl Hittable for Checked<'_> {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let hit = self.scene.hit_by(ray, valid_t.clone())?;
+        if !hit.t.is_finite() || !valid_t.contains(&hit.t) {
+            self.report(format!("t = {} outside of {valid_t:?} for {ray:?}", hit.t));
+        } else if !(hit.p.x().is_finite() && hit.p.y().is_finite() && hit.p.z().is_finite()) {
+            self.report(format!("Non-finite hit point {:?} for {ray:?}", hit.p));
+        } else if !hit.normal.length_squared().is_normal() {
+            self.report(format!("Degenerate normal {:?} for {ray:?}", hit.normal));
+        }
+        Some(hit)
+    }
+}
+
+/// A material chosen at runtime. Object constructors need a concrete material type, so this
+/// forwards to whichever material was picked.
+struct AnyMaterial(Arc<dyn Material>);
+
+impl Material for AnyMaterial {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        self.0.scatter(ray, hit_record)
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        self.0.emitted(ray, hit_record)
+    }
+
+    fn max_depth(&self) -> Option<usize> {
+        self.0.max_depth()
+    }
+
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+}
+
+fn random_vec(rng: &mut SmallRng, range: RangeInclusive<f64>) -> Vec3 {
+    Vec3::new(
+        rng.gen_range(range.clone()),
+        rng.gen_range(range.clone()),
+        rng.gen_range(range),
+    )
+}
+
+fn random_color(rng: &mut SmallRng) -> Color {
+    Color::new(rng.gen(), rng.gen(), rng.gen())
+}
+
+fn random_material(rng: &mut SmallRng) -> Arc<AnyMaterial> {
+    Arc::new(AnyMaterial(match rng.gen_range(0..6) {
+        0 => Arc::new(Lambertian::from_color(random_color(rng))),
+        1 => Arc::new(Lambertian::new(Arc::new(Checker::from_colors(
+            rng.gen_range(0.01..2.),
+            random_color(rng),
+            random_color(rng),
+        )))),
+        2 => Arc::new(Metal::rough_from_color(random_color(rng), rng.gen())),
+        // Indices below one are physically unusual but shouldn't break anything.
+        3 => Arc::new(Dielectric::new(rng.gen_range(0.5..3.))),
+        4 => Arc::new(DiffuseLight::new(
+            rng.gen_range(0.0..10.) * random_color(rng),
+        )),
+        _ => Arc::new(HenyeyGreenstein::new(
+            random_color(rng),
+            rng.gen_range(-1.0..=1.),
+        )),
+    }))
+}
+
+fn random_object(rng: &mut SmallRng) -> Arc<dyn Hittable> {
+    let center = random_vec(rng, -4.0..=4.);
+    let material = random_material(rng);
+    let object: Arc<dyn Hittable> = match rng.gen_range(0..7) {
+        // Negative radii are used for hollow glass.
+        0 => Arc::new(Sphere::new(center, rng.gen_range(-2.0..2.), material)),
+        1 => Arc::new(MovingSphere::new(
+            center,
+            0.,
+            center + random_vec(rng, -1.0..=1.),
+            1.,
+            rng.gen_range(0.01..2.),
+            material,
+        )),
+        2 => Arc::new(Cylinder::new(
+            center,
+            random_vec(rng, -1.0..=1.),
+            rng.gen_range(0.01..2.),
+            rng.gen_range(0.01..4.),
+            rng.gen(),
+            material,
+        )),
+        3 => {
+            let major_radius = rng.gen_range(0.1..2.);
+            Arc::new(Torus::new(
+                center,
+                random_vec(rng, -1.0..=1.),
+                major_radius,
+                rng.gen_range(0.01..major_radius),
+                material,
+            ))
+        }
+        4 => Arc::new(Quadric::ellipsoid(
+            center,
+            random_vec(rng, 0.01..=2.),
+            material,
+        )),
+        5 => {
+            let resolution = [2, 2, 2].map(|n| n + rng.gen_range(0..4));
+            let grid =
+                DensityGrid::from_fn(center, center + random_vec(rng, 0.1..=3.), resolution, {
+                    let scale = rng.gen_range(0.0..5.);
+                    move |p| scale * (p.x() + p.y() + p.z()).sin()
+                })
+                .expect("Fuzzed grids should have at least two samples along each axis");
+            Arc::new(GridVolume::new(
+                grid,
+                Arc::new(Isotropic::new(random_color(rng))),
+            ))
+        }
+        _ => Arc::new(Sphere::new(center, rng.gen_range(0.01..2.), material)),
+    };
+    match rng.gen_range(0..4) {
+        0 => Arc::new(Translate::new(object, random_vec(rng, -2.0..=2.))),
+        1 => Arc::new(Rotate::new(
+            object,
+            random_vec(rng, -1.0..=1.),
+            Angle::Degrees(rng.gen_range(-180.0..180.)),
+        )),
+        _ => object,
+    }
+}
+
+fn random_camera(rng: &mut SmallRng) -> Camera {
+    let origin = random_vec(rng, -10.0..=10.);
+    let look_at = random_vec(rng, -2.0..=2.);
+    Camera::new(
+        Orientation {
+            origin,
+            look_at,
+            up: Vec3::new(0., 1., 0.),
+        },
+        Structure {
+            vertical_fov: Angle::Degrees(rng.gen_range(1.0..150.)),
+            aspect_ratio: 1.,
+            aperture_width: rng.gen_range(0.0..1.),
+            focus_distance: (origin - look_at).length().max(0.1),
+            near_clip: 0.,
+            far_clip: f64::INFINITY,
+            shutter_open: 0.,
+            shutter_close: 1.,
+        },
+    )
+}
+
+/// Generates the scene and camera for a single fuzzing case. The same seed always produces the
+/// same scene.
+fn random_case(seed: u64) -> (Scene, Camera) {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut scene = Scene::default();
+    for _ in 0..rng.gen_range(1..=8) {
+        scene.push(random_object(&mut rng));
+    }
+    (scene, random_camera(&mut rng))
+}
+
+/// Renders a single case and describes the first problem found, if any.
+fn check_case(seed: u64) -> Option<String> {
+    let (scene, camera) = random_case(seed);
+    let checked = Checked {
+        scene: &scene,
+        problem: Mutex::new(None),
+    };
+    let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+        for j in 0..RESOLUTION {
+            for i in 0..RESOLUTION {
+                for _ in 0..SAMPLES_PER_PIXEL {
+                    let u = (i as f64 + ray_tracing::rng::random::<f64>()) / RESOLUTION as f64;
+                    let v = (j as f64 + ray_tracing::rng::random::<f64>()) / RESOLUTION as f64;
+                    let ray = camera.get_ray(u, v);
+                    let color = PathTracer::new(MAX_DEPTH).radiance(
+                        &ray,
+                        camera.clip_range(&ray),
+                        &checked,
+                        scene.background(),
+                        None,
+                    );
+                    if !(color.red().is_finite()
+                        && color.green().is_finite()
+                        && color.blue().is_finite())
+                    {
+                        checked.report(format!("Non-finite color {color:?} for {ray:?}"));
+                    }
+                }
+            }
+        }
+    }));
+    match rendered {
+        Ok(()) => checked
+            .problem
+            .into_inner()
+            .expect("Fuzzing problem was poisoned"),
+        Err(_) => Some("Rendering panicked".to_string()),
+    }
+}
+
+/// Renders `cases` randomized scenes at a tiny resolution and reports every scene that panics or
+/// produces invalid hits or colors. Case `i` uses the seed `seed + i`, so any failing case can be
+/// rerun on its own.
+pub fn fuzz_scenes(seed: u64, cases: u64) -> io::Result<()> {
+    let mut stderr = io::stderr().lock();
+    let mut failures = 0;
+    for case_seed in (0..cases).map(|i| seed.wrapping_add(i)) {
+        if let Some(problem) = check_case(case_seed) {
+            failures += 1;
+            writeln!(stderr, "Scene {case_seed} failed: {problem}")?;
+        }
+    }
+    writeln!(stderr, "{failures} of {cases} scenes failed")?;
+    if failures == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{failures} fuzzed scenes failed")))
+    }
+}
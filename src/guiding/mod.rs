@@ -0,0 +1,527 @@
+use std::{f64::consts::PI, ops::RangeInclusive, sync::OnceLock};
+
+use rayon::prelude::*;
+
+use crate::{
+    background::Background,
+    bvh::Aabb,
+    camera::Camera,
+    material::ScatterRecord,
+    ray::{Hittable, RayHit},
+    rng::random,
+    Color, Point3, Ray, Vec3,
+};
+
+/// A guide that learns where the light arriving at each part of a scene comes from and scatters
+/// paths toward those directions more often than their materials alone would, as in "Practical
+/// Path Guiding for Efficient Light-Transport Simulation" by Müller, Gross, and Novák (2017).
+/// This helps most where light reaches surfaces through narrow openings or from small bright
+/// areas that sampling the material rarely finds.
+///
+/// The guide is trained before rendering by tracing passes of paths from the camera, each twice
+/// as long as the one before, and learning from each pass where to guide the next. What it learns
+/// is kept in an SD-tree: a binary tree that splits space more finely where more paths pass
+/// through, with a quadtree at each leaf that splits the sphere of directions more finely where
+/// more light arrives from.
+///
+/// The training paths gather light the way a plain path tracer does. They don't read an
+/// [`IrradianceCache`](crate::irradiance::IrradianceCache) and don't leave out the caustics that
+/// a [`PhotonMap`](crate::photon::PhotonMap) carries, so a guide trained on them would point a
+/// path tracer that uses either toward light that it doesn't gather that way.
+#[derive(Debug)]
+pub struct PathGuide {
+    iterations: usize,
+    paths: usize,
+    /// What was learned in the last pass of training.
+    tree: OnceLock<SdTree>,
+}
+
+impl PathGuide {
+    /// The fraction of the scattered directions that are drawn from the guide instead of from the
+    /// material where the guide has learned anything.
+    const GUIDED_FRACTION: f64 = 0.5;
+
+    /// The most times that the paths traced for training may scatter.
+    const TRAINING_DEPTH: usize = 16;
+
+    /// Creates a new, untrained guide that learns over `iterations` passes, the first of which
+    /// traces `paths` paths from the camera.
+    pub fn new(iterations: usize, paths: usize) -> Self {
+        Self {
+            iterations,
+            paths,
+            tree: OnceLock::new(),
+        }
+    }
+
+    /// Gets the number of passes of paths that the guide learns over.
+    pub const fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Gets the number of paths traced in the first pass of training.
+    pub const fn paths(&self) -> usize {
+        self.paths
+    }
+
+    /// Checks whether the guide has been trained.
+    pub fn is_trained(&self) -> bool {
+        self.tree.get().is_some()
+    }
+
+    /// Trains the guide on paths traced through `world` from `camera`, lit by `background`. Only
+    /// the first call trains the guide, so it should be trained once the scene is complete.
+    /// Returns `false` if the guide was already trained.
+    pub fn train(
+        &self,
+        camera: &Camera,
+        world: &dyn Hittable,
+        background: &dyn Background,
+    ) -> bool {
+        if self.is_trained() {
+            return false;
+        }
+        let mut tree: Option<SdTree> = None;
+        for iteration in 0..self.iterations {
+            let records: Vec<Record> = (0..self.paths << iteration)
+                .into_par_iter()
+                .flat_map_iter(|_| {
+                    let ray = camera.get_ray(random(), random());
+                    let valid_t = camera.clip_range(&ray);
+                    let mut records = vec![];
+                    let mut trainer = Trainer {
+                        tree: tree.as_ref(),
+                        world,
+                        background,
+                        records: &mut records,
+                    };
+                    trainer.radiance(&ray, valid_t, Self::TRAINING_DEPTH);
+                    records
+                })
+                .collect();
+            // The first pass isn't guided, and only sets the bounds of the space that is learned.
+            let tree = tree.get_or_insert_with(|| {
+                SdTree::new(Aabb::around_points(records.iter().map(|record| record.p)))
+            });
+            for record in &records {
+                tree.record(record);
+            }
+            tree.refine(iteration);
+        }
+        tree.is_some_and(|tree| self.tree.set(tree).is_ok())
+    }
+
+    /// Scatters `ray` off of the material at `hit_record`, drawing the direction from what the
+    /// guide has learned about the light arriving there for a share of the rays. The guide only
+    /// changes how rays that the material would scatter in a direction with a density are
+    /// scattered, so mirror reflections and refractions through smooth glass are left alone, as
+    /// is everything before the guide is trained.
+    pub fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        match self.tree.get() {
+            Some(tree) => tree.scatter(ray, hit_record),
+            None => hit_record.material.scatter(ray, hit_record),
+        }
+    }
+}
+
+/// An estimate of the light arriving at a point from one direction.
+#[derive(Clone, Copy, Debug)]
+struct Record {
+    p: Point3,
+    /// The unit vector toward the light.
+    direction: Vec3,
+    /// The luminance of the light divided by the density with which the direction was chosen.
+    value: f64,
+}
+
+/// Traces paths for one pass of training, guided by what was learned in the pass before.
+struct Trainer<'a, 'r> {
+    tree: Option<&'a SdTree>,
+    world: &'a dyn Hittable,
+    background: &'a dyn Background,
+    records: &'r mut Vec<Record>,
+}
+
+impl Trainer<'_, '_> {
+    /// Computes the light carried back along `ray` with `depth` more scatterings allowed,
+    /// recording the light that arrives at each surface along the way.
+    fn radiance(&mut self, ray: &Ray, valid_t: RangeInclusive<f64>, depth: usize) -> Color {
+        if depth == 0 {
+            return Color::default();
+        }
+        let Some(hit_record) = self.world.hit_by(ray, valid_t) else {
+            return self.background.value(&ray.direction().normalized());
+        };
+        let emitted = hit_record.material.emitted(ray, &hit_record);
+        let scattered = match self.tree {
+            Some(tree) => tree.scatter(ray, &hit_record),
+            None => hit_record.material.scatter(ray, &hit_record),
+        };
+        let Some(ScatterRecord {
+            attenuation,
+            direction,
+            pdf,
+        }) = scattered
+        else {
+            return emitted;
+        };
+        let incoming = self.radiance(&direction, 0.001..=f64::INFINITY, depth - 1);
+        if let Some(pdf) = pdf.filter(|&pdf| pdf > 0.) {
+            self.records.push(Record {
+                p: hit_record.p,
+                direction: direction.direction().normalized(),
+                value: incoming.luminance() / pdf,
+            });
+        }
+        emitted + incoming.attenuate(&attenuation)
+    }
+}
+
+/// A binary tree over space with a distribution of directions at each leaf.
+#[derive(Clone, Debug)]
+struct SdTree {
+    /// A cube around every point that was recorded in the first pass.
+    bounds: Aabb,
+    /// The nodes of the tree, starting with the root. The children of a node at depth `d` split
+    /// it in half along axis `d % 3`.
+    nodes: Vec<SpatialNode>,
+}
+
+/// A node of an [`SdTree`].
+#[derive(Clone, Debug)]
+enum SpatialNode {
+    Inner { children: [usize; 2] },
+    Leaf(Leaf),
+}
+
+/// The directions of the light arriving in a region of space.
+#[derive(Clone, Debug)]
+struct Leaf {
+    /// The number of records added during the current pass.
+    samples: usize,
+    /// The light being recorded during the current pass.
+    learning: QuadTree,
+    /// The light recorded during the last pass, which rays are scattered by.
+    sampling: QuadTree,
+}
+
+impl SdTree {
+    /// The number of records that a leaf may get in a pass before it is split, for the first
+    /// pass. Each pass traces twice as many paths, so this grows by a factor of `sqrt(2)` per
+    /// pass to let the leaves grow finer at about the rate that the noise of each falls.
+    const SPLIT_THRESHOLD: f64 = 4000.;
+
+    /// Creates a new tree with a single leaf over a cube around `bounds`, or around the origin if
+    /// there are no bounds.
+    fn new(bounds: Option<Aabb>) -> Self {
+        let bounds = bounds.unwrap_or_else(|| Aabb::new(Point3::default(), Point3::default()));
+        let center = bounds.centroid();
+        let extent = bounds.max() - bounds.min();
+        let half = 0.5 * extent.x().max(extent.y()).max(extent.z()).max(1e-3) * 1.01;
+        let half = Vec3::new(half, half, half);
+        Self {
+            bounds: Aabb::new(center - half, center + half),
+            nodes: vec![SpatialNode::Leaf(Leaf {
+                samples: 0,
+                learning: QuadTree::default(),
+                sampling: QuadTree::default(),
+            })],
+        }
+    }
+
+    /// Finds the index of the leaf that contains `p`. Points outside of the bounds of the tree
+    /// belong to the nearest leaf.
+    fn leaf_index(&self, p: &Point3) -> usize {
+        let (mut min, mut max) = (self.bounds.min(), self.bounds.max());
+        let mut index = 0;
+        let mut depth = 0;
+        while let SpatialNode::Inner { children } = &self.nodes[index] {
+            let axis = depth % 3;
+            let middle = 0.5 * (min[axis] + max[axis]);
+            if p[axis] < middle {
+                max[axis] = middle;
+                index = children[0];
+            } else {
+                min[axis] = middle;
+                index = children[1];
+            }
+            depth += 1;
+        }
+        index
+    }
+
+    /// Gets the distribution of directions learned for the region around `p`, or `None` if no
+    /// light was recorded there.
+    fn distribution(&self, p: &Point3) -> Option<&QuadTree> {
+        match &self.nodes[self.leaf_index(p)] {
+            SpatialNode::Leaf(leaf) if leaf.sampling.total() > 0. => Some(&leaf.sampling),
+            _ => None,
+        }
+    }
+
+    /// Adds `record` to the light being learned for the region around its point.
+    fn record(&mut self, record: &Record) {
+        let index = self.leaf_index(&record.p);
+        if let SpatialNode::Leaf(leaf) = &mut self.nodes[index] {
+            leaf.samples += 1;
+            leaf.learning
+                .record(direction_to_square(&record.direction), record.value);
+        }
+    }
+
+    /// Ends the pass numbered `iteration`, counting from `0`, by making what was learned in it
+    /// the distribution that rays are scattered by and splitting the leaves that got many records
+    /// and the directions that brought much light.
+    fn refine(&mut self, iteration: usize) {
+        for node in &mut self.nodes {
+            if let SpatialNode::Leaf(leaf) = node {
+                leaf.sampling = std::mem::take(&mut leaf.learning);
+                leaf.learning = leaf.sampling.refined();
+            }
+        }
+        let threshold = Self::SPLIT_THRESHOLD * 2f64.powf(iteration as f64 / 2.);
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let leaf = match &mut self.nodes[index] {
+                SpatialNode::Inner { children } => {
+                    stack.extend(*children);
+                    continue;
+                }
+                SpatialNode::Leaf(leaf) => leaf,
+            };
+            if (leaf.samples as f64) <= threshold {
+                leaf.samples = 0;
+                continue;
+            }
+            // Each half is assumed to have gotten half of the records, and is split further if
+            // that is still too many.
+            leaf.samples /= 2;
+            let half = leaf.clone();
+            let children = [self.nodes.len(), self.nodes.len() + 1];
+            self.nodes.push(SpatialNode::Leaf(half.clone()));
+            self.nodes.push(SpatialNode::Leaf(half));
+            self.nodes[index] = SpatialNode::Inner { children };
+            stack.extend(children);
+        }
+    }
+
+    /// Scatters `ray` off of the material at `hit_record` as described by
+    /// [`PathGuide::scatter()`], combining the material and the guide by one-sample multiple
+    /// importance sampling.
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let scattered = hit_record.material.scatter(ray, hit_record)?;
+        let Some(material_pdf) = scattered.pdf else {
+            return Some(scattered);
+        };
+        let Some(distribution) = self.distribution(&hit_record.p) else {
+            return Some(scattered);
+        };
+        let fraction = PathGuide::GUIDED_FRACTION;
+        if random::<f64>() < fraction {
+            let (direction, guide_pdf) = distribution.sample();
+            let material_pdf = hit_record
+                .material
+                .scattering_pdf(ray, hit_record, &direction);
+            let pdf = fraction * guide_pdf + (1. - fraction) * material_pdf;
+            let bsdf = hit_record.material.bsdf(ray, hit_record, &direction);
+            Some(ScatterRecord {
+                attenuation: bsdf / pdf,
+                direction: ray.redirected(hit_record.p, direction),
+                pdf: Some(pdf),
+            })
+        } else {
+            let direction = scattered.direction.direction().normalized();
+            let pdf = fraction * distribution.pdf(&direction) + (1. - fraction) * material_pdf;
+            Some(ScatterRecord {
+                attenuation: scattered.attenuation * (material_pdf / pdf),
+                pdf: Some(pdf),
+                ..scattered
+            })
+        }
+    }
+}
+
+/// A quadtree over the unit square, which is mapped onto the sphere of directions by
+/// [`square_to_direction()`] without distorting areas. Each node holds the light recorded in each
+/// of its quadrants.
+#[derive(Clone, Debug)]
+struct QuadTree {
+    /// The nodes of the tree, starting with the root.
+    nodes: Vec<QuadNode>,
+}
+
+/// A node of a [`QuadTree`]. The quadrants are numbered with the bit `1` set in the upper half
+/// of the first coordinate and the bit `2` set in the upper half of the second.
+#[derive(Clone, Copy, Debug, Default)]
+struct QuadNode {
+    sums: [f64; 4],
+    /// The index of the node that splits each quadrant, or `0` if the quadrant isn't split.
+    children: [usize; 4],
+}
+
+impl Default for QuadTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![QuadNode::default()],
+        }
+    }
+}
+
+impl QuadTree {
+    /// The fraction of all of the recorded light that a quadrant must hold to be split.
+    const SPLIT_FRACTION: f64 = 0.01;
+
+    /// The deepest that the quadrants may be split.
+    const MAX_DEPTH: usize = 20;
+
+    /// Gets the total of the recorded light.
+    fn total(&self) -> f64 {
+        self.nodes[0].sums.iter().sum()
+    }
+
+    /// Finds the quadrant of a node that `point` lies in, and the position of `point` within that
+    /// quadrant scaled back up to the unit square.
+    fn quadrant(point: [f64; 2]) -> (usize, [f64; 2]) {
+        let [x, y] = point;
+        let (qx, x) = if x < 0.5 {
+            (0, 2. * x)
+        } else {
+            (1, 2. * x - 1.)
+        };
+        let (qy, y) = if y < 0.5 {
+            (0, 2. * y)
+        } else {
+            (2, 2. * y - 1.)
+        };
+        (qx | qy, [x, y])
+    }
+
+    /// Adds `value` to every quadrant that contains `point`.
+    fn record(&mut self, mut point: [f64; 2], value: f64) {
+        let mut index = 0;
+        loop {
+            let (quadrant, inner) = Self::quadrant(point);
+            let node = &mut self.nodes[index];
+            node.sums[quadrant] += value;
+            if node.children[quadrant] == 0 {
+                return;
+            }
+            index = node.children[quadrant];
+            point = inner;
+        }
+    }
+
+    /// Computes the density over the sphere of directions with which [`sample()`] chooses the
+    /// unit vector `direction`.
+    ///
+    /// [`sample()`]: Self::sample()
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let mut point = direction_to_square(direction);
+        let mut density = 1.;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let total: f64 = node.sums.iter().sum();
+            if total <= 0. {
+                break;
+            }
+            let (quadrant, inner) = Self::quadrant(point);
+            density *= 4. * node.sums[quadrant] / total;
+            if node.children[quadrant] == 0 {
+                break;
+            }
+            index = node.children[quadrant];
+            point = inner;
+        }
+        density / (4. * PI)
+    }
+
+    /// Chooses a unit vector with a density proportional to the recorded light, returning it
+    /// along with its density over the sphere of directions.
+    fn sample(&self) -> (Vec3, f64) {
+        let (mut origin, mut size) = ([0., 0.], 1.);
+        let mut density = 1.;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let total: f64 = node.sums.iter().sum();
+            if total <= 0. {
+                break;
+            }
+            let mut choice = random::<f64>() * total;
+            let quadrant = (0..3)
+                .find(|&quadrant| {
+                    choice -= node.sums[quadrant];
+                    choice < 0.
+                })
+                .unwrap_or(3);
+            density *= 4. * node.sums[quadrant] / total;
+            size /= 2.;
+            origin[0] += size * (quadrant & 1) as f64;
+            origin[1] += size * (quadrant >> 1) as f64;
+            if node.children[quadrant] == 0 {
+                break;
+            }
+            index = node.children[quadrant];
+        }
+        let point = [
+            origin[0] + size * random::<f64>(),
+            origin[1] + size * random::<f64>(),
+        ];
+        (square_to_direction(point), density / (4. * PI))
+    }
+
+    /// Creates an empty tree for the next pass, whose quadrants are split wherever they held
+    /// much of the light recorded in this tree.
+    fn refined(&self) -> Self {
+        let mut refined = Self::default();
+        let total = self.total();
+        if total > 0. {
+            self.refine_into(&mut refined, Some(0), self.nodes[0].sums, 0, 1, total);
+        }
+        refined
+    }
+
+    /// Splits the quadrants of the node `index` of `refined`, which lie at `depth` and held
+    /// `sums` of the light in this tree, as the node `old` of this tree if it has one.
+    fn refine_into(
+        &self,
+        refined: &mut Self,
+        old: Option<usize>,
+        sums: [f64; 4],
+        index: usize,
+        depth: usize,
+        total: f64,
+    ) {
+        for (quadrant, &sum) in sums.iter().enumerate() {
+            if sum / total <= Self::SPLIT_FRACTION || depth >= Self::MAX_DEPTH {
+                continue;
+            }
+            let old = old
+                .map(|old| self.nodes[old].children[quadrant])
+                .filter(|&child| child != 0);
+            // Quadrants that weren't split before are assumed to have held their light evenly.
+            let child_sums = old.map_or([sum / 4.; 4], |old| self.nodes[old].sums);
+            let child = refined.nodes.len();
+            refined.nodes.push(QuadNode::default());
+            refined.nodes[index].children[quadrant] = child;
+            self.refine_into(refined, old, child_sums, child, depth + 1, total);
+        }
+    }
+}
+
+/// Maps a point of the unit square onto the unit sphere by the cylindrical projection, which
+/// preserves areas. The first coordinate gives the height and the second the longitude.
+fn square_to_direction([x, y]: [f64; 2]) -> Vec3 {
+    let cos_theta = 2. * x - 1.;
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = 2. * PI * y;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Maps the unit vector `direction` onto the unit square, undoing [`square_to_direction()`].
+fn direction_to_square(direction: &Vec3) -> [f64; 2] {
+    let x = (0.5 * (direction.z() + 1.)).clamp(0., 1.);
+    let y = direction.y().atan2(direction.x()) / (2. * PI);
+    [x, if y < 0. { y + 1. } else { y }.min(1. - f64::EPSILON)]
+}
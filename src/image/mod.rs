@@ -0,0 +1,474 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use crate::{Color, Vec3};
+
+/// A rectangular grid of pixels stored in memory. Row `0` is the top of the image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    /// Creates a new black image with the given dimensions.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width as usize * height as usize],
+        }
+    }
+
+    /// Creates a new image from its pixels in row-major order starting from the top-left corner.
+    /// Returns `None` if the number of pixels doesn't match the dimensions.
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Color>) -> Option<Self> {
+        (pixels.len() == width as usize * height as usize).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Gets the width of the image in pixels.
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Gets the height of the image in pixels.
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gets the pixels of the image in row-major order starting from the top-left corner.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Gets the color of the pixel in column `x` and row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside of the image.
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Sets the color of the pixel in column `x` and row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is outside of the image.
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "Pixel ({x}, {y}) is outside of a {}x{} image",
+            self.width,
+            self.height
+        );
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Reads an image in either the plain (`P3`) or raw (`P6`) PPM format. Channels are scaled so
+    /// that the maximum value declared by the file is `1.0`.
+    pub fn read_ppm(input: &mut dyn BufRead) -> io::Result<Self> {
+        let mut bytes = vec![];
+        input.read_to_end(&mut bytes)?;
+        let mut parser = PpmParser {
+            bytes: &bytes,
+            position: 0,
+        };
+        let magic = parser.token()?;
+        let raw = match magic {
+            b"P3" => false,
+            b"P6" => true,
+            _ => return Err(invalid_data("Not a PPM file")),
+        };
+        let width = parser.number()?;
+        let height = parser.number()?;
+        let max_value = parser.number()?;
+        if max_value == 0 || max_value > u16::MAX as u32 {
+            return Err(invalid_data("Invalid maximum channel value"));
+        }
+        // Every value takes up at least one byte, so an image can't have more values than there
+        // are bytes left. Checking this first keeps a bogus size from overflowing.
+        let num_values = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .filter(|&num_values| num_values <= parser.bytes.len() - parser.position)
+            .ok_or_else(|| invalid_data("Image is larger than its data"))?;
+        let values = if raw {
+            // Exactly one whitespace byte separates the header from the raster.
+            parser.position += 1;
+            let sample_size = if max_value > 255 { 2 } else { 1 };
+            let raster = parser
+                .bytes
+                .get(parser.position..)
+                .and_then(|rest| rest.get(..num_values * sample_size))
+                .ok_or_else(|| invalid_data("Truncated raster"))?;
+            raster
+                .chunks(sample_size)
+                .map(|sample| sample.iter().fold(0, |acc, &b| acc << 8 | u32::from(b)))
+                .collect::<Vec<_>>()
+        } else {
+            (0..num_values)
+                .map(|_| parser.number())
+                .collect::<io::Result<Vec<_>>>()?
+        };
+        let scale = f64::from(max_value).recip();
+        let pixels = values
+            .chunks(3)
+            .map(|rgb| {
+                Color::new(
+                    f64::from(rgb[0]) * scale,
+                    f64::from(rgb[1]) * scale,
+                    f64::from(rgb[2]) * scale,
+                )
+            })
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Reads a high dynamic range image in the Radiance RGBE (`.hdr`) format. Both flat and
+    /// run-length encoded scanlines are supported, but only the standard `-Y <height> +X <width>`
+    /// orientation is.
+    pub fn read_hdr(input: &mut dyn BufRead) -> io::Result<Self> {
+        let mut line = vec![];
+        input.read_until(b'\n', &mut line)?;
+        if !(line.starts_with(b"#?RADIANCE") || line.starts_with(b"#?RGBE")) {
+            return Err(invalid_data("Not a Radiance HDR file"));
+        }
+        // The header is a list of variables terminated by an empty line.
+        loop {
+            line.clear();
+            if input.read_until(b'\n', &mut line)? == 0 {
+                return Err(invalid_data("Truncated header"));
+            }
+            match line.trim_ascii() {
+                b"" => break,
+                b"FORMAT=32-bit_rle_rgbe" => {}
+                format if format.starts_with(b"FORMAT=") => {
+                    return Err(invalid_data("Unsupported pixel format"))
+                }
+                _ => {}
+            }
+        }
+        line.clear();
+        input.read_until(b'\n', &mut line)?;
+        let resolution = std::str::from_utf8(&line)
+            .map_err(|_| invalid_data("Invalid resolution"))?
+            .split_ascii_whitespace()
+            .collect::<Vec<_>>();
+        let (height, width) = match resolution[..] {
+            ["-Y", height, "+X", width] => (
+                height
+                    .parse::<u32>()
+                    .map_err(|_| invalid_data("Invalid height"))?,
+                width
+                    .parse::<u32>()
+                    .map_err(|_| invalid_data("Invalid width"))?,
+            ),
+            _ => return Err(invalid_data("Unsupported image orientation")),
+        };
+        let mut raster = vec![];
+        input.read_to_end(&mut raster)?;
+        // Checking that the raster is long enough to hold the image first keeps a bogus size
+        // from overflowing or asking for more memory than there is.
+        let columns = width as usize;
+        // Each channel of an encoded scanline takes at least two bytes for every run of up to 127
+        // pixels.
+        let scanline_bytes = if RLE_WIDTHS.contains(&columns) {
+            Some(4 * 2 * columns.div_ceil(127))
+        } else {
+            columns.checked_mul(4)
+        };
+        if scanline_bytes
+            .and_then(|bytes| bytes.checked_mul(height as usize))
+            .is_none_or(|bytes| bytes > raster.len())
+        {
+            return Err(invalid_data("Image is larger than its data"));
+        }
+        let mut raster = &raster[..];
+        let mut pixels = vec![];
+        let mut scanline = vec![[0u8; 4]; columns];
+        for _ in 0..height {
+            read_hdr_scanline(&mut raster, &mut scanline)?;
+            pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+                if e == 0 {
+                    Color::default()
+                } else {
+                    let scale = 2f64.powi(i32::from(e) - (128 + 8));
+                    Color::new(
+                        (f64::from(r) + 0.5) * scale,
+                        (f64::from(g) + 0.5) * scale,
+                        (f64::from(b) + 0.5) * scale,
+                    )
+                }
+            }));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Writes the image in the plain (`P3`) PPM format. The channels are written as they are,
+    /// without any gamma correction.
+    pub fn write_ppm(&self, out: &mut dyn Write) -> io::Result<()> {
+        PpmSink::new(out, self.width, self.height)?.write_pixels(&self.pixels)
+    }
+}
+
+/// A destination for an image that receives its pixels a few at a time in row-major order starting
+/// from the top-left corner, so that the whole image never needs to be in memory at once.
+pub trait ImageSink {
+    /// Writes the next pixels of the image.
+    fn write_pixels(&mut self, pixels: &[Color]) -> io::Result<()>;
+}
+
+/// Streams an image in the plain (`P3`) PPM format. The channels are written as they are, without
+/// any gamma correction. The output is flushed after every batch of pixels.
+#[derive(Debug)]
+pub struct PpmSink<W> {
+    out: W,
+    width: u32,
+    height: u32,
+    remaining_pixels: usize,
+    header: Option<Header<W>>,
+}
+
+/// Where the header of a [`PpmSink`] that keeps it up to date is, and how to rewrite it.
+#[derive(Debug)]
+struct Header<W> {
+    start: u64,
+    rows: u32,
+    rewrite: fn(&mut W, u64, u32, u32, u32) -> io::Result<()>,
+}
+
+impl<W> PpmSink<W>
+where
+    W: Write,
+{
+    /// Writes the header of a `width` by `height` image to `out` and prepares to write its pixels.
+    pub fn new(mut out: W, width: u32, height: u32) -> io::Result<Self> {
+        write_ppm_header(&mut out, width, height, height)?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            remaining_pixels: width as usize * height as usize,
+            header: None,
+        })
+    }
+
+    /// The number of pixels that still need to be written to complete the image.
+    pub const fn remaining_pixels(&self) -> usize {
+        self.remaining_pixels
+    }
+}
+
+impl<W> PpmSink<W>
+where
+    W: Write + Seek,
+{
+    /// Like [`new()`], but the height in the header only counts the rows that have been written
+    /// so far and is rewritten after each batch of pixels, so the output is a valid image of the
+    /// finished rows even if the rest are never written. Outputs that can't seek, such as pipes,
+    /// get the full height in the header as with `new()`.
+    ///
+    /// [`new()`]: Self::new()
+    pub fn crash_safe(mut out: W, width: u32, height: u32) -> io::Result<Self> {
+        let Ok(start) = out.stream_position() else {
+            return Self::new(out, width, height);
+        };
+        write_ppm_header(&mut out, width, 0, height)?;
+        out.flush()?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            remaining_pixels: width as usize * height as usize,
+            header: Some(Header {
+                start,
+                rows: 0,
+                rewrite: |out, start, width, rows, height| {
+                    out.seek(SeekFrom::Start(start))?;
+                    write_ppm_header(out, width, rows, height)?;
+                    out.seek(SeekFrom::End(0))?;
+                    Ok(())
+                },
+            }),
+        })
+    }
+}
+
+/// Writes the header of a PPM image that is `width` pixels wide and `rows` pixels tall. `rows` is
+/// padded to as many digits as `height` so that the header can be rewritten in place as the
+/// image grows.
+fn write_ppm_header(out: &mut dyn Write, width: u32, rows: u32, height: u32) -> io::Result<()> {
+    let digits = height.to_string().len();
+    writeln!(out, "P3")?;
+    writeln!(out, "{width} {rows:>digits$}")?;
+    writeln!(out, "255")
+}
+
+impl<W> ImageSink for PpmSink<W>
+where
+    W: Write,
+{
+    fn write_pixels(&mut self, pixels: &[Color]) -> io::Result<()> {
+        if pixels.len() > self.remaining_pixels {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Too many pixels were written to the image",
+            ));
+        }
+        let mut text = String::new();
+        for color in pixels {
+            writeln!(text, "{color}").expect("Writing to a String can't fail");
+        }
+        self.out.write_all(text.as_bytes())?;
+        self.remaining_pixels -= pixels.len();
+        if let Some(header) = &mut self.header {
+            let total = self.width as usize * self.height as usize;
+            let rows = ((total - self.remaining_pixels) / self.width.max(1) as usize) as u32;
+            if rows != header.rows {
+                (header.rewrite)(&mut self.out, header.start, self.width, rows, self.height)?;
+                header.rows = rows;
+            }
+        }
+        self.out.flush()
+    }
+}
+
+/// Writes a `width` by `height` image in the color Portable Float Map (`PF`) format, which is able
+/// to hold negative and unbounded values. `pixels` are in row-major order starting from the
+/// top-left corner and each component of a pixel is stored in one channel.
+pub fn write_pfm(out: &mut dyn Write, width: u32, height: u32, pixels: &[Vec3]) -> io::Result<()> {
+    if pixels.len() != width as usize * height as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The number of pixels doesn't match the dimensions of the image",
+        ));
+    }
+    writeln!(out, "PF")?;
+    writeln!(out, "{width} {height}")?;
+    // A negative scale marks the data as little-endian.
+    writeln!(out, "-1.0")?;
+    // PFM rasters start from the bottom row.
+    for row in pixels.chunks(width.max(1) as usize).rev() {
+        for pixel in row {
+            for channel in [pixel.x(), pixel.y(), pixel.z()] {
+                out.write_all(&(channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads one scanline of RGBE pixels, which may be flat or run-length encoded.
+/// The widths of the scanlines of a Radiance HDR file that may be run-length encoded.
+const RLE_WIDTHS: Range<usize> = 8..0x8000;
+
+fn read_hdr_scanline(input: &mut dyn BufRead, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let Some(first) = scanline.first_mut() else {
+        return Ok(());
+    };
+    input.read_exact(first)?;
+    let encoded = first[0] == 2
+        && first[1] == 2
+        && usize::from(first[2]) << 8 | usize::from(first[3]) == width
+        && RLE_WIDTHS.contains(&width);
+    if !encoded {
+        for pixel in &mut scanline[1..] {
+            input.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    // Run-length encoded scanlines store each channel separately.
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0];
+            input.read_exact(&mut count)?;
+            let (count, run) = match count[0] {
+                count if count > 128 => (usize::from(count - 128), true),
+                count => (usize::from(count), false),
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid_data("Invalid run length"));
+            }
+            if run {
+                let mut value = [0];
+                input.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                for pixel in &mut scanline[x..x + count] {
+                    let mut value = [0];
+                    input.read_exact(&mut value)?;
+                    pixel[channel] = value[0];
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Splits the header and plain raster of a PPM file into tokens.
+struct PpmParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PpmParser<'a> {
+    /// Reads the next whitespace-delimited token, skipping comments.
+    fn token(&mut self) -> io::Result<&'a [u8]> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(b'#') => {
+                    while !matches!(self.bytes.get(self.position), None | Some(b'\n')) {
+                        self.position += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.position += 1,
+                Some(_) => break,
+                None => return Err(invalid_data("Unexpected end of file")),
+            }
+        }
+        let start = self.position;
+        while matches!(self.bytes.get(self.position), Some(b) if !b.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        Ok(&self.bytes[start..self.position])
+    }
+
+    /// Reads the next token as a decimal number.
+    fn number(&mut self) -> io::Result<u32> {
+        std::str::from_utf8(self.token()?)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid_data("Expected a number"))
+    }
+}
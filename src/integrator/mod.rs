@@ -0,0 +1,609 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    background::Background,
+    guiding::PathGuide,
+    irradiance::IrradianceCache,
+    material::ScatterRecord,
+    object::Sphere,
+    photon::PhotonMap,
+    ray::{Hittable, RayHit},
+    rng::random,
+    Color, Point3, Ray, Vec3,
+};
+
+/// A way of computing the light that reaches the camera along a ray. Path tracing gives the
+/// final image, while other integrators show simplified or partial views of a scene that are
+/// useful for previewing and debugging it.
+pub trait Integrator: Send + Sync {
+    /// Computes the light carried back along `ray` from the first object of `world` that it hits
+    /// within `valid_t`, or from `background` if it hits nothing. If `path` is present, each point
+    /// that the light is traced through is appended to it.
+    fn radiance(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color;
+
+    /// The name of the integrator.
+    fn name(&self) -> &'static str;
+}
+
+impl Debug for dyn Integrator + '_ {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Integrator")
+            .field("name", &self.name())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An integrator that follows each ray as it scatters from surface to surface, gathering the
+/// light emitted along the way, until it escapes the scene or has scattered too many times.
+#[derive(Clone, Copy, Debug)]
+pub struct PathTracer<'a> {
+    max_depth: usize,
+    irradiance_cache: Option<&'a IrradianceCache>,
+    photon_map: Option<&'a PhotonMap>,
+    path_guide: Option<&'a PathGuide>,
+    splits: usize,
+}
+
+/// How far a path traced by a [`PathTracer`] has gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct PathState {
+    /// The number of times that the path has scattered.
+    bounces: usize,
+    /// How far the path has gone along a caustic.
+    caustic: CausticPath,
+}
+
+/// How far a path has gone along a caustic that the photon map of a [`PathTracer`] already
+/// carries the light of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CausticPath {
+    /// The path isn't on a caustic.
+    None,
+    /// The path was just scattered off of a diffuse surface whose caustics were gathered from the
+    /// photon map.
+    Diffuse,
+    /// The path has only been reflected or refracted specularly within the target of the photon
+    /// map since scattering off of a diffuse surface whose caustics were gathered from the map,
+    /// so the background that it reaches was already counted.
+    Specular,
+}
+
+impl<'a> PathTracer<'a> {
+    /// Creates a new path tracer that considers a ray to be absorbed once it has scattered
+    /// `max_depth` times.
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            irradiance_cache: None,
+            photon_map: None,
+            path_guide: None,
+            splits: 1,
+        }
+    }
+
+    /// Interpolates the light reflected by diffuse surfaces from `cache` instead of tracing it at
+    /// every hit, if `cache` is present.
+    pub const fn with_irradiance_cache(self, cache: Option<&'a IrradianceCache>) -> Self {
+        Self {
+            irradiance_cache: cache,
+            ..self
+        }
+    }
+
+    /// Gathers the caustics that the background casts onto diffuse surfaces from `map` instead of
+    /// tracing them, if `map` is present. The photons of the map must be traced before rendering.
+    pub const fn with_photon_map(self, map: Option<&'a PhotonMap>) -> Self {
+        Self {
+            photon_map: map,
+            ..self
+        }
+    }
+
+    /// Scatters rays in the directions that `guide` has learned that light arrives from more often,
+    /// if `guide` is present. The guide must be trained before rendering. The guide learns from
+    /// plain paths, so it is a poor fit for a tracer that takes light from an irradiance cache or
+    /// leaves out the caustics of a photon map.
+    pub const fn with_path_guide(self, guide: Option<&'a PathGuide>) -> Self {
+        Self {
+            path_guide: guide,
+            ..self
+        }
+    }
+
+    /// Scatters `splits` rays from the first surface that each camera ray hits and averages the
+    /// light that they carry back, instead of only one. This cuts the noise of glossy surfaces
+    /// seen directly by the camera for less than the cost of tracing `splits` times as many
+    /// camera rays, since the rays scattered after the first bounce aren't split. A `splits` of
+    /// `0` is treated as `1`.
+    pub const fn with_splitting(self, splits: usize) -> Self {
+        Self {
+            splits: if splits == 0 { 1 } else { splits },
+            ..self
+        }
+    }
+
+    /// Gets the number of times that a ray can scatter before it is considered to be absorbed.
+    pub const fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Gets the number of rays scattered from the first surface that each camera ray hits.
+    pub const fn splits(&self) -> usize {
+        self.splits
+    }
+
+    /// Computes the light carried back along `ray`, which was cast after the path had gone as far
+    /// as `state`.
+    fn trace(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        state: PathState,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        let hit_record = world.hit_by(ray, valid_t);
+        self.hit_color(ray, hit_record, world, background, state, path)
+    }
+
+    /// Computes the light carried back along `ray`, which hit `hit_record` or nothing, as
+    /// described by [`trace()`].
+    ///
+    /// [`trace()`]: Self::trace()
+    fn hit_color(
+        &self,
+        ray: &Ray,
+        hit_record: Option<RayHit>,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        PathState { bounces, caustic }: PathState,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        match hit_record {
+            None => {
+                let light = sky(ray, background, path);
+                if caustic == CausticPath::Specular {
+                    Color::default()
+                } else {
+                    light
+                }
+            }
+            Some(hit_record) => {
+                let mut path = path;
+                if let Some(path) = path.as_deref_mut() {
+                    path.push(hit_record.p);
+                }
+                let emitted = hit_record.material.emitted(ray, &hit_record);
+                let caustics = self
+                    .photon_map
+                    .zip(hit_record.material.diffuse_albedo(&hit_record))
+                    .map(|(map, albedo)| {
+                        map.lookup(&hit_record.p, &facing_normal(ray, &hit_record))
+                            .attenuate(&albedo)
+                    });
+                let emitted = emitted + caustics.unwrap_or_default();
+                // Materials may give the paths that scatter off of them a limit of their own.
+                let max_depth = hit_record.material.max_depth().unwrap_or(self.max_depth);
+                let traced = bounces + 1 < max_depth;
+                if let Some(cache) = self.irradiance_cache.filter(|_| traced) {
+                    if let Some(albedo) = hit_record.material.diffuse_albedo(&hit_record) {
+                        let incoming =
+                            self.cached_light(cache, ray, &hit_record, world, background, bounces);
+                        return emitted + incoming.attenuate(&albedo);
+                    }
+                }
+                // Only the first bounce is split, so the number of rays doesn't grow with depth.
+                let splits = if bounces == 0 { self.splits } else { 1 };
+                let mut scattered = Color::default();
+                for split in 0..splits {
+                    // Only the first of the split rays is recorded.
+                    let path = path.as_deref_mut().filter(|_| split == 0);
+                    let scatter = match self.path_guide {
+                        Some(guide) => guide.scatter(ray, &hit_record),
+                        None => hit_record.material.scatter(ray, &hit_record),
+                    };
+                    scattered += scatter
+                        .filter(|_| traced)
+                        .map(
+                            |ScatterRecord {
+                                 attenuation,
+                                 direction,
+                                 pdf,
+                             }| {
+                                // The photon map only carries the caustics whose specular
+                                // bounces all lie within its target.
+                                let covered =
+                                    self.photon_map.is_some_and(|map| map.covers(&hit_record.p));
+                                let caustic = match (caustics, pdf, caustic) {
+                                    (Some(_), _, _) => CausticPath::Diffuse,
+                                    (None, None, CausticPath::Diffuse | CausticPath::Specular)
+                                        if covered =>
+                                    {
+                                        CausticPath::Specular
+                                    }
+                                    (None, _, _) => CausticPath::None,
+                                };
+                                self.trace(
+                                    &direction,
+                                    0.001..=f64::INFINITY,
+                                    world,
+                                    background,
+                                    PathState {
+                                        bounces: bounces + 1,
+                                        caustic,
+                                    },
+                                    path,
+                                )
+                                .attenuate(&attenuation)
+                            },
+                        )
+                        .unwrap_or_default();
+                }
+                emitted + scattered / splits as f64
+            }
+        }
+    }
+
+    /// Gets the light arriving at the diffuse surface that `ray` hit at `hit_record` from `cache`,
+    /// or estimates it and saves it in `cache` if there aren't any estimates close enough to the
+    /// hit. The rays traced for the estimate don't use the cache, so at most one surface along
+    /// each path is interpolated. The caustics of the photon map are left out of the estimate.
+    fn cached_light(
+        &self,
+        cache: &IrradianceCache,
+        ray: &Ray,
+        hit_record: &RayHit,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        bounces: usize,
+    ) -> Color {
+        let normal = facing_normal(ray, hit_record);
+        if let Some(light) = cache.lookup(&hit_record.p, &normal) {
+            return light;
+        }
+        let uncached = self.with_irradiance_cache(None);
+        let caustic = if self.photon_map.is_some() {
+            CausticPath::Diffuse
+        } else {
+            CausticPath::None
+        };
+        let mut total = Color::default();
+        let mut inverse_distances = 0.;
+        for _ in 0..cache.samples() {
+            let mut direction = normal + Vec3::random_unit_vector();
+            if direction.near_zero() {
+                direction = normal;
+            }
+            let sample = ray.redirected(hit_record.p, direction);
+            let hit = world.hit_by(&sample, 0.001..=f64::INFINITY);
+            if let Some(hit) = &hit {
+                inverse_distances += 1. / (hit.t * direction.length());
+            }
+            let state = PathState {
+                bounces: bounces + 1,
+                caustic,
+            };
+            total += uncached.hit_color(&sample, hit, world, background, state, None);
+        }
+        let light = total / cache.samples() as f64;
+        cache.insert(
+            hit_record.p,
+            normal,
+            light,
+            cache.samples() as f64 / inverse_distances,
+        );
+        light
+    }
+}
+
+impl Integrator for PathTracer<'_> {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        if self.max_depth == 0 {
+            return Color::new(0., 0., 0.);
+        }
+        let state = PathState {
+            bounces: 0,
+            caustic: CausticPath::None,
+        };
+        self.trace(ray, valid_t, world, background, state, path)
+    }
+
+    fn name(&self) -> &'static str {
+        "path tracer"
+    }
+}
+
+/// An integrator that only gathers the light that reaches the surfaces seen by the camera
+/// directly from the background and from glowing objects, without the light that bounces between
+/// surfaces on the way. This shows roughly how a scene is lit in a fraction of the time that
+/// [`PathTracer`] takes. Mirror reflections and refractions through smooth glass don't count as
+/// bounces, so the surfaces seen in mirrors and through glass are lit in the same way.
+///
+/// The light of the glowing spheres given to [`with_lights()`](Self::with_lights()) is gathered
+/// both by aiming rays at the spheres and by scattering rays off of the surfaces, and the two are
+/// weighted by multiple importance sampling, so small, bright lights are found without as much
+/// noise as scattering alone gives.
+#[derive(Clone, Debug)]
+pub struct DirectLighting {
+    max_depth: usize,
+    /// The glowing spheres that rays are aimed at.
+    lights: Vec<Sphere>,
+}
+
+impl DirectLighting {
+    /// Creates a new direct lighting integrator that considers a ray to be absorbed once it has
+    /// scattered `max_depth` times, counting mirror reflections and refractions.
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            lights: Vec::new(),
+        }
+    }
+
+    /// Aims rays at `lights`, which should be the glowing spheres of the scene. Leaving a sphere
+    /// out only makes its light noisier, and glowing objects of other shapes are still found by
+    /// scattering.
+    pub fn with_lights(self, lights: Vec<Sphere>) -> Self {
+        Self { lights, ..self }
+    }
+
+    /// Gets the number of times that a ray can scatter before it is considered to be absorbed.
+    pub const fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Computes the probability density per unit of solid angle with which
+    /// [`aimed_light()`](Self::aimed_light()) aims a ray from `from` toward `direction`.
+    fn light_pdf(&self, from: Point3, direction: &Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.;
+        }
+        self.lights
+            .iter()
+            .map(|light| light.solid_angle_pdf(from, direction))
+            .sum::<f64>()
+            / self.lights.len() as f64
+    }
+
+    /// Gets the light that arrives at the surface that `ray` hit at `hit_record` along a ray aimed
+    /// at one of the lights, weighted against scattering the ray in the same direction.
+    fn aimed_light(
+        &self,
+        ray: &Ray,
+        hit_record: &RayHit,
+        world: &dyn Hittable,
+        background: &dyn Background,
+    ) -> Color {
+        if self.lights.is_empty() {
+            return Color::default();
+        }
+        let light = &self.lights[random::<usize>() % self.lights.len()];
+        let Some(direction) = light.sample_solid_angle(hit_record.p) else {
+            return Color::default();
+        };
+        let bsdf = hit_record.material.bsdf(ray, hit_record, &direction);
+        if bsdf == Color::default() {
+            return bsdf;
+        }
+        let pdf = self.light_pdf(hit_record.p, &direction)
+            + hit_record
+                .material
+                .scattering_pdf(ray, hit_record, &direction);
+        let aimed = ray.redirected(hit_record.p, direction);
+        gathered_light(&aimed, world, background, None).attenuate(&bsdf) / pdf
+    }
+
+    /// Computes the light carried back along `ray`, which has already scattered `bounces` times.
+    fn trace(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        bounces: usize,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        let Some(hit_record) = world.hit_by(ray, valid_t) else {
+            return sky(ray, background, path);
+        };
+        let mut path = path;
+        if let Some(path) = path.as_deref_mut() {
+            path.push(hit_record.p);
+        }
+        let emitted = hit_record.material.emitted(ray, &hit_record);
+        // Materials may give the paths that scatter off of them a limit of their own.
+        let max_depth = hit_record.material.max_depth().unwrap_or(self.max_depth);
+        if bounces >= max_depth {
+            return emitted;
+        }
+        let aimed = self.aimed_light(ray, &hit_record, world, background);
+        let scattered = hit_record
+            .material
+            .scatter(ray, &hit_record)
+            .map(
+                |ScatterRecord {
+                     attenuation,
+                     direction,
+                     pdf,
+                 }| {
+                    let light = match pdf {
+                        // Directions without a density are mirror reflections and refractions,
+                        // which keep looking for the surface to light.
+                        None => self.trace(
+                            &direction,
+                            0.001..=f64::INFINITY,
+                            world,
+                            background,
+                            bounces + 1,
+                            path,
+                        ),
+                        Some(pdf) => {
+                            let light_pdf = self.light_pdf(hit_record.p, direction.direction());
+                            pdf / (pdf + light_pdf)
+                                * gathered_light(&direction, world, background, path)
+                        }
+                    };
+                    light.attenuate(&attenuation)
+                },
+            )
+            .unwrap_or_default();
+        emitted + aimed + scattered
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        background: &dyn Background,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        self.trace(ray, valid_t, world, background, 0, path)
+    }
+
+    fn name(&self) -> &'static str {
+        "direct lighting"
+    }
+}
+
+/// An integrator that colors each ray by the normal of the first surface that it hits, with the
+/// x, y, and z components of the unit normal mapped from `-1..=1` to the red, green, and blue
+/// channels from `0` to `1`. Rays that hit nothing are black. The normals are shown as the
+/// objects report them without turning them toward the ray, so surfaces whose normals point the
+/// wrong way stand out from their neighbors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        _background: &dyn Background,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        match world.hit_by(ray, valid_t) {
+            None => Color::default(),
+            Some(hit_record) => {
+                if let Some(path) = path {
+                    path.push(hit_record.p);
+                }
+                let normal = hit_record.normal.normalized();
+                Color::new(
+                    0.5 * (normal.x() + 1.),
+                    0.5 * (normal.y() + 1.),
+                    0.5 * (normal.z() + 1.),
+                )
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "normals"
+    }
+}
+
+/// An integrator that shades each ray in gray by the distance along it to the first surface that
+/// it hits, from black at the origin of the ray to white at the far distance of the integrator
+/// and beyond. Rays that hit nothing are white.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Depth {
+    far: f64,
+}
+
+impl Depth {
+    /// Creates a new depth integrator that shows surfaces at `far` or farther as white.
+    pub const fn new(far: f64) -> Self {
+        Self { far }
+    }
+
+    /// Gets the distance at which surfaces are shown as white.
+    pub const fn far(&self) -> f64 {
+        self.far
+    }
+}
+
+impl Integrator for Depth {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        valid_t: RangeInclusive<f64>,
+        world: &dyn Hittable,
+        _background: &dyn Background,
+        path: Option<&mut Vec<Point3>>,
+    ) -> Color {
+        let shade = match world.hit_by(ray, valid_t) {
+            None => 1.,
+            Some(hit_record) => {
+                if let Some(path) = path {
+                    path.push(hit_record.p);
+                }
+                (hit_record.t * ray.direction().length() / self.far).clamp(0., 1.)
+            }
+        };
+        Color::new(shade, shade, shade)
+    }
+
+    fn name(&self) -> &'static str {
+        "depth"
+    }
+}
+
+/// Gets the light that arrives along `ray` at the surface that it was scattered from directly
+/// from the glowing surface that it hits or from `background`.
+fn gathered_light(
+    ray: &Ray,
+    world: &dyn Hittable,
+    background: &dyn Background,
+    path: Option<&mut Vec<Point3>>,
+) -> Color {
+    match world.hit_by(ray, 0.001..=f64::INFINITY) {
+        None => sky(ray, background, path),
+        Some(hit_record) => {
+            if let Some(path) = path {
+                path.push(hit_record.p);
+            }
+            hit_record.material.emitted(ray, &hit_record)
+        }
+    }
+}
+
+/// Gets the light of `background` seen along `ray`, which hits nothing.
+fn sky(ray: &Ray, background: &dyn Background, path: Option<&mut Vec<Point3>>) -> Color {
+    let unit_direction = ray.direction().normalized();
+    if let Some(path) = path {
+        path.push(*ray.origin() + unit_direction);
+    }
+    background.value(&unit_direction)
+}
+
+/// Gets the unit normal of the surface at `hit_record` on the side that `ray` came from.
+fn facing_normal(ray: &Ray, hit_record: &RayHit) -> Vec3 {
+    let normal = hit_record.normal.normalized();
+    if normal.dot(ray.direction()) < 0. {
+        normal
+    } else {
+        -normal
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::{Color, Point3, Vec3};
+
+/// A cache of the light arriving at diffuse surfaces from the rest of the scene. The light is
+/// estimated at sparse points and interpolated between them as described by Ward, Rubinstein, and
+/// Clear (1988), which smooths away the noise of indirect lighting at the cost of blurring small
+/// details of it.
+///
+/// Points are added as the rays that render the image need them, and rays are traced on many
+/// threads at once, so which points end up in the cache depends on the order that the threads
+/// happen to reach them. The blotches left by the interpolation are therefore laid out
+/// differently every time a scene is rendered, even with the same seed.
+#[derive(Debug)]
+pub struct IrradianceCache {
+    accuracy: f64,
+    min_spacing: f64,
+    max_spacing: f64,
+    samples: usize,
+    /// The records, sorted into cubes whose sides are the farthest that any record reaches.
+    cells: RwLock<HashMap<[i64; 3], Vec<Record>>>,
+}
+
+/// The light arriving at one point of a surface.
+#[derive(Clone, Copy, Debug)]
+struct Record {
+    p: Point3,
+    normal: Vec3,
+    radiance: Color,
+    /// The harmonic mean of the distances to the surfaces seen from the point, clamped to the
+    /// spacing of the cache. Records reach farther across open areas than into corners.
+    radius: f64,
+}
+
+impl IrradianceCache {
+    /// The number of rays traced to estimate the light arriving at each point by default.
+    const DEFAULT_SAMPLES: usize = 128;
+
+    /// Creates a new, empty cache. `accuracy` is the error of interpolation that is tolerated,
+    /// where smaller values place points more densely; `0.2` is a good starting point. No two
+    /// points are farther than about `accuracy * max_spacing` apart, nor closer than a hundredth
+    /// of that.
+    pub fn new(accuracy: f64, max_spacing: f64) -> Self {
+        Self {
+            accuracy: accuracy.max(1e-3),
+            min_spacing: max_spacing / 100.,
+            max_spacing,
+            samples: Self::DEFAULT_SAMPLES,
+            cells: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Estimates the light arriving at each point from `samples` rays instead of the default.
+    pub fn with_samples(self, samples: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+            ..self
+        }
+    }
+
+    /// Gets the number of rays that should be traced to estimate the light arriving at a point.
+    pub const fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Gets the number of points at which the light has been estimated.
+    pub fn len(&self) -> usize {
+        self.cells
+            .read()
+            .expect("Irradiance cache was poisoned")
+            .values()
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Checks whether the light hasn't been estimated anywhere yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Interpolates the light arriving at `p` on a surface with the unit normal vector `normal`
+    /// from the nearby points at which it has been estimated. The light is the average radiance
+    /// over the hemisphere around `normal`, weighted by the cosine of the angle from it, so a
+    /// diffuse surface reflects the light times its albedo. Returns `None` if none of the points
+    /// are close enough to `p` for the result to be within the accuracy of the cache.
+    pub fn lookup(&self, p: &Point3, normal: &Vec3) -> Option<Color> {
+        let cells = self.cells.read().expect("Irradiance cache was poisoned");
+        let [x, y, z] = self.cell(p);
+        let mut total = Color::default();
+        let mut total_weight = 0.;
+        for neighbor in (-1..=1)
+            .flat_map(|i| (-1..=1).flat_map(move |j| (-1..=1).map(move |k| [x + i, y + j, z + k])))
+        {
+            for record in cells.get(&neighbor).into_iter().flatten() {
+                let offset = *p - record.p;
+                // Records in front of the point see light that the point may not.
+                if offset.dot(&(*normal + record.normal)) < -0.01 * record.radius {
+                    continue;
+                }
+                let error = offset.length() / record.radius
+                    + (1. - normal.dot(&record.normal)).max(0.).sqrt();
+                if error < self.accuracy {
+                    let weight = 1. / error.max(1e-6);
+                    total += weight * record.radiance;
+                    total_weight += weight;
+                }
+            }
+        }
+        (total_weight > 0.).then(|| total / total_weight)
+    }
+
+    /// Saves an estimate of the light arriving at `p` on a surface with the unit normal vector
+    /// `normal`, as described by [`lookup()`], that was made by tracing rays that hit surfaces at
+    /// the harmonic mean distance `mean_distance` from `p`.
+    ///
+    /// [`lookup()`]: Self::lookup()
+    pub fn insert(&self, p: Point3, normal: Vec3, radiance: Color, mean_distance: f64) {
+        let record = Record {
+            p,
+            normal,
+            radiance,
+            radius: mean_distance.clamp(self.min_spacing, self.max_spacing),
+        };
+        self.cells
+            .write()
+            .expect("Irradiance cache was poisoned")
+            .entry(self.cell(&p))
+            .or_default()
+            .push(record);
+    }
+
+    /// Finds the cube that `p` is sorted into.
+    fn cell(&self, p: &Point3) -> [i64; 3] {
+        let size = self.accuracy * self.max_spacing;
+        [0, 1, 2].map(|i| (p[i] / size).floor() as i64)
+    }
+}
@@ -7,12 +7,37 @@
 /// An angle should carry its units with it.
 pub mod angle;
 
+/// The light that reaches a scene from its distant surroundings.
+pub mod background;
+
+/// Bounding boxes and the hierarchies of them that speed up finding which object a ray hits.
+pub mod bvh;
+
 /// A camera produces [`Ray`]s.
 pub mod camera;
 
-/// An RGB color. The intensity of each component is in the range `[0.0, 1.0]`.
+/// An RGB color. The intensity of each component is non-negative.
 pub mod color;
-pub use color::Color;
+pub use color::{Color, Primaries};
+
+/// Tools for inspecting how a scene is rendered.
+pub mod debug;
+
+/// The flares that a camera lens scatters from bright lights.
+pub mod flare;
+
+/// Learning where the light in a scene comes from to guide the paths traced through it.
+pub mod guiding;
+
+/// Images stored in memory.
+pub mod image;
+pub use image::Image;
+
+/// Ways of computing the light that reaches the camera along each ray.
+pub mod integrator;
+
+/// Caching of the light that reaches diffuse surfaces.
+pub mod irradiance;
 
 /// A description of how rays scatter off of a surface.
 pub mod material;
@@ -21,10 +46,45 @@ pub use material::Material;
 /// Primitive objects that can be hit by [`Ray`]s.
 pub mod object;
 
+/// Photon mapping of the caustics that mirrors and glass focus onto diffuse surfaces.
+pub mod photon;
+
+/// Utilities for finding the roots of polynomials.
+pub mod poly;
+
+/// Objects that are generated from a seed as they are needed.
+pub mod procedural;
+
+/// A quaternion for representing rotations.
+pub mod quat;
+
 /// The path of a light ray.
 pub mod ray;
 pub use ray::Ray;
 
+/// Perlin noise.
+pub mod perlin;
+
+/// Per-thread random number generation.
+pub mod rng;
+
+/// Sources of the points that the rays through a pixel are spread over.
+pub mod sampler;
+
+/// A collection of objects to render.
+pub mod scene;
+pub use scene::Scene;
+
+/// Textures that vary the appearance of a surface across it.
+pub mod texture;
+pub use texture::Texture;
+
+/// Non-photorealistic rendering.
+pub mod toon;
+
+/// Affine transformations of 3D space.
+pub mod transform;
+
 /// A 3D vector.
 pub mod vec3;
 pub use vec3::Vec3;
@@ -4,164 +4,773 @@
 #![warn(missing_copy_implementations, missing_docs, rust_2018_idioms)]
 #![deny(unsafe_op_in_unsafe_fn, missing_debug_implementations)]
 
+mod fuzz;
+
 use std::{
     fs::File,
-    io::{self, Write},
+    io::{self, BufReader, Seek, SeekFrom, Write},
+    str::FromStr,
     sync::Arc,
+    time::Instant,
 };
 
-use clap::{Parser, Subcommand};
-use rand::{distributions::WeightedIndex, prelude::*};
+use clap::{Parser, Subcommand, ValueEnum};
 use ray_tracing::{
     angle::Angle,
-    camera::{Camera, Orientation, Structure},
-    material::{Dielectric, Lambertian, Metal, ScatterRecord},
-    object::{List, Sphere},
+    background::EnvironmentMap,
+    bvh::{BvhBuildOptions, SplitStrategy, TraversalOrder},
+    camera::{Camera, Orientation, Projection, Structure},
+    debug::{self, FocusPreview, FurnaceResult, Overlay, PathRecorder},
+    flare::LensFlare,
+    guiding::PathGuide,
+    image::{self, ImageSink, PpmSink},
+    integrator::{Depth, DirectLighting, Integrator, Normals, PathTracer},
+    irradiance::IrradianceCache,
+    material::{Dielectric, Lambertian, Metal, Principled, PrincipledParameters},
+    object::{List, Solid, Sphere},
+    photon::PhotonMap,
+    procedural::SphereField,
     ray::Hittable,
-    Color, Point3, Ray, Vec3,
+    rng,
+    sampler::{
+        BlueNoise, CorrelatedMultiJittered, Halton, Independent, SampleIndex, Sampler, Sobol,
+        Stratified,
+    },
+    texture::Checker,
+    toon::{self, ToonStyle},
+    Color, Image, Material, Point3, Primaries, Scene, Vec3,
 };
 use rayon::prelude::*;
 
-fn ray_color(ray: &Ray, world: &dyn Hittable, max_depth: usize) -> Color {
-    if max_depth == 0 {
-        return Color::new(0., 0., 0.);
+/// The parameters that control how an image is sampled.
+#[derive(Clone, Copy, Debug)]
+struct RenderSettings<'a> {
+    /// The width of the image in pixels.
+    width: u32,
+    /// The height of the image in pixels.
+    height: u32,
+    /// The number of rays to trace through each pixel.
+    samples_per_pixel: usize,
+    /// The number of rows to render before writing them out. Only this many rows are held in
+    /// memory at once.
+    band_rows: u32,
+    /// The primaries of the color space that the image is written in.
+    primaries: Primaries,
+    /// The way of computing the light that reaches the camera along each ray.
+    integrator: &'a dyn Integrator,
+    /// The source of the points within each pixel, on the lens, and during the exposure that rays
+    /// are traced through.
+    sampler: &'a dyn Sampler,
+    /// The rule for stopping early at pixels that have converged, if any. Without it, every pixel
+    /// gets the same number of rays.
+    adaptive: Option<AdaptiveSampling>,
+}
+
+impl RenderSettings<'_> {
+    /// The number of rows to render at once. Adaptive sampling shares its budget between the
+    /// pixels that are rendered together, so with it the whole image is rendered at once.
+    fn band_rows(&self) -> u32 {
+        match self.adaptive {
+            Some(_) => self.height,
+            None => self.band_rows.max(1),
+        }
     }
-    match world.hit_by(ray, 0.001..=f64::INFINITY) {
-        None => {
-            let unit_direction = ray.direction().normalized();
-            let t = 0.5 * (unit_direction.y() + 1.0);
-            Color::new(1., 1., 1.).interpolate(&Color::new(0.5, 0.7, 1.0), t)
+}
+
+/// A request to stop tracing rays through each pixel once its color is known precisely enough,
+/// and to spend the rays saved on smooth pixels on noisy ones instead. The pixels rendered
+/// together share a budget of the number of rays that the scene normally uses for each of them:
+/// every pixel is first given `min_samples` rays, and the rest of the budget is then handed out in
+/// rounds to the pixels that haven't converged, in proportion to their error.
+#[derive(Clone, Copy, Debug)]
+struct AdaptiveSampling {
+    /// The largest error in the brightness of a pixel as it is written out, from `0` to `1`, that
+    /// is tolerated with 95% confidence.
+    tolerance: f64,
+    /// The number of rays traced through every pixel before it is first checked for whether it
+    /// has converged, so that a pixel whose first few rays happen to agree isn't stopped early.
+    min_samples: usize,
+    /// The number of rays that the budget is handed out in. A pixel's share of each round is a
+    /// whole number of batches.
+    batch: usize,
+    /// The most rays that a pixel may be given, as a multiple of the number that the scene normally
+    /// uses.
+    budget: f64,
+}
+
+impl AdaptiveSampling {
+    /// The brightness below which pixels are held to the error tolerated at this brightness, so
+    /// that the variance of nearly black pixels isn't magnified without bound.
+    const DARKEST: f64 = 1. / 256.;
+
+    /// Averages the colors returned by `trace` for the rays through each of `pixels` pixels that
+    /// the scene normally traces `samples` rays through. `trace` is given the pixel, the index of
+    /// each ray, and the number of rays in each batch, which are spread out as sets of their own.
+    fn sample(
+        &self,
+        pixels: usize,
+        samples: usize,
+        trace: impl Fn(usize, usize, usize) -> Color + Sync,
+    ) -> Vec<Color> {
+        let max_samples = ((samples as f64 * self.budget).ceil() as usize).max(1);
+        let batch = self.batch.max(2);
+        let first = batch.max(self.min_samples).min(max_samples);
+        let mut estimates: Vec<PixelEstimate> = (0..pixels)
+            .into_par_iter()
+            .map(|pixel| {
+                let mut estimate = PixelEstimate::default();
+                estimate.trace(first, batch, |index, count| trace(pixel, index, count));
+                estimate
+            })
+            .collect();
+        let mut remaining = pixels
+            .saturating_mul(samples)
+            .saturating_sub(pixels.saturating_mul(first));
+        loop {
+            let errors: Vec<f64> = estimates
+                .par_iter()
+                .map(|estimate| match estimate.error() {
+                    error if error > self.tolerance && estimate.count < max_samples => error,
+                    _ => 0.,
+                })
+                .collect();
+            let open = errors.iter().filter(|&&error| error > 0.).count();
+            let total_error: f64 = errors.iter().sum();
+            // Each round hands out about one batch for each open pixel so that the errors are
+            // measured again before the rest of the budget is handed out.
+            let round = (remaining / batch).min(open);
+            if round == 0 {
+                break;
+            }
+            let shares: Vec<usize> = estimates
+                .iter()
+                .zip(&errors)
+                .map(|(estimate, error)| {
+                    let batches = (round as f64 * error / total_error).floor() as usize;
+                    (batches * batch).min(max_samples - estimate.count)
+                })
+                .collect();
+            let handed_out: usize = shares.iter().sum();
+            if handed_out == 0 {
+                break;
+            }
+            estimates
+                .par_iter_mut()
+                .zip(shares)
+                .enumerate()
+                .filter(|(_, (_, share))| *share > 0)
+                .for_each(|(pixel, (estimate, share))| {
+                    estimate.trace(share, batch, |index, count| trace(pixel, index, count));
+                });
+            remaining = remaining.saturating_sub(handed_out);
         }
-        Some(hit_record) => hit_record
-            .material
-            .scatter(ray, &hit_record)
-            .map(
-                |ScatterRecord {
-                     attenuation,
-                     direction,
-                 }| {
-                    ray_color(&direction, world, max_depth - 1).attenuate(&attenuation)
-                },
-            )
-            .unwrap_or_default(),
+        estimates.iter().map(PixelEstimate::mean).collect()
+    }
+}
+
+/// The rays traced through a pixel so far by adaptive sampling.
+#[derive(Clone, Copy, Debug, Default)]
+struct PixelEstimate {
+    /// The sum of the colors of the rays.
+    sum: Color,
+    /// The sum of the brightnesses of the rays.
+    brightness: f64,
+    /// The sum of the squares of the brightnesses of the rays.
+    squared_brightness: f64,
+    /// The number of rays.
+    count: usize,
+}
+
+impl PixelEstimate {
+    /// Traces `rays` more rays with `trace`, which is given the index of each ray and `batch`.
+    fn trace(&mut self, rays: usize, batch: usize, trace: impl Fn(usize, usize) -> Color + Sync) {
+        let (sum, brightness, squared_brightness) = (self.count..self.count + rays)
+            .into_par_iter()
+            .map(|index| {
+                let color = trace(index, batch);
+                let luminance = color.luminance();
+                (color, luminance, luminance * luminance)
+            })
+            .reduce(
+                || (Color::default(), 0., 0.),
+                |(c1, l1, s1), (c2, l2, s2)| (c1 + c2, l1 + l2, s1 + s2),
+            );
+        self.sum += sum;
+        self.brightness += brightness;
+        self.squared_brightness += squared_brightness;
+        self.count += rays;
+    }
+
+    /// The half-width of the 95% confidence interval of the mean brightness after gamma
+    /// correction, which shrinks the error of a brightness `b` by `2 * sqrt(b)`.
+    fn error(&self) -> f64 {
+        let n = self.count as f64;
+        let mean = self.brightness / n;
+        let variance = ((self.squared_brightness - n * mean * mean) / (n - 1.)).max(0.);
+        1.96 * (variance / n).sqrt() / (2. * mean.max(AdaptiveSampling::DARKEST).sqrt())
+    }
+
+    /// The average color of the rays.
+    fn mean(&self) -> Color {
+        self.sum / self.count as f64
+    }
+}
+
+/// Converts a linear color in the working space of the renderer to `primaries` and
+/// gamma-corrects it for gamma=2.0.
+fn output_color(color: Color, primaries: Primaries) -> Color {
+    let color = primaries.convert(color);
+    Color::new(
+        color.red().sqrt(),
+        color.green().sqrt(),
+        color.blue().sqrt(),
+    )
+}
+
+/// Traces `samples` rays through each of `pixels`, given as the column from the left and the row
+/// from the bottom of the image, and averages their linear colors. With adaptive sampling, the
+/// pixels share a budget of `samples` rays for each of them instead.
+fn sample_pixels(
+    pixels: &[(u32, u32)],
+    settings: RenderSettings<'_>,
+    samples: usize,
+    camera: &Camera,
+    world: &Scene,
+    recorder: Option<&PathRecorder>,
+) -> Vec<Color> {
+    let RenderSettings {
+        width,
+        height,
+        integrator,
+        sampler,
+        adaptive,
+        ..
+    } = settings;
+    let trace = |pixel: usize, index: usize, count: usize| {
+        let sample = SampleIndex {
+            pixel: pixels[pixel],
+            index,
+            count,
+        };
+        let ray = camera.get_sampler_ray(sampler, sample, width, height);
+        let valid_t = camera.clip_range(&ray);
+        match recorder.filter(|recorder| recorder.should_record()) {
+            Some(recorder) => {
+                let mut path = vec![*ray.origin()];
+                let color =
+                    integrator.radiance(&ray, valid_t, world, world.background(), Some(&mut path));
+                recorder.record(path);
+                color
+            }
+            None => integrator.radiance(&ray, valid_t, world, world.background(), None),
+        }
+    };
+    match adaptive {
+        Some(adaptive) => adaptive.sample(pixels.len(), samples, trace),
+        None => (0..pixels.len())
+            .into_par_iter()
+            .map(|pixel| {
+                Color::merge_samples(
+                    (0..samples)
+                        .into_par_iter()
+                        .map(|index| trace(pixel, index, samples)),
+                )
+            })
+            .collect(),
     }
 }
 
 fn write_image(
+    out: &mut FileOrStdout,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+    recorder: Option<&PathRecorder>,
+) -> io::Result<()> {
+    let RenderSettings {
+        width,
+        height,
+        samples_per_pixel,
+        primaries,
+        ..
+    } = settings;
+    let mut sink = PpmSink::crash_safe(out, width, height)?;
+    let band_rows = settings.band_rows();
+    let mut rows_remaining = height;
+    while rows_remaining > 0 {
+        writeln!(io::stderr().lock(), "Scanlines remaining: {rows_remaining}")?;
+        let rows = band_rows.min(rows_remaining);
+        // Rows are numbered from the bottom of the image but written from the top.
+        let top = rows_remaining - 1;
+        let pixels: Vec<(u32, u32)> = (0..rows * width)
+            .map(|index| (index % width, top - index / width))
+            .collect();
+        let band: Vec<Color> = sample_pixels(
+            &pixels,
+            settings,
+            samples_per_pixel,
+            camera,
+            world,
+            recorder,
+        )
+        .into_iter()
+        .map(|color| output_color(color, primaries))
+        .collect();
+        sink.write_pixels(&band)?;
+        rows_remaining -= rows;
+    }
+    writeln!(io::stderr().lock(), "Done")?;
+    Ok(())
+}
+
+/// Re-renders the pixels of `patch.image` that are inside `patch.region` and writes the patched
+/// image to `out`. The image must have been rendered from the same scene, which the scene commands
+/// guarantee as long as the seed is the same.
+fn write_patch(
     out: &mut dyn Write,
-    width: u32,
-    height: u32,
-    samples_per_pixel: usize,
+    settings: RenderSettings<'_>,
     camera: &Camera,
-    world: &(dyn Hittable + Sync),
-    max_depth: usize,
+    world: &Scene,
+    patch: &Patch<'_>,
 ) -> io::Result<()> {
-    writeln!(out, "P3")?;
-    writeln!(out, "{width} {height}")?;
-    writeln!(out, "255")?;
-    for j in (0..height).rev() {
-        writeln!(io::stderr().lock(), "Scanlines remaining: {j}")?;
-        for i in 0..width {
-            let color = Color::merge_samples((0..samples_per_pixel).into_par_iter().map(|_| {
-                let u = (i as f64 + rand::random::<f64>()) / (width - 1) as f64;
-                let v = (j as f64 + rand::random::<f64>()) / (height - 1) as f64;
-                ray_color(&camera.get_ray(u, v), world, max_depth)
-            }));
-            // Gamma-correct for gamma=2.0.
-            let color = Color::new(
-                color.red().sqrt(),
-                color.green().sqrt(),
-                color.blue().sqrt(),
-            );
-            writeln!(out, "{color}")?;
+    let RenderSettings {
+        width,
+        height,
+        primaries,
+        ..
+    } = settings;
+    let mut image = patch.image.clone();
+    if (image.width(), image.height()) != (width, height) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "The image to patch is {}x{} but the scene renders at {width}x{height}",
+                image.width(),
+                image.height()
+            ),
+        ));
+    }
+    let Region {
+        x,
+        y,
+        width: region_width,
+        height: region_height,
+    } = patch.region;
+    let columns = x.min(width)..x.saturating_add(region_width).min(width);
+    let rows = y.min(height)..y.saturating_add(region_height).min(height);
+    let samples = patch
+        .samples_per_pixel
+        .unwrap_or(4 * settings.samples_per_pixel);
+    // Adaptive sampling shares its budget between the pixels that are rendered together, so with
+    // it the whole region is rendered at once.
+    let rows_per_pass = match settings.adaptive {
+        Some(_) => rows.len().max(1),
+        None => 1,
+    };
+    for first in rows.clone().step_by(rows_per_pass) {
+        writeln!(
+            io::stderr().lock(),
+            "Scanlines remaining: {}",
+            rows.end - first
+        )?;
+        let last = (first + rows_per_pass as u32).min(rows.end);
+        let pixels: Vec<(u32, u32)> = (first..last)
+            // Rows are numbered from the bottom of the image for rendering.
+            .flat_map(|row| {
+                columns
+                    .clone()
+                    .map(move |column| (column, height - 1 - row))
+            })
+            .collect();
+        let colors = sample_pixels(&pixels, settings, samples, camera, world, None);
+        for ((column, j), color) in pixels.into_iter().zip(colors) {
+            image.set(column, height - 1 - j, output_color(color, primaries));
         }
     }
     writeln!(io::stderr().lock(), "Done")?;
+    image.write_ppm(out)
+}
+
+/// Renders the image in passes that each double the number of rays traced through every pixel,
+/// writes a CSV file of how far the image is from converging after each pass, and then writes the
+/// finished image to `out`. Without a reference image, each pass is compared to the one before it.
+fn write_convergence(
+    out: &mut dyn Write,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+    recorder: Option<&PathRecorder>,
+    convergence: &Convergence<'_>,
+) -> io::Result<()> {
+    let RenderSettings {
+        width,
+        height,
+        samples_per_pixel,
+        primaries,
+        ..
+    } = settings;
+    if let Some(reference) = convergence.reference {
+        if (reference.width(), reference.height()) != (width, height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The reference image is {}x{} but the scene renders at {width}x{height}",
+                    reference.width(),
+                    reference.height()
+                ),
+            ));
+        }
+    }
+    let mut csv = create_file(convergence.csv.trim())?;
+    writeln!(csv, "samples,seconds,rmse")?;
+    let start = Instant::now();
+    // Rows are numbered from the bottom of the image for rendering.
+    let pixels: Vec<(u32, u32)> = (0..width * height)
+        .map(|index| (index % width, height - 1 - index / width))
+        .collect();
+    let mut sums = vec![Color::default(); pixels.len()];
+    let mut previous: Option<Vec<Color>> = None;
+    let mut samples = 0;
+    while samples < samples_per_pixel.max(1) {
+        let batch = samples.max(1).min(samples_per_pixel - samples);
+        writeln!(
+            io::stderr().lock(),
+            "Samples per pixel: {}",
+            samples + batch
+        )?;
+        let colors = sample_pixels(&pixels, settings, batch, camera, world, recorder);
+        for (sum, color) in sums.iter_mut().zip(colors) {
+            *sum += batch as f64 * color;
+        }
+        samples += batch;
+        let image: Vec<Color> = sums
+            .iter()
+            .map(|sum| output_color(*sum / samples as f64, primaries))
+            .collect();
+        let rmse = match (convergence.reference, &previous) {
+            (Some(reference), _) => Some(rmse(reference.pixels(), &image)),
+            (None, Some(previous)) => Some(rmse(previous, &image)),
+            (None, None) => None,
+        };
+        let rmse = rmse.map_or_else(String::new, |rmse| format!("{rmse:.6}"));
+        let seconds = start.elapsed().as_secs_f64();
+        writeln!(csv, "{samples},{seconds:.3},{rmse}")?;
+        previous = Some(image);
+    }
+    writeln!(io::stderr().lock(), "Done")?;
+    let pixels = previous.unwrap_or_default();
+    PpmSink::new(out, width, height)?.write_pixels(&pixels)
+}
+
+/// Renders the whole image into memory and returns the linear color of each pixel in row-major
+/// order starting from the top-left corner.
+fn render_linear(
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+    recorder: Option<&PathRecorder>,
+) -> io::Result<Vec<Color>> {
+    let RenderSettings {
+        width,
+        height,
+        samples_per_pixel,
+        ..
+    } = settings;
+    let band_rows = settings.band_rows();
+    let mut linear = Vec::with_capacity(width as usize * height as usize);
+    let mut rows_remaining = height;
+    while rows_remaining > 0 {
+        writeln!(io::stderr().lock(), "Scanlines remaining: {rows_remaining}")?;
+        let rows = band_rows.min(rows_remaining);
+        // Rows are numbered from the bottom of the image but stored from the top.
+        let top = rows_remaining - 1;
+        let pixels: Vec<(u32, u32)> = (0..rows * width)
+            .map(|index| (index % width, top - index / width))
+            .collect();
+        linear.extend(sample_pixels(
+            &pixels,
+            settings,
+            samples_per_pixel,
+            camera,
+            world,
+            recorder,
+        ));
+        rows_remaining -= rows;
+    }
+    writeln!(io::stderr().lock(), "Done")?;
+    Ok(linear)
+}
+
+/// Renders the whole image into memory, adds `lens_flare` to it, and writes it to `out`.
+fn write_flared_image(
+    out: &mut dyn Write,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+    recorder: Option<&PathRecorder>,
+    lens_flare: &LensFlare,
+) -> io::Result<()> {
+    let RenderSettings {
+        width,
+        height,
+        primaries,
+        ..
+    } = settings;
+    let linear = render_linear(settings, camera, world, recorder)?;
+    let mut image = Image::from_pixels(width, height, linear).expect("Every pixel was rendered");
+    lens_flare.apply(&mut image);
+    let pixels: Vec<Color> = image
+        .pixels()
+        .iter()
+        .map(|color| output_color(*color, primaries))
+        .collect();
+    PpmSink::new(out, width, height)?.write_pixels(&pixels)
+}
+
+/// Renders the whole image into memory, adds `lens_flare` to it if present, writes it to `out`,
+/// and then writes a copy of it for each exposure offset in `brackets`.
+fn write_brackets(
+    out: &mut dyn Write,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+    recorder: Option<&PathRecorder>,
+    brackets: &Brackets<'_>,
+    lens_flare: Option<&LensFlare>,
+) -> io::Result<()> {
+    let RenderSettings {
+        width,
+        height,
+        primaries,
+        ..
+    } = settings;
+    let mut linear = render_linear(settings, camera, world, recorder)?;
+    if let Some(lens_flare) = lens_flare {
+        let mut image =
+            Image::from_pixels(width, height, linear).expect("Every pixel was rendered");
+        lens_flare.apply(&mut image);
+        linear = image.pixels().to_vec();
+    }
+    let exposed = |stops: f64| -> Vec<Color> {
+        let scale = stops.exp2();
+        linear
+            .iter()
+            .map(|color| output_color(scale * *color, primaries))
+            .collect()
+    };
+    PpmSink::new(out, width, height)?.write_pixels(&exposed(0.))?;
+    for &stops in brackets.stops {
+        let filename = brackets.filename(stops);
+        let mut file = create_file(&filename)?;
+        PpmSink::new(&mut file, width, height)?.write_pixels(&exposed(stops))?;
+    }
     Ok(())
 }
 
-fn random_scene() -> List {
-    let mut world = List::default();
+/// Writes a PFM image to `filename` whose red and green channels hold the distance in pixels that
+/// the surface seen through each pixel moves across the image while the shutter is open. Positive
+/// values point right and up.
+fn write_velocity_pass(
+    filename: &str,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+) -> io::Result<()> {
+    let RenderSettings { width, height, .. } = settings;
+    let exposure = camera.shutter_close() - camera.shutter_open();
+    let time = camera.shutter_open() + exposure / 2.;
+    let pixels: Vec<Vec3> = (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            let (i, j) = (index % width, height - 1 - index / width);
+            let u = (i as f64 + 0.5) / (width - 1) as f64;
+            let v = (j as f64 + 0.5) / (height - 1) as f64;
+            let ray = camera.get_pinhole_ray(u, v, time);
+            world
+                .hit_by(&ray, camera.clip_range(&ray))
+                .and_then(|hit| {
+                    let (start_u, start_v) = camera.project(&hit.p)?;
+                    let (end_u, end_v) = camera.project(&(hit.p + exposure * hit.velocity))?;
+                    Some(Vec3::new(
+                        (end_u - start_u) * (width - 1) as f64,
+                        (end_v - start_v) * (height - 1) as f64,
+                        0.,
+                    ))
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+    image::write_pfm(&mut create_file(filename.trim())?, width, height, &pixels)
+}
 
-    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.push(Arc::new(Sphere::new(
-        Vec3::new(0., -1000., 0.),
-        1000.,
-        ground_material,
-    )));
+/// Writes a PFM image to `filename` whose channels all hold the distance from the camera to the
+/// surface seen through the center of each pixel at the middle of the exposure, or infinity if
+/// the pixel sees nothing.
+fn write_depth_pass(
+    filename: &str,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &Scene,
+) -> io::Result<()> {
+    let RenderSettings { width, height, .. } = settings;
+    let time = (camera.shutter_open() + camera.shutter_close()) / 2.;
+    let pixels: Vec<Vec3> = (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            let (i, j) = (index % width, height - 1 - index / width);
+            let u = (i as f64 + 0.5) / (width - 1) as f64;
+            let v = (j as f64 + 0.5) / (height - 1) as f64;
+            let ray = camera.get_pinhole_ray(u, v, time);
+            let distance = world
+                .hit_by(&ray, camera.clip_range(&ray))
+                .map_or(f64::INFINITY, |hit| hit.t * ray.direction().length());
+            Vec3::new(distance, distance, distance)
+        })
+        .collect();
+    image::write_pfm(&mut create_file(filename.trim())?, width, height, &pixels)
+}
 
-    let material_weights = [16, 3, 1];
-    let distribution = WeightedIndex::new(&material_weights).unwrap();
-    let mut rng = rand::thread_rng();
-    for a in (-11..11).map(f64::from) {
-        for b in (-11..11).map(f64::from) {
-            let center = Point3::new(a + 0.9 * rng.gen::<f64>(), 0.2, b + 0.9 * rng.gen::<f64>());
-            if (center - Point3::new(4., 0.2, 0.)).length_squared() < 0.81 {
-                continue;
+/// Renders `world` as seen by `camera` in the style chosen on the command line, then writes any
+/// extra passes that were asked for.
+fn render_scene(
+    out: &mut FileOrStdout,
+    settings: RenderSettings<'_>,
+    camera: &Camera,
+    world: &mut Scene,
+    options: &SceneOptions<'_>,
+) -> io::Result<()> {
+    if let Some(bvh) = options.bvh {
+        world.build_bvh(camera.shutter_open()..=camera.shutter_close(), bvh);
+    }
+    let world = &*world;
+    if let Some(photon_map) = options.photon_map {
+        photon_map.trace(world, world.background());
+    }
+    if let Some(path_guide) = options.path_guide {
+        path_guide.train(camera, world, world.background());
+    }
+    match (
+        options.patch,
+        options.convergence,
+        options.brackets,
+        options.toon,
+        options.focus_preview,
+    ) {
+        (Some(patch), _, _, _, _) => write_patch(out, settings, camera, world, patch)?,
+        (None, Some(convergence), _, _, _) => {
+            write_convergence(out, settings, camera, world, options.recorder, convergence)?
+        }
+        (None, None, Some(brackets), _, _) => write_brackets(
+            out,
+            settings,
+            camera,
+            world,
+            options.recorder,
+            brackets,
+            options.lens_flare,
+        )?,
+        (None, None, None, None, None) => match options.lens_flare {
+            Some(lens_flare) => {
+                write_flared_image(out, settings, camera, world, options.recorder, lens_flare)?
             }
-            match distribution.sample(&mut rng) {
-                0 => {
-                    let albedo = rng.gen::<Color>().attenuate(&rng.gen());
-                    let material = Arc::new(Lambertian::new(albedo));
-                    world.push(Arc::new(Sphere::new(center, 0.2, material)));
-                }
-                1 => {
-                    let albedo = Color::random(0.5..1.);
-                    let fuzziness = 0.5 * rng.gen::<f64>();
-                    let material = Arc::new(Metal::new(albedo, fuzziness));
-                    world.push(Arc::new(Sphere::new(center, 0.2, material)));
-                }
-                2 => {
-                    let material = Arc::new(Dielectric::new(1.5));
-                    world.push(Arc::new(Sphere::new(center, 0.2, material)));
-                }
-                n => unreachable!("Unknown material ID {n}"),
-            };
+            None => write_image(out, settings, camera, world, options.recorder)?,
+        },
+        (None, None, None, style, focus_preview) => {
+            let RenderSettings { width, height, .. } = settings;
+            let style = style.copied().unwrap_or_default();
+            let mut image = toon::render(camera, world, width, height, &style);
+            if let Some(focus_preview) = focus_preview {
+                focus_preview.apply(&mut image, camera, world);
+            }
+            if let Some(lens_flare) = options.lens_flare {
+                lens_flare.apply(&mut image);
+            }
+            let pixels: Vec<Color> = image
+                .pixels()
+                .iter()
+                .map(|color| output_color(*color, settings.primaries))
+                .collect();
+            PpmSink::new(out, width, height)?.write_pixels(&pixels)?;
         }
     }
+    if let Some(filename) = options.velocity_pass {
+        write_velocity_pass(filename, settings, camera, world)?;
+    }
+    if let Some(filename) = options.depth_pass {
+        write_depth_pass(filename, settings, camera, world)?;
+    }
+    Ok(())
+}
+
+fn random_scene() -> Scene {
+    let mut world = Scene::default();
+
+    let checker = Arc::new(Checker::from_colors(
+        0.32,
+        Color::new(0.2, 0.3, 0.1),
+        Color::new(0.9, 0.9, 0.9),
+    ));
+    let ground_material = Arc::new(Lambertian::new(checker));
+    world.push_named(
+        Scene::GROUND,
+        Arc::new(Sphere::new(
+            Vec3::new(0., -1000., 0.),
+            1000.,
+            ground_material,
+        )),
+    );
+
+    let mut field = SphereField::new(rng::random(), Some(11));
+    field.add_clearing(Point3::new(4., 0.2, 0.), 0.9);
+    world.push(Arc::new(field));
 
     let material = Arc::new(Dielectric::new(1.5));
     world.push(Arc::new(Sphere::new(Point3::new(0., 1., 0.), 1., material)));
 
-    let material = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    let material = Arc::new(Lambertian::from_color(Color::new(0.4, 0.2, 0.1)));
     world.push(Arc::new(Sphere::new(
         Point3::new(-4., 1., 0.),
         1.,
         material,
     )));
 
-    let material = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.));
+    let material = Arc::new(Metal::rough_from_color(Color::new(0.7, 0.6, 0.5), 0.));
     world.push(Arc::new(Sphere::new(Point3::new(4., 1., 0.), 1., material)));
 
     world
 }
 
-fn static_scene() -> List {
-    let mut world = List::default();
-    let ground_material = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    let center_material = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
+fn static_scene() -> Scene {
+    let mut world = Scene::default();
+    let ground_material = Arc::new(Lambertian::from_color(Color::new(0.8, 0.8, 0.0)));
+    let center_material = Arc::new(Lambertian::from_color(Color::new(0.1, 0.2, 0.5)));
     let left_material = Arc::new(Dielectric::new(1.5));
-    let right_material = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
-    world.push(Arc::new(Sphere::new(
-        Point3::new(0., -100.5, -1.),
-        100.,
-        ground_material,
-    )));
+    let right_material = Arc::new(Metal::rough_from_color(Color::new(0.8, 0.6, 0.2), 0.0));
+    world.push_named(
+        Scene::GROUND,
+        Arc::new(Sphere::new(
+            Point3::new(0., -100.5, -1.),
+            100.,
+            ground_material,
+        )),
+    );
     world.push(Arc::new(Sphere::new(
         Point3::new(0., 0., -1.),
         0.5,
         center_material,
     )));
-    world.push(Arc::new(Sphere::new(
+    let mut left = List::default();
+    left.push(Arc::new(Sphere::new(
         Point3::new(-1., 0., -1.),
         0.5,
         Arc::clone(&left_material),
     )));
-    world.push(Arc::new(Sphere::new(
+    left.push(Arc::new(Sphere::new(
         Point3::new(-1., 0., -1.),
         -0.45,
         left_material,
     )));
+    world.push(Arc::new(Solid::new(Arc::new(left))));
     world.push(Arc::new(Sphere::new(
         Point3::new(1., 0., -1.),
         0.5,
@@ -170,14 +779,398 @@ fn static_scene() -> List {
     world
 }
 
-fn write_random_ppm_image(out: &mut dyn Write) -> io::Result<()> {
+/// Builds a ball of `material` on a checkered floor under the default sky. The floor has one
+/// square per unit so that the blur of reflections and refractions can be judged against it.
+fn material_preview_scene<M>(material: Arc<M>) -> Scene
+where
+    M: Material + 'static,
+{
+    let mut world = Scene::default();
+    let checker = Arc::new(Checker::from_colors(
+        1.,
+        Color::new(0.2, 0.2, 0.2),
+        Color::new(0.8, 0.8, 0.8),
+    ));
+    world.push_named(
+        Scene::GROUND,
+        Arc::new(Sphere::new(
+            Point3::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new(checker)),
+        )),
+    );
+    world.push(Arc::new(Sphere::new(Point3::new(0., 1., 0.), 1., material)));
+    world
+}
+
+/// Runs a white furnace test on the material chosen by `preview` with `samples` rays at each
+/// angle and prints the results. Fails if the material gains energy at any angle.
+fn validate_material(preview: &MaterialPreview, samples: usize) -> io::Result<()> {
+    let material = preview.material()?;
+    let results = debug::white_furnace(&material, samples);
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "Angle  Red                Green              Blue")?;
+    for result in &results {
+        let FurnaceResult {
+            angle,
+            albedo,
+            error,
+        } = result;
+        writeln!(
+            stdout,
+            "{angle:>4}°  {:.4} ± {:.4}    {:.4} ± {:.4}    {:.4} ± {:.4}{}",
+            albedo.red(),
+            error.red(),
+            albedo.green(),
+            error.green(),
+            albedo.blue(),
+            error.blue(),
+            if result.gains_energy() {
+                "  gains energy"
+            } else {
+                ""
+            },
+        )?;
+    }
+    if results.iter().any(FurnaceResult::gains_energy) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The {} material gains energy", material.name()),
+        ));
+    }
+    writeln!(stdout, "The {} material conserves energy", material.name())?;
+    Ok(())
+}
+
+fn write_material_preview(
+    out: &mut FileOrStdout,
+    options: &SceneOptions<'_>,
+    preview: &MaterialPreview,
+) -> io::Result<()> {
+    const WIDTH: u32 = 300;
+    const HEIGHT: u32 = 300;
+    const SAMPLES_PER_PIXEL: usize = 200;
+    const MAX_DEPTH: usize = 50;
+
+    let mut world = preview.scene()?;
+    options.cut_away(&mut world);
+    options.add_ground(&mut world)?;
+    options.overlay.add_to(&mut world);
+    if let Some(environment) = options.environment {
+        world.set_background(Arc::clone(environment));
+    }
+
+    let camera_origin = Point3::new(0., 2.5, 7.);
+    let look_at = Point3::new(0., 0.8, 0.);
+    let camera = Camera::new(
+        Orientation {
+            origin: camera_origin,
+            look_at,
+            up: Vec3::new(0., 1., 0.),
+        },
+        Structure {
+            vertical_fov: Angle::Degrees(30.),
+            aspect_ratio: WIDTH as f64 / HEIGHT as f64,
+            aperture_width: 0.,
+            focus_distance: (camera_origin - look_at).length(),
+            near_clip: options.near_clip,
+            far_clip: options.far_clip,
+            shutter_open: 0.,
+            shutter_close: 1.,
+        },
+    )
+    .with_projection(options.projection);
+
+    let integrator = options.integrator(MAX_DEPTH, &world);
+    let settings = RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        band_rows: options.band_rows,
+        primaries: options.primaries,
+        integrator: &*integrator,
+        sampler: options.sampler,
+        adaptive: options.adaptive,
+    };
+    render_scene(out, settings, &camera, &mut world, options)
+}
+
+/// Settings from the command line that apply to every scene.
+#[derive(Clone, Copy, Debug)]
+struct SceneOptions<'a> {
+    /// The recorder that a sample of the traced paths should be saved to, if any.
+    recorder: Option<&'a PathRecorder>,
+    /// The distance in front of the camera at which objects start being visible.
+    near_clip: f64,
+    /// The distance in front of the camera at which objects stop being visible.
+    far_clip: f64,
+    /// The debugging guides to add to the scene before rendering it.
+    overlay: &'a Overlay,
+    /// The number of rows of the image to render at a time.
+    band_rows: u32,
+    /// The file to write the screen-space velocity of each pixel to, if any.
+    velocity_pass: Option<&'a str>,
+    /// The file to write the distance to the surface seen through each pixel to, if any.
+    depth_pass: Option<&'a str>,
+    /// The surroundings that light the scene in place of the default sky, if any.
+    environment: Option<&'a Arc<EnvironmentMap>>,
+    /// The cartoon style to draw the scene in instead of path tracing it, if any.
+    toon: Option<&'a ToonStyle>,
+    /// The highlighting of in-focus surfaces to draw over a quick preview instead of path tracing
+    /// the scene, if any.
+    focus_preview: Option<&'a FocusPreview>,
+    /// The settings to sort the objects of the scene into a hierarchy of bounding boxes with, if
+    /// any.
+    bvh: Option<&'a BvhBuildOptions>,
+    /// The part of an existing image to re-render instead of rendering the whole image, if any.
+    patch: Option<&'a Patch<'a>>,
+    /// The primaries of the color space that the image is written in.
+    primaries: Primaries,
+    /// The measurement of how quickly the image converges to take while rendering it, if any.
+    convergence: Option<&'a Convergence<'a>>,
+    /// How the directions around the camera are laid out on the image.
+    projection: Projection,
+    /// The exposures to write copies of the image at, if any.
+    brackets: Option<&'a Brackets<'a>>,
+    /// The cache to interpolate the light reflected by diffuse surfaces from, if any.
+    irradiance_cache: Option<&'a IrradianceCache>,
+    /// The source of the points that the rays through each pixel are spread over.
+    sampler: &'a dyn Sampler,
+    /// The flares to add around the bright lights of the image, if any.
+    lens_flare: Option<&'a LensFlare>,
+    /// The rule for stopping early at pixels that have converged, if any.
+    adaptive: Option<AdaptiveSampling>,
+    /// The plane to cut the objects of the scene away on one side of, if any.
+    clip_plane: Option<ClipPlane>,
+    /// The color of the cross sections where `clip_plane` cuts through closed objects, if they
+    /// should be filled in.
+    clip_cap: Option<Color>,
+    /// How the light reaching the camera along each ray is computed.
+    integrator: RenderIntegrator,
+    /// The map to gather the caustics that the background casts onto diffuse surfaces from, if
+    /// any. Its photons are traced once the scene is complete.
+    photon_map: Option<&'a PhotonMap>,
+    /// The guide to scatter the rays of the path tracer toward the light with, if any. It is
+    /// trained once the scene is complete.
+    path_guide: Option<&'a PathGuide>,
+    /// The number of rays that the path tracer scatters from the first surface that each camera
+    /// ray hits.
+    splits: usize,
+    /// The distance that the depth integrator shows as white.
+    depth_range: f64,
+    /// The color of the ground plane to put beneath the objects of the scene, if any.
+    ground_plane: Option<Color>,
+    /// How far beneath the lowest point of the objects the ground plane is put.
+    ground_gap: f64,
+}
+
+impl<'a> SceneOptions<'a> {
+    /// Creates the integrator for `world`, whose rays are absorbed after scattering `max_depth`
+    /// times.
+    fn integrator(&self, max_depth: usize, world: &Scene) -> Box<dyn Integrator + 'a> {
+        match self.integrator {
+            RenderIntegrator::Path => Box::new(
+                PathTracer::new(max_depth)
+                    .with_irradiance_cache(self.irradiance_cache)
+                    .with_photon_map(self.photon_map)
+                    .with_path_guide(self.path_guide)
+                    .with_splitting(self.splits),
+            ),
+            RenderIntegrator::Direct => {
+                Box::new(DirectLighting::new(max_depth).with_lights(world.lights().to_vec()))
+            }
+            RenderIntegrator::Normals => Box::new(Normals),
+            RenderIntegrator::Depth => Box::new(Depth::new(self.depth_range)),
+        }
+    }
+
+    /// Cuts away the part of `world` beyond the clipping plane, if there is one.
+    fn cut_away(&self, world: &mut Scene) {
+        if let Some(ClipPlane { point, normal }) = self.clip_plane {
+            match self.clip_cap {
+                Some(color) => {
+                    world.clip_with_cap(point, normal, Arc::new(Lambertian::from_color(color)))
+                }
+                None => world.clip(point, normal),
+            }
+        }
+    }
+
+    /// Puts the ground plane beneath the objects of `world` in place of its own ground, if there
+    /// is one. Fails if the objects have no lowest point to put the plane beneath.
+    fn add_ground(&self, world: &mut Scene) -> io::Result<()> {
+        if let Some(color) = self.ground_plane {
+            // Every scene opens the shutter at `0` and closes it at `1`.
+            world
+                .add_ground_plane(
+                    Arc::new(Lambertian::from_color(color)),
+                    self.ground_gap,
+                    0.0..=1.0,
+                )
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "--ground-plane needs a scene whose objects all have bounding boxes",
+                    )
+                })?;
+        }
+        Ok(())
+    }
+}
+
+/// A plane that cuts away everything on the side of it that its normal points toward.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ClipPlane {
+    /// A point on the plane.
+    point: Point3,
+    /// The normal to the plane, which points toward the side that is cut away.
+    normal: Vec3,
+}
+
+impl FromStr for ClipPlane {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x, y, z, nx, ny, nz] if (nx, ny, nz) != (0., 0., 0.) => Ok(Self {
+                point: Point3::new(x, y, z),
+                normal: Vec3::new(nx, ny, nz),
+            }),
+            _ => Err(format!(
+                "Expected a point and a nonzero normal in the form X,Y,Z,NX,NY,NZ but found {s:?}"
+            )),
+        }
+    }
+}
+
+/// The sphere that the photons of a [`PhotonMap`] are aimed at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CausticTarget {
+    /// The center of the sphere.
+    center: Point3,
+    /// The radius of the sphere.
+    radius: f64,
+}
+
+impl FromStr for CausticTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x, y, z, radius] if radius > 0. => Ok(Self {
+                center: Point3::new(x, y, z),
+                radius,
+            }),
+            _ => Err(format!(
+                "Expected a center and a positive radius in the form X,Y,Z,RADIUS but found {s:?}"
+            )),
+        }
+    }
+}
+
+/// A request to write copies of an image that are brighter or darker by whole or fractional stops.
+#[derive(Clone, Copy, Debug)]
+struct Brackets<'a> {
+    /// The file that the image at its normal exposure is written to, which the names of the
+    /// copies are based on.
+    out: &'a str,
+    /// The offsets from the normal exposure of each copy, in stops.
+    stops: &'a [f64],
+}
+
+impl Brackets<'_> {
+    /// Names the copy of the image whose exposure is offset by `stops` by inserting the offset
+    /// before the extension of the main image, so `render.ppm` becomes `render.ev-2.ppm`.
+    fn filename(&self, stops: f64) -> String {
+        let name_start = self.out.rfind('/').map_or(0, |slash| slash + 1);
+        match self.out[name_start..].rfind('.') {
+            Some(dot) if dot > 0 => {
+                let (stem, extension) = self.out.split_at(name_start + dot);
+                format!("{stem}.ev{stops:+}{extension}")
+            }
+            _ => format!("{}.ev{stops:+}", self.out),
+        }
+    }
+}
+
+/// A request to record how far an image is from converging as more rays are traced.
+#[derive(Clone, Copy, Debug)]
+struct Convergence<'a> {
+    /// The file to write the CSV table of errors to.
+    csv: &'a str,
+    /// The image to measure the error against, if any.
+    reference: Option<&'a Image>,
+}
+
+/// A request to improve part of an image that was already rendered.
+#[derive(Clone, Copy, Debug)]
+struct Patch<'a> {
+    /// The image to patch.
+    image: &'a Image,
+    /// The pixels to re-render.
+    region: Region,
+    /// The number of rays to trace through each re-rendered pixel. If absent, four times as many
+    /// rays as the scene normally uses are traced.
+    samples_per_pixel: Option<usize>,
+}
+
+/// A rectangle of pixels in an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Region {
+    /// The column of the left edge of the region.
+    x: u32,
+    /// The row of the top edge of the region.
+    y: u32,
+    /// The number of columns in the region.
+    width: u32,
+    /// The number of rows in the region.
+    height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<u32>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x, y, width, height] => Ok(Self {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!(
+                "Expected a region in the form X,Y,WIDTH,HEIGHT but found {s:?}"
+            )),
+        }
+    }
+}
+
+fn write_random_ppm_image(out: &mut FileOrStdout, options: &SceneOptions<'_>) -> io::Result<()> {
     const ASPECT_RATIO: f64 = 3. / 2.;
     const WIDTH: u32 = 1200;
     const HEIGHT: u32 = (WIDTH as f64 / ASPECT_RATIO) as _;
     const SAMPLES_PER_PIXEL: usize = 500;
     const MAX_DEPTH: usize = 50;
 
-    let world = random_scene();
+    let mut world = random_scene();
+    options.cut_away(&mut world);
+    options.add_ground(&mut world)?;
+    options.overlay.add_to(&mut world);
+    if let Some(environment) = options.environment {
+        world.set_background(Arc::clone(environment));
+    }
 
     let camera = Camera::new(
         Orientation {
@@ -190,28 +1183,42 @@ fn write_random_ppm_image(out: &mut dyn Write) -> io::Result<()> {
             aspect_ratio: ASPECT_RATIO,
             aperture_width: 0.1,
             focus_distance: 10.,
+            near_clip: options.near_clip,
+            far_clip: options.far_clip,
+            shutter_open: 0.,
+            shutter_close: 1.,
         },
-    );
-
-    write_image(
-        out,
-        WIDTH,
-        HEIGHT,
-        SAMPLES_PER_PIXEL,
-        &camera,
-        &world,
-        MAX_DEPTH,
     )
+    .with_projection(options.projection);
+
+    let integrator = options.integrator(MAX_DEPTH, &world);
+    let settings = RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        band_rows: options.band_rows,
+        primaries: options.primaries,
+        integrator: &*integrator,
+        sampler: options.sampler,
+        adaptive: options.adaptive,
+    };
+    render_scene(out, settings, &camera, &mut world, options)
 }
 
-fn write_static_ppm_image(out: &mut dyn Write) -> io::Result<()> {
+fn write_static_ppm_image(out: &mut FileOrStdout, options: &SceneOptions<'_>) -> io::Result<()> {
     const ASPECT_RATIO: f64 = 16. / 9.;
     const WIDTH: u32 = 400;
     const HEIGHT: u32 = (WIDTH as f64 / ASPECT_RATIO) as _;
     const SAMPLES_PER_PIXEL: usize = 100;
     const MAX_DEPTH: usize = 50;
 
-    let world = static_scene();
+    let mut world = static_scene();
+    options.cut_away(&mut world);
+    options.add_ground(&mut world)?;
+    options.overlay.add_to(&mut world);
+    if let Some(environment) = options.environment {
+        world.set_background(Arc::clone(environment));
+    }
 
     let camera_origin = Point3::new(3., 3., 2.);
     let look_at = Point3::new(0., 0., -1.);
@@ -226,22 +1233,30 @@ fn write_static_ppm_image(out: &mut dyn Write) -> io::Result<()> {
             aspect_ratio: ASPECT_RATIO,
             aperture_width: 2.,
             focus_distance: (camera_origin - look_at).length(),
+            near_clip: options.near_clip,
+            far_clip: options.far_clip,
+            shutter_open: 0.,
+            shutter_close: 1.,
         },
-    );
-
-    write_image(
-        out,
-        WIDTH,
-        HEIGHT,
-        SAMPLES_PER_PIXEL,
-        &camera,
-        &world,
-        MAX_DEPTH,
     )
+    .with_projection(options.projection);
+
+    let integrator = options.integrator(MAX_DEPTH, &world);
+    let settings = RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        band_rows: options.band_rows,
+        primaries: options.primaries,
+        integrator: &*integrator,
+        sampler: options.sampler,
+        adaptive: options.adaptive,
+    };
+    render_scene(out, settings, &camera, &mut world, options)
 }
 
 #[derive(Clone, Debug, Subcommand)]
-enum SceneType {
+enum Command {
     /// Raytrace the static scene baked into the executable. Invoking this mode multiple times will
     /// produce images that are identical except for minute differences due to the randomness in
     /// the direction that rays are scattered.
@@ -255,17 +1270,540 @@ enum SceneType {
         #[arg(short, long)]
         r#in: String,
     },
+    /// Compare the PPM image <CANDIDATE> against the PPM image <REFERENCE> and print the
+    /// root-mean-square error and peak signal-to-noise ratio between them. Both images must have
+    /// the same dimensions.
+    Diff {
+        reference: String,
+        candidate: String,
+        /// If present, a false-color image of the per-pixel error will be written to this file.
+        /// Black pixels match exactly and the brightest colors mark the largest error.
+        #[arg(long)]
+        heatmap: Option<String>,
+    },
+    /// Raytrace many tiny randomized scenes and report any that panic, produce invalid
+    /// intersections, or produce non-finite colors. The scenes are generated from `--seed`, and
+    /// each failing scene is reported with a seed that reproduces it as the first case.
+    FuzzScene {
+        /// The number of scenes to generate.
+        #[arg(long, default_value_t = 100)]
+        cases: u64,
+    },
+    /// Raytrace a ball made of a single material on a checkered floor, so that the parameters of
+    /// the material can be tuned quickly in isolation from larger scenes.
+    PreviewMaterial(MaterialPreview),
+    /// Run a white furnace test on a material: surround a flat surface made of it with uniform
+    /// white light and print the fraction of the light arriving at each of several angles that it
+    /// scatters. The test fails if the material scatters more light than arrives at any angle.
+    ValidateMaterial {
+        #[command(flatten)]
+        preview: MaterialPreview,
+        /// The number of rays to scatter at each angle.
+        #[arg(long, default_value_t = 100_000)]
+        samples: usize,
+    },
+}
+
+/// The material to render with the `preview-material` command.
+#[derive(Clone, Debug, clap::Args)]
+struct MaterialPreview {
+    /// The kind of material to make the ball out of.
+    #[arg(value_enum)]
+    material: PreviewMaterial,
+    /// The color of the material as comma-separated red, green, and blue components between `0`
+    /// and `1`. The metals with measured colors ignore it.
+    #[arg(long, value_delimiter = ',', default_values_t = [0.8, 0.8, 0.8])]
+    color: Vec<f64>,
+    /// How rough the surface is, from `0` for a perfect mirror finish. Lambertian materials ignore
+    /// it.
+    #[arg(long, default_value_t = 0.)]
+    roughness: f64,
+    /// The refractive index of a dielectric.
+    #[arg(long, default_value_t = 1.5)]
+    refractive_index: f64,
+    /// How metallic a principled material is, from `0` to `1`.
+    #[arg(long, default_value_t = 0.)]
+    metallic: f64,
+    /// How much of the light that reaches a principled material passes through it, from `0` to
+    /// `1`.
+    #[arg(long, default_value_t = 0.)]
+    transmission: f64,
+}
+
+impl MaterialPreview {
+    /// Builds the ball of the chosen material on its checkered floor.
+    fn scene(&self) -> io::Result<Scene> {
+        Ok(material_preview_scene(Arc::new(self.material()?)))
+    }
+
+    /// Creates the chosen material.
+    fn material(&self) -> io::Result<Arc<dyn Material>> {
+        let color = match self.color[..] {
+            [red, green, blue] => Color::new(red, green, blue),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--color takes exactly three components",
+                ))
+            }
+        };
+        let MaterialPreview {
+            roughness,
+            refractive_index,
+            metallic,
+            transmission,
+            ..
+        } = *self;
+        Ok(match self.material {
+            PreviewMaterial::Lambertian => Arc::new(Lambertian::from_color(color)),
+            PreviewMaterial::Metal => Arc::new(Metal::rough_from_color(color, roughness)),
+            PreviewMaterial::Gold => Arc::new(Metal::gold(roughness)),
+            PreviewMaterial::Silver => Arc::new(Metal::silver(roughness)),
+            PreviewMaterial::Copper => Arc::new(Metal::copper(roughness)),
+            PreviewMaterial::Aluminum => Arc::new(Metal::aluminum(roughness)),
+            PreviewMaterial::Dielectric => Arc::new(Dielectric::rough(refractive_index, roughness)),
+            PreviewMaterial::Principled => Arc::new(Principled::from_color(
+                color,
+                PrincipledParameters {
+                    metallic,
+                    roughness,
+                    transmission,
+                    ..Default::default()
+                },
+            )),
+        })
+    }
+}
+
+/// The kinds of material that can be previewed on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PreviewMaterial {
+    /// A perfectly diffuse surface.
+    Lambertian,
+    /// A metal tinted by `--color`.
+    Metal,
+    /// Gold, with its measured optical constants.
+    Gold,
+    /// Silver, with its measured optical constants.
+    Silver,
+    /// Copper, with its measured optical constants.
+    Copper,
+    /// Aluminum, with its measured optical constants.
+    Aluminum,
+    /// A clear dielectric such as glass or water.
+    Dielectric,
+    /// The principled material, which blends between diffuse, metallic, and glassy surfaces.
+    Principled,
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version)]
 struct Args {
     #[command(subcommand)]
-    scene_type: SceneType,
+    command: Command,
     /// The file to write the image to. Whitespace at the beginning and end of the filename will be
     /// ignored. If the given filename is empty or "-", the image will be written to stdout.
     #[arg(short, long, default_value = "-")]
     out: String,
+    /// If present, a random sample of the traced paths will be written to this file as a
+    /// Wavefront OBJ line set.
+    #[arg(long)]
+    record_paths: Option<String>,
+    /// The probability that any given path is included in the file given by `--record-paths`.
+    #[arg(long, default_value_t = 0.0001)]
+    record_probability: f64,
+    /// The maximum number of paths to write to the file given by `--record-paths`.
+    #[arg(long, default_value_t = 10_000)]
+    max_recorded_paths: usize,
+    /// The distance in front of the camera at which objects start being visible. Anything closer
+    /// to the camera is cut away.
+    #[arg(long, default_value_t = 0.)]
+    near_clip: f64,
+    /// The distance in front of the camera at which objects stop being visible. Anything farther
+    /// from the camera is cut away.
+    #[arg(long, default_value_t = f64::INFINITY)]
+    far_clip: f64,
+    /// The seed for the random number generators. The same seed always generates the same random
+    /// scene, but the noise of the image still differs between runs, since the rays are traced by
+    /// several threads that take their work in whatever order they become free. If absent, a
+    /// different seed is chosen on every run.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// If present, glowing unit-length axes will be drawn from the origin. The x-axis is red, the
+    /// y-axis is green, and the z-axis is blue.
+    #[arg(long)]
+    debug_axes: bool,
+    /// If present, a glowing grid of unit squares will be drawn on the ground plane `y = 0` out to
+    /// this distance from the origin.
+    #[arg(long)]
+    debug_grid: Option<u32>,
+    /// The number of rows of the image to render before writing them out. Larger bands keep more
+    /// threads busy but hold more of the image in memory at once.
+    #[arg(long, default_value_t = 16)]
+    band_rows: u32,
+    /// If present, the distance that the surface seen through each pixel moves across the image
+    /// while the shutter is open will be written to this file as a PFM image. The red channel is
+    /// the distance to the right in pixels and the green channel is the distance up.
+    #[arg(long)]
+    velocity_pass: Option<String>,
+    /// If present, the distance from the camera to the surface seen through each pixel will be
+    /// written to this file as a PFM image, with the same distance in every channel. Pixels that
+    /// see nothing hold infinity.
+    #[arg(long)]
+    depth_pass: Option<String>,
+    /// If present, the scene will be lit by this equirectangular Radiance HDR image instead of
+    /// the default sky.
+    #[arg(long)]
+    environment: Option<String>,
+    /// If present, the scene will be drawn with flat bands of shading and outlined edges instead
+    /// of being path traced. Only one ray is traced per pixel, so this is much faster.
+    #[arg(long)]
+    toon: bool,
+    /// If present, a quick preview of the scene will be drawn with the surfaces that the depth of
+    /// field keeps sharp tinted green instead of being path traced. The preview is drawn in the
+    /// style of `--toon`.
+    #[arg(long)]
+    focus_preview: bool,
+    /// If present, the objects of the scene will be tested against every ray instead of being
+    /// sorted into a bounding volume hierarchy.
+    #[arg(long)]
+    no_bvh: bool,
+    /// The most objects that a leaf of the bounding volume hierarchy may hold.
+    #[arg(long, default_value_t = BvhBuildOptions::default().max_leaf_size)]
+    bvh_leaf_size: usize,
+    /// How the nodes of the bounding volume hierarchy are split.
+    #[arg(long, value_enum, default_value_t = BvhSplit::Sah)]
+    bvh_split: BvhSplit,
+    /// The order in which the children of the nodes of the bounding volume hierarchy are visited.
+    #[arg(long, value_enum, default_value_t = BvhTraversal::SplitAxis)]
+    bvh_traversal: BvhTraversal,
+    /// If present, only the pixels in `--patch-region` of this previously rendered image of the
+    /// same scene will be rendered again, and the image with those pixels replaced will be
+    /// written to the output. Pass the same `--seed` that the image was rendered with when
+    /// patching the random scene.
+    #[arg(long, requires = "patch_region")]
+    patch: Option<String>,
+    /// The pixels to re-render with `--patch`, as `X,Y,WIDTH,HEIGHT` measured from the top-left
+    /// corner of the image.
+    #[arg(long)]
+    patch_region: Option<Region>,
+    /// The number of rays to trace through each pixel re-rendered with `--patch`. Defaults to four
+    /// times the number that the scene normally uses.
+    #[arg(long)]
+    patch_samples: Option<usize>,
+    /// The primaries of the color space that the image is written in. Use `display-p3` or
+    /// `rec2020` for wide-gamut displays that expect them. An image being patched with `--patch`
+    /// must have been written with the same primaries.
+    #[arg(long, value_enum, default_value_t = OutputPrimaries::Srgb)]
+    primaries: OutputPrimaries,
+    /// If present, the image will be rendered in passes that each double the number of rays
+    /// traced through every pixel, and a CSV table of the root-mean-square error of the image
+    /// after each pass will be written to this file. The error is measured against
+    /// `--convergence-reference` if it is given and against the previous pass otherwise.
+    #[arg(long)]
+    convergence: Option<String>,
+    /// The PPM image to measure the error of each pass of `--convergence` against, such as a
+    /// render of the same scene with many more rays per pixel.
+    #[arg(long, requires = "convergence")]
+    convergence_reference: Option<String>,
+    /// How the directions around the camera are laid out on the image. The panoramic projections
+    /// see all the way around the camera from the same point as the usual perspective.
+    #[arg(long, value_enum, default_value_t = CameraProjection::Perspective)]
+    projection: CameraProjection,
+    /// If present, the image will also be written at each of these comma-separated exposure
+    /// offsets in stops, such as `-2,2`, from a single render. Each copy is written next to
+    /// `--out` with the offset added before the extension, so `render.ppm` gets a copy named
+    /// `render.ev-2.ppm`.
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    bracket: Vec<f64>,
+    /// If present, the light reflected by diffuse surfaces will be estimated at sparse points and
+    /// interpolated between them with at most this much error, such as `0.2`. This smooths away
+    /// the noise of the lighting in exchange for blurring small details of it, and is much faster
+    /// than tracing the light at every hit in scenes where it bounces between diffuse surfaces
+    /// many times. The points are chosen as the threads that render the image reach them, so
+    /// the pattern of the smoothing differs between renders even with `--seed`.
+    #[arg(long)]
+    irradiance_cache: Option<f64>,
+    /// The farthest that the light estimated at a point of `--irradiance-cache` may be
+    /// interpolated is this distance times the accuracy of the cache.
+    #[arg(long, default_value_t = 1., requires = "irradiance_cache")]
+    irradiance_spacing: f64,
+    /// How the rays through each pixel are spread over the pixel, the lens, and the exposure.
+    #[arg(long, value_enum, default_value_t = PixelSampler::Stratified)]
+    sampler: PixelSampler,
+    /// If present, ghosts and streaks of light will be added around the parts of the image that
+    /// are brighter than this, such as `1`, as if they were scattered by the lens of a camera. The
+    /// whole image is held in memory to add them, and `--patch` and `--convergence` ignore them.
+    #[arg(long)]
+    lens_flare: Option<f64>,
+    /// The brightness of the flares of `--lens-flare` relative to the light that casts them.
+    #[arg(long, default_value_t = LensFlare::default().strength, requires = "lens_flare")]
+    flare_strength: f64,
+    /// The number of ghosts that each light casts with `--lens-flare`, one for each pair of lens
+    /// elements that reflect it.
+    #[arg(long, default_value_t = LensFlare::default().ghosts, requires = "lens_flare")]
+    flare_ghosts: u32,
+    /// The number of blades of the aperture of the lens for `--lens-flare`, which sets the number
+    /// of streaks around each light. Fewer than three blades cast no streaks.
+    #[arg(long, default_value_t = LensFlare::default().aperture_blades, requires = "lens_flare")]
+    aperture_blades: u32,
+    /// If present, rays will stop being traced through each pixel once its brightness as written
+    /// out is known to within this much with 95% confidence, such as `0.02`, and noisy pixels
+    /// will be given more rays than usual, up to `--adaptive-budget`. Every pixel is first given
+    /// `--adaptive-min-samples` rays, and the rest of the rays that the image normally uses are
+    /// then handed out in proportion to how noisy each pixel still is, so the rays saved on smooth
+    /// areas, such as open sky, are spent on noisy ones. The image is held in memory until it is
+    /// finished.
+    #[arg(long)]
+    adaptive: Option<f64>,
+    /// The number of rays traced through every pixel before `--adaptive` first checks it.
+    #[arg(long, default_value_t = 16, requires = "adaptive")]
+    adaptive_min_samples: usize,
+    /// The number of rays that `--adaptive` hands out to noisy pixels at a time.
+    #[arg(long, default_value_t = 16, requires = "adaptive")]
+    adaptive_batch: usize,
+    /// The most rays that `--adaptive` may trace through a noisy pixel, as a multiple of the
+    /// number that the scene normally uses.
+    #[arg(long, default_value_t = 4., requires = "adaptive")]
+    adaptive_budget: f64,
+    /// If present, everything on one side of this plane will be cut away so that the insides of
+    /// the objects can be seen, given as a point on the plane and a normal pointing toward the
+    /// side to cut away in the form `X,Y,Z,NX,NY,NZ`.
+    #[arg(long, allow_hyphen_values = true)]
+    clip_plane: Option<ClipPlane>,
+    /// If present, the cross sections where `--clip-plane` cuts through closed objects will be
+    /// filled in with a diffuse surface of this color, given as comma-separated red, green, and
+    /// blue components between `0` and `1`.
+    #[arg(long, value_delimiter = ',', requires = "clip_plane")]
+    clip_cap: Vec<f64>,
+    /// How the light reaching the camera along each ray is computed. The integrators other than
+    /// `path` show simplified views of the scene for debugging it.
+    #[arg(long, value_enum, default_value_t = RenderIntegrator::Path)]
+    integrator: RenderIntegrator,
+    /// If present, the caustics that the background casts through mirrors and glass onto diffuse
+    /// surfaces will be gathered from photons traced from the background instead of left for the
+    /// path tracer to find, which it almost never does. The photons are aimed at this sphere
+    /// around the objects that cast the caustics, given as `X,Y,Z,RADIUS`, such as `0,1,0,1` for
+    /// the large glass sphere of the random scene. Caustics cast by objects outside of the sphere
+    /// and by glowing objects are still left to the path tracer.
+    #[arg(long, allow_hyphen_values = true)]
+    caustics: Option<CausticTarget>,
+    /// The number of photons that `--caustics` aims at its sphere. More photons give sharper
+    /// caustics with less blotchy noise.
+    #[arg(long, default_value_t = 200_000, requires = "caustics")]
+    caustic_photons: usize,
+    /// The farthest from a point that the photons of `--caustics` that light it are gathered
+    /// from.
+    #[arg(long, default_value_t = 0.1, requires = "caustics")]
+    caustic_radius: f64,
+    /// If present, the path tracer will learn where the light in the scene comes from over this
+    /// many passes of paths traced before rendering, such as `5`, and scatter rays toward it more
+    /// often. This speeds up the convergence of scenes where light reaches surfaces through narrow
+    /// openings or from small bright areas. Each pass traces twice as many paths as the one
+    /// before. The guide learns from plain paths, so it can't be combined with `--caustics` or
+    /// `--irradiance-cache`.
+    #[arg(long, conflicts_with_all = ["caustics", "irradiance_cache"])]
+    path_guiding: Option<usize>,
+    /// The number of paths that the first pass of `--path-guiding` traces from the camera.
+    #[arg(long, default_value_t = 8192, requires = "path_guiding")]
+    guiding_paths: usize,
+    /// The number of rays that `--integrator path` scatters from the first surface that each
+    /// camera ray hits, averaging the light that they carry back. Raising this cuts the noise of
+    /// glossy surfaces seen directly by the camera more cheaply than raising the number of rays
+    /// per pixel, since the rays scattered after the first bounce aren't split.
+    #[arg(long, default_value_t = 1)]
+    splits: usize,
+    /// The distance from the camera that `--integrator depth` shows as white. Closer surfaces are
+    /// darker.
+    #[arg(long, default_value_t = 10.)]
+    depth_range: f64,
+    /// If present, a diffuse ground plane of this color will replace the ground of the scene and
+    /// be put just beneath the lowest point of the rest of the objects, given as comma-separated
+    /// red, green, and blue components between `0` and `1`.
+    #[arg(long, value_delimiter = ',')]
+    ground_plane: Vec<f64>,
+    /// How far beneath the lowest point of the objects in the scene `--ground-plane` is put.
+    #[arg(long, default_value_t = 0.001, requires = "ground_plane")]
+    ground_gap: f64,
+}
+
+/// The integrators that can be chosen on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum RenderIntegrator {
+    /// Trace the full paths of light through the scene.
+    Path,
+    /// Only gather the light that reaches each surface directly from the sky and glowing
+    /// objects, for a quick preview of the lighting.
+    Direct,
+    /// Color each surface by its normal, with the x, y, and z components as red, green, and blue.
+    Normals,
+    /// Shade each surface in gray by its distance from the camera, out to `--depth-range`.
+    Depth,
+}
+
+/// The samplers that can be chosen on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum PixelSampler {
+    /// Pick every point independently at random.
+    Independent,
+    /// Give each ray its own cell of a grid and pick a random point within it.
+    Stratified,
+    /// Take the points from a scrambled Sobol sequence. This works best when the number of rays
+    /// through each pixel is a power of two.
+    Sobol,
+    /// Take the points from a Halton sequence with its digits shuffled for each pixel.
+    Halton,
+    /// Shift the same points of a Sobol sequence in every pixel by a tile of blue noise, which
+    /// makes the noise of quick previews look like fine grain. This works best when the number of
+    /// rays through each pixel is a power of two.
+    BlueNoise,
+    /// Give each ray its own cell of a grid and its own row and column of the finer grid within
+    /// the cells, by correlated multi-jittered sampling.
+    Cmj,
+}
+
+/// The projections of the camera that can be chosen on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CameraProjection {
+    /// Keep straight lines straight, as through an ordinary lens.
+    Perspective,
+    /// Show every direction with longitude across the image and latitude up it.
+    Equirectangular,
+    /// Show directions through the stereographic projection, with the view straight ahead at the
+    /// center of the image.
+    Stereographic,
+}
+
+/// The color spaces that the image can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputPrimaries {
+    /// sRGB, which most displays expect.
+    Srgb,
+    /// Display P3, which the displays of many recent phones and laptops expect.
+    DisplayP3,
+    /// Rec.2020, which ultra high definition and HDR displays expect.
+    Rec2020,
+}
+
+/// The split strategies of the bounding volume hierarchy that can be chosen on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BvhSplit {
+    /// Split at the middle of the longest axis.
+    Middle,
+    /// Split so that each child gets half of the objects.
+    Median,
+    /// Split where the surface area heuristic predicts the least work.
+    Sah,
+}
+
+/// The traversal orders of the bounding volume hierarchy that can be chosen on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BvhTraversal {
+    /// Always visit the first child first.
+    Fixed,
+    /// Visit first the child that comes first along the split axis in the direction of the ray.
+    SplitAxis,
+    /// Visit first the child whose bounding box the ray enters first.
+    Nearest,
+}
+
+fn create_file(filename: &str) -> io::Result<File> {
+    File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(filename)
+}
+
+fn read_ppm_file(filename: &str) -> io::Result<Image> {
+    Image::read_ppm(&mut BufReader::new(File::open(filename.trim())?))
+}
+
+/// Computes the root-mean-square difference between the channels of two colors as they would be
+/// written to an image.
+fn pixel_error(a: &Color, b: &Color) -> f64 {
+    let clamp = |color: &Color| {
+        Vec3::new(
+            color.red().min(1.),
+            color.green().min(1.),
+            color.blue().min(1.),
+        )
+    };
+    ((clamp(a) - clamp(b)).length_squared() / 3.).sqrt()
+}
+
+/// Computes the root-mean-square difference between the channels of two images of the same size.
+fn rmse(a: &[Color], b: &[Color]) -> f64 {
+    let squared_error = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| pixel_error(a, b).powi(2))
+        .sum::<f64>();
+    (squared_error / a.len().max(1) as f64).sqrt()
+}
+
+/// Maps an error in `[0.0, 1.0]` to a color that runs from black through blue, cyan, green, and
+/// yellow to red.
+fn heatmap_color(error: f64) -> Color {
+    const STOPS: [(f64, f64, f64); 6] = [
+        (0., 0., 0.),
+        (0., 0., 1.),
+        (0., 1., 1.),
+        (0., 1., 0.),
+        (1., 1., 0.),
+        (1., 0., 0.),
+    ];
+    let scaled = error.clamp(0., 1.) * (STOPS.len() - 1) as f64;
+    let index = (scaled.floor() as usize).min(STOPS.len() - 2);
+    let (r0, g0, b0) = STOPS[index];
+    let (r1, g1, b1) = STOPS[index + 1];
+    Color::new(r0, g0, b0).interpolate(&Color::new(r1, g1, b1), scaled - index as f64)
+}
+
+fn diff_images(reference: &str, candidate: &str, heatmap: Option<&str>) -> io::Result<()> {
+    let reference = read_ppm_file(reference)?;
+    let candidate = read_ppm_file(candidate)?;
+    if (reference.width(), reference.height()) != (candidate.width(), candidate.height()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Cannot compare a {}x{} image to a {}x{} image",
+                reference.width(),
+                reference.height(),
+                candidate.width(),
+                candidate.height()
+            ),
+        ));
+    }
+    let errors: Vec<f64> = reference
+        .pixels()
+        .iter()
+        .zip(candidate.pixels())
+        .map(|(a, b)| pixel_error(a, b))
+        .collect();
+    let mean_squared_error =
+        errors.iter().map(|error| error * error).sum::<f64>() / errors.len().max(1) as f64;
+    let rmse = mean_squared_error.sqrt();
+    let max_error = errors.iter().copied().fold(0., f64::max);
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "RMSE: {rmse:.6}")?;
+    writeln!(stdout, "PSNR: {:.2} dB", -10. * mean_squared_error.log10())?;
+    writeln!(stdout, "Max pixel error: {max_error:.6}")?;
+    if let Some(filename) = heatmap {
+        let scale = if max_error > 0. {
+            max_error.recip()
+        } else {
+            0.
+        };
+        let pixels = errors
+            .iter()
+            .map(|error| heatmap_color(error * scale))
+            .collect();
+        Image::from_pixels(reference.width(), reference.height(), pixels)
+            .expect("Error image should have the same dimensions as its inputs")
+            .write_ppm(&mut create_file(filename.trim())?)?;
+    }
+    Ok(())
 }
 
 enum FileOrStdout {
@@ -289,23 +1827,211 @@ impl Write for FileOrStdout {
     }
 }
 
+/// Only files can seek. The standard output may be a pipe, so it never tries.
+impl Seek for FileOrStdout {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Stdout => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "The standard output can't seek",
+            )),
+            Self::File(f) => f.seek(pos),
+        }
+    }
+}
+
 fn main() -> io::Result<()> {
     let args = Args::parse();
+    if let Some(seed) = args.seed {
+        rng::set_seed(seed);
+    }
+    if let Command::Diff {
+        reference,
+        candidate,
+        heatmap,
+    } = &args.command
+    {
+        return diff_images(reference, candidate, heatmap.as_deref());
+    }
+    if let Command::ValidateMaterial { preview, samples } = &args.command {
+        return validate_material(preview, *samples);
+    }
+    if let Command::FuzzScene { cases } = args.command {
+        let seed = args.seed.unwrap_or_else(rand::random);
+        writeln!(io::stderr().lock(), "Fuzzing from seed {seed}")?;
+        return fuzz::fuzz_scenes(seed, cases);
+    }
+    // Read the image to patch before opening the output, which may be the same file.
+    let patch_image = args.patch.as_deref().map(read_ppm_file).transpose()?;
+    let convergence_reference = args
+        .convergence_reference
+        .as_deref()
+        .map(read_ppm_file)
+        .transpose()?;
+    let clip_cap = match args.clip_cap[..] {
+        [] => None,
+        [red, green, blue] => Some(Color::new(red, green, blue)),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--clip-cap takes exactly three components",
+            ))
+        }
+    };
+    let ground_plane = match args.ground_plane[..] {
+        [] => None,
+        [red, green, blue] => Some(Color::new(red, green, blue)),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--ground-plane takes exactly three components",
+            ))
+        }
+    };
+    if !args.bracket.is_empty() && matches!(args.out.trim(), "" | "-") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--bracket needs --out to name a file that the copies can be written next to",
+        ));
+    }
     let mut out = match args.out.trim() {
         "" | "-" => FileOrStdout::Stdout,
-        filename => FileOrStdout::File(
-            File::options()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(filename)?,
-        ),
+        filename => FileOrStdout::File(create_file(filename)?),
+    };
+    let recorder = args
+        .record_paths
+        .as_ref()
+        .map(|_| PathRecorder::new(args.record_probability, args.max_recorded_paths));
+    let environment = args
+        .environment
+        .as_deref()
+        .map(|filename| {
+            Image::read_hdr(&mut BufReader::new(File::open(filename.trim())?))
+                .map(|image| Arc::new(EnvironmentMap::new(image)))
+        })
+        .transpose()?;
+    let overlay = Overlay {
+        axes: args.debug_axes,
+        grid_extent: args.debug_grid,
+        ..Default::default()
+    };
+    let toon_style = ToonStyle::default();
+    let patch = patch_image
+        .as_ref()
+        .zip(args.patch_region)
+        .map(|(image, region)| Patch {
+            image,
+            region,
+            samples_per_pixel: args.patch_samples,
+        });
+    let bvh = BvhBuildOptions {
+        max_leaf_size: args.bvh_leaf_size,
+        split: match args.bvh_split {
+            BvhSplit::Middle => SplitStrategy::Middle,
+            BvhSplit::Median => SplitStrategy::Median,
+            BvhSplit::Sah => BvhBuildOptions::default().split,
+        },
+        traversal: match args.bvh_traversal {
+            BvhTraversal::Fixed => TraversalOrder::Fixed,
+            BvhTraversal::SplitAxis => TraversalOrder::SplitAxis,
+            BvhTraversal::Nearest => TraversalOrder::NearestBox,
+        },
+    };
+    let focus_preview = FocusPreview::default();
+    let brackets = (!args.bracket.is_empty()).then(|| Brackets {
+        out: args.out.trim(),
+        stops: &args.bracket,
+    });
+    let convergence = args.convergence.as_deref().map(|csv| Convergence {
+        csv,
+        reference: convergence_reference.as_ref(),
+    });
+    let irradiance_cache = args
+        .irradiance_cache
+        .map(|accuracy| IrradianceCache::new(accuracy, args.irradiance_spacing));
+    let photon_map = args.caustics.map(|CausticTarget { center, radius }| {
+        PhotonMap::new(center, radius, args.caustic_photons, args.caustic_radius)
+    });
+    let path_guide = args
+        .path_guiding
+        .map(|iterations| PathGuide::new(iterations, args.guiding_paths));
+    // The scrambling follows the seed so that renders with different seeds differ.
+    let sampler_seed = args.seed.unwrap_or_else(rand::random) as u32;
+    let sobol = Sobol::new(sampler_seed);
+    let halton = Halton::new(sampler_seed);
+    let blue_noise = BlueNoise::new(sampler_seed);
+    let cmj = CorrelatedMultiJittered::new(sampler_seed);
+    let lens_flare = args.lens_flare.map(|threshold| LensFlare {
+        threshold,
+        strength: args.flare_strength,
+        ghosts: args.flare_ghosts,
+        aperture_blades: args.aperture_blades,
+        ..Default::default()
+    });
+    let options = SceneOptions {
+        recorder: recorder.as_ref(),
+        near_clip: args.near_clip,
+        far_clip: args.far_clip,
+        overlay: &overlay,
+        band_rows: args.band_rows,
+        velocity_pass: args.velocity_pass.as_deref(),
+        depth_pass: args.depth_pass.as_deref(),
+        environment: environment.as_ref(),
+        toon: args.toon.then_some(&toon_style),
+        focus_preview: args.focus_preview.then_some(&focus_preview),
+        bvh: (!args.no_bvh).then_some(&bvh),
+        patch: patch.as_ref(),
+        primaries: match args.primaries {
+            OutputPrimaries::Srgb => Primaries::Srgb,
+            OutputPrimaries::DisplayP3 => Primaries::DisplayP3,
+            OutputPrimaries::Rec2020 => Primaries::Rec2020,
+        },
+        convergence: convergence.as_ref(),
+        brackets: brackets.as_ref(),
+        projection: match args.projection {
+            CameraProjection::Perspective => Projection::Perspective,
+            CameraProjection::Equirectangular => Projection::Equirectangular,
+            CameraProjection::Stereographic => Projection::Stereographic,
+        },
+        irradiance_cache: irradiance_cache.as_ref(),
+        sampler: match args.sampler {
+            PixelSampler::Independent => &Independent,
+            PixelSampler::Stratified => &Stratified,
+            PixelSampler::Sobol => &sobol,
+            PixelSampler::Halton => &halton,
+            PixelSampler::BlueNoise => &blue_noise,
+            PixelSampler::Cmj => &cmj,
+        },
+        lens_flare: lens_flare.as_ref(),
+        adaptive: args.adaptive.map(|tolerance| AdaptiveSampling {
+            tolerance,
+            min_samples: args.adaptive_min_samples,
+            batch: args.adaptive_batch,
+            budget: args.adaptive_budget,
+        }),
+        clip_plane: args.clip_plane,
+        clip_cap,
+        integrator: args.integrator,
+        photon_map: photon_map.as_ref(),
+        path_guide: path_guide.as_ref(),
+        splits: args.splits,
+        depth_range: args.depth_range,
+        ground_plane,
+        ground_gap: args.ground_gap,
     };
-    match args.scene_type {
-        SceneType::Static => write_static_ppm_image(&mut out),
-        SceneType::Random => write_random_ppm_image(&mut out),
-        SceneType::File { r#in } => {
+    match args.command {
+        Command::Static => write_static_ppm_image(&mut out, &options)?,
+        Command::Random => write_random_ppm_image(&mut out, &options)?,
+        Command::PreviewMaterial(preview) => write_material_preview(&mut out, &options, &preview)?,
+        Command::File { r#in } => {
             todo!("Scene in {in:?}")
         }
+        Command::Diff { .. } | Command::FuzzScene { .. } | Command::ValidateMaterial { .. } => {
+            unreachable!("Commands without an output image are handled before opening the output")
+        }
     }
+    if let (Some(filename), Some(recorder)) = (args.record_paths, recorder) {
+        recorder.write_obj(&mut create_file(filename.trim())?)?;
+    }
+    Ok(())
 }
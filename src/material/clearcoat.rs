@@ -0,0 +1,128 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+use crate::{ray::RayHit, rng::random, Color, Ray, Vec3};
+
+use super::{
+    microfacet::{dielectric_reflectance, reflect, scatter_toward, Ggx, LocalFrame},
+    Material, ScatterRecord,
+};
+
+/// Wraps another material in a thin, clear, glossy coat, like the lacquer on varnished wood or the
+/// clear coat over car paint. Light hitting the outside of the surface is reflected by the coat as
+/// often as the Fresnel equations say a dielectric would reflect it and otherwise reaches the
+/// wrapped material, so the coat shines at grazing angles and the base shows through head-on. The
+/// base gets the light that a smooth coat would let through.
+#[derive(Clone)]
+pub struct Clearcoat {
+    base: Arc<dyn Material>,
+    refractive_index: f64,
+    roughness: f64,
+}
+
+impl Clearcoat {
+    /// Creates a new material that coats `base` in a layer with a refractive index of
+    /// `refractive_index` whose surface is as rough as `roughness`, the perceptual roughness shared
+    /// by all glossy materials. A coat with a `roughness` of `0.0` is perfectly smooth.
+    pub fn new<M>(base: Arc<M>, refractive_index: f64, roughness: f64) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            base,
+            refractive_index,
+            roughness: roughness.max(0.),
+        }
+    }
+}
+
+impl Debug for Clearcoat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clearcoat")
+            .field("base", &self.base.name())
+            .field("refractive_index", &self.refractive_index)
+            .field("roughness", &self.roughness)
+            .finish()
+    }
+}
+
+impl Clearcoat {
+    /// Gets the frame of `ray` at the hit if it arrives from outside of the coat. Light that is
+    /// already under the coat only sees the base.
+    fn coated_frame(ray: &Ray, hit_record: &RayHit) -> Option<LocalFrame> {
+        LocalFrame::new(ray, hit_record).filter(|frame| frame.entering)
+    }
+
+    /// Evaluates the BSDF and the density of scattering `ray` toward `direction`.
+    fn evaluate(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> (Color, f64) {
+        let base = (
+            self.base.bsdf(ray, hit_record, direction),
+            self.base.scattering_pdf(ray, hit_record, direction),
+        );
+        let Some(frame) = Self::coated_frame(ray, hit_record) else {
+            return base;
+        };
+        let ggx = Ggx::from_roughness(self.roughness, self.roughness);
+        let wi = frame.world_to_local(&direction.normalized());
+        let coat = frame.reflection(&ggx, &wi).map_or((0., 0.), |facet| {
+            let reflectance = dielectric_reflectance(frame.wo.dot(&facet.m), self.refractive_index);
+            (reflectance * facet.value, facet.pdf)
+        });
+        let reflectance = dielectric_reflectance(frame.wo.z(), self.refractive_index);
+        (
+            Color::new(coat.0, coat.0, coat.0) + (1. - reflectance) * base.0,
+            reflectance * coat.1 + (1. - reflectance) * base.1,
+        )
+    }
+}
+
+impl Material for Clearcoat {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let Some(frame) = Self::coated_frame(ray, hit_record) else {
+            return self.base.scatter(ray, hit_record);
+        };
+        if random::<f64>() < dielectric_reflectance(frame.wo.z(), self.refractive_index) {
+            let ggx = Ggx::from_roughness(self.roughness, self.roughness);
+            let m = ggx.sample_visible_normal(&frame.wo);
+            let direction = frame.local_to_world(&reflect(&frame.wo, &m));
+            return scatter_toward(
+                ray,
+                hit_record,
+                direction,
+                self.evaluate(ray, hit_record, &direction),
+            );
+        }
+        let record = self.base.scatter(ray, hit_record)?;
+        // The coat can't pick the directions that the base picks without a density, so only the
+        // base scatters light that way.
+        if record.pdf.is_none() {
+            return Some(record);
+        }
+        // Either the coat or the base could have picked the direction, so weight it by both.
+        let direction = *record.direction.direction();
+        let (bsdf, pdf) = self.evaluate(ray, hit_record, &direction);
+        (pdf > 0.).then(|| ScatterRecord {
+            attenuation: bsdf / pdf,
+            pdf: Some(pdf),
+            ..record
+        })
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.evaluate(ray, hit_record, direction).0
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.evaluate(ray, hit_record, direction).1
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        self.base.emitted(ray, hit_record)
+    }
+
+    fn name(&self) -> &'static str {
+        "clearcoat"
+    }
+}
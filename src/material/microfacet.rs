@@ -0,0 +1,450 @@
+use std::{
+    f64::consts::PI,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+use crate::{
+    angle::Angle,
+    ray::RayHit,
+    rng::random,
+    texture::{SolidColor, Texture},
+    Color, Ray, Vec3,
+};
+
+use super::{tangent_frame, Material, ScatterRecord};
+
+/// The GGX (Trowbridge-Reitz) distribution of the normals of the microscopic facets that make up
+/// a rough surface. Vectors are in the tangent space of the surface, with the tangent along `x`,
+/// the bitangent along `y`, and the normal along `z`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    /// The smallest roughness that is simulated. Smoother surfaces make the distribution so
+    /// narrow that floating-point error dominates.
+    const MIN_ALPHA: f64 = 1e-4;
+
+    /// Creates a new distribution with the width `alpha_x` along the tangent and `alpha_y` along
+    /// the bitangent.
+    pub(super) fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            alpha_x: alpha_x.max(Self::MIN_ALPHA),
+            alpha_y: alpha_y.max(Self::MIN_ALPHA),
+        }
+    }
+
+    /// Creates a new distribution from the perceptual roughness along the tangent and the
+    /// bitangent that the glossy materials all take, where the width of the distribution is the
+    /// square of the roughness. Steps in this roughness look like even steps in blurriness, as in
+    /// Blender and glTF.
+    pub(super) fn from_roughness(tangent_roughness: f64, bitangent_roughness: f64) -> Self {
+        Self::new(tangent_roughness.powi(2), bitangent_roughness.powi(2))
+    }
+
+    /// The density of facets with the normal `m` per unit of area of the surface.
+    fn distribution(&self, m: &Vec3) -> f64 {
+        if m.z() <= 0. {
+            return 0.;
+        }
+        let stretched =
+            (m.x() / self.alpha_x).powi(2) + (m.y() / self.alpha_y).powi(2) + m.z().powi(2);
+        1. / (PI * self.alpha_x * self.alpha_y * stretched * stretched)
+    }
+
+    /// The Smith auxiliary function, which measures how much of the surface is hidden by other
+    /// facets when seen from `w`.
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let slope_squared =
+            ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / w.z().powi(2);
+        0.5 * ((1. + slope_squared).sqrt() - 1.)
+    }
+
+    /// The fraction of facets facing `w` that are not hidden from it by other facets.
+    pub(super) fn masking(&self, w: &Vec3) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// The fraction of facets that are seen from both `wo` and `wi`, using the height-correlated
+    /// form of the Smith shadowing-masking function.
+    pub(super) fn shadowing_masking(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Picks the normal of a facet that is visible from `wo`, which must be above the surface, in
+    /// proportion to how much of it is visible, following Heitz (2018).
+    pub(super) fn sample_visible_normal(&self, wo: &Vec3) -> Vec3 {
+        // Stretch the view direction so that the distribution becomes a hemisphere.
+        let stretched =
+            Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).normalized();
+        let length_squared = stretched.x().powi(2) + stretched.y().powi(2);
+        let t1 = if length_squared > 0. {
+            Vec3::new(-stretched.y(), stretched.x(), 0.) / length_squared.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = stretched.cross(&t1);
+        // Pick a point on the projection of the visible half of the hemisphere.
+        let r = random::<f64>().sqrt();
+        let (sin_phi, cos_phi) = Angle::Radians(2. * PI * random::<f64>()).sin_cos();
+        let p1 = r * cos_phi;
+        let s = 0.5 * (1. + stretched.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * sin_phi;
+        let normal = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * stretched;
+        Vec3::new(
+            self.alpha_x * normal.x(),
+            self.alpha_y * normal.y(),
+            normal.z().max(0.),
+        )
+        .normalized()
+    }
+
+    /// Computes the probability density per unit of solid angle with which
+    /// [`sample_visible_normal()`](Self::sample_visible_normal()) picks the normal `m` when seen
+    /// from `wo`.
+    fn visible_normal_pdf(&self, wo: &Vec3, m: &Vec3) -> f64 {
+        self.masking(wo) * wo.dot(m).max(0.) * self.distribution(m) / wo.z()
+    }
+}
+
+/// How much light the facets with a single normal scatter from one direction to another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Facet {
+    /// The normal of the facets.
+    pub(super) m: Vec3,
+    /// The BSDF multiplied by the cosine of the angle between the new direction and the normal of
+    /// the surface, without the Fresnel reflectance or the color of the facets.
+    pub(super) value: f64,
+    /// The probability density per unit of solid angle of picking the new direction by reflecting
+    /// or refracting off of a normal chosen by [`Ggx::sample_visible_normal()`].
+    pub(super) pdf: f64,
+}
+
+impl Facet {
+    /// Scales the scattering by the chance that the facets scatter light this way.
+    pub(super) fn weighted(self, chance: f64) -> Self {
+        Self {
+            value: chance * self.value,
+            pdf: chance * self.pdf,
+            ..self
+        }
+    }
+}
+
+/// Computes the fraction of unpolarized light that is reflected at the boundary between two
+/// dielectrics, where `cos_theta` is the cosine of the angle of incidence and `eta` is the
+/// refractive index on the far side divided by that on the near side.
+pub(super) fn dielectric_reflectance(cos_theta: f64, eta: f64) -> f64 {
+    let sin_squared_t = (1. - cos_theta * cos_theta) / (eta * eta);
+    if sin_squared_t >= 1. {
+        return 1.;
+    }
+    let cos_t = (1. - sin_squared_t).sqrt();
+    let parallel = (eta * cos_theta - cos_t) / (eta * cos_theta + cos_t);
+    let perpendicular = (cos_theta - eta * cos_t) / (cos_theta + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+/// The tangent space at a hit, turned to face the side of the surface that a ray arrived from.
+/// Vectors in this space are laid out as in [`Ggx`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct LocalFrame {
+    tangent: Vec3,
+    bitangent: Vec3,
+    normal: Vec3,
+    /// The direction back along the ray, which is always above the surface.
+    pub(super) wo: Vec3,
+    /// Whether the ray arrived from the front of the surface.
+    pub(super) entering: bool,
+}
+
+impl LocalFrame {
+    /// Creates the frame at `hit_record` for `ray`. Returns `None` if the ray only grazes the
+    /// surface.
+    pub(super) fn new(ray: &Ray, hit_record: &RayHit) -> Option<Self> {
+        let (tangent, _, normal) = tangent_frame(hit_record);
+        let entering = ray.direction().dot(&normal) < 0.;
+        let normal = if entering { normal } else { -normal };
+        let mut frame = Self {
+            tangent,
+            bitangent: normal.cross(&tangent),
+            normal,
+            wo: Vec3::default(),
+            entering,
+        };
+        frame.wo = frame.world_to_local(&-ray.direction().normalized());
+        (frame.wo.z() > 0.).then_some(frame)
+    }
+
+    /// Converts `v` from world space to this frame.
+    pub(super) fn world_to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            v.dot(&self.tangent),
+            v.dot(&self.bitangent),
+            v.dot(&self.normal),
+        )
+    }
+
+    /// Converts `v` from this frame to world space.
+    pub(super) fn local_to_world(&self, v: &Vec3) -> Vec3 {
+        v.x() * self.tangent + v.y() * self.bitangent + v.z() * self.normal
+    }
+
+    /// Evaluates the light reflected toward `wi` by the facets of `ggx`. Returns `None` if `wi` is
+    /// below the surface, where it would be blocked by neighboring facets.
+    pub(super) fn reflection(&self, ggx: &Ggx, wi: &Vec3) -> Option<Facet> {
+        if wi.z() <= 0. {
+            return None;
+        }
+        let m = (self.wo + *wi).normalized();
+        let value = ggx.distribution(&m) * ggx.shadowing_masking(&self.wo, wi) / (4. * self.wo.z());
+        Some(Facet {
+            m,
+            value,
+            pdf: ggx.visible_normal_pdf(&self.wo, &m) / (4. * self.wo.dot(&m)),
+        })
+    }
+
+    /// Evaluates the light refracted toward `wi` by the facets of `ggx`, where `eta` is the
+    /// refractive index on the far side divided by that on the near side. Returns `None` if `wi`
+    /// is above the surface or no facet can refract light toward it.
+    pub(super) fn transmission(&self, ggx: &Ggx, wi: &Vec3, eta: f64) -> Option<Facet> {
+        if wi.z() >= 0. {
+            return None;
+        }
+        let m = self.wo + eta * *wi;
+        if m.near_zero() {
+            return None;
+        }
+        let m = if m.z() < 0. { -m } else { m }.normalized();
+        let (cos_o, cos_i) = (self.wo.dot(&m), wi.dot(&m));
+        if cos_o <= 0. || cos_i >= 0. {
+            return None;
+        }
+        // The change in solid angle between the normal and the refracted direction.
+        let jacobian = eta * eta * -cos_i / (cos_o + eta * cos_i).powi(2);
+        let value = ggx.distribution(&m) * ggx.shadowing_masking(&self.wo, wi) * cos_o * jacobian
+            / self.wo.z();
+        Some(Facet {
+            m,
+            value,
+            pdf: ggx.visible_normal_pdf(&self.wo, &m) * jacobian,
+        })
+    }
+
+    /// Evaluates the light scattered toward `wi` by the facets of `ggx` of a dielectric, as chosen
+    /// by [`sample_dielectric_facet()`], where `eta` is the refractive index on the far side
+    /// divided by that on the near side. Returns the scattering and whether `wi` passes through
+    /// the surface.
+    pub(super) fn dielectric(&self, ggx: &Ggx, wi: &Vec3, eta: f64) -> Option<(Facet, bool)> {
+        let transmitted = wi.z() < 0.;
+        let facet = if transmitted {
+            self.transmission(ggx, wi, eta)
+        } else {
+            self.reflection(ggx, wi)
+        }?;
+        let reflectance = dielectric_reflectance(self.wo.dot(&facet.m), eta);
+        let chance = if transmitted {
+            1. - reflectance
+        } else {
+            reflectance
+        };
+        Some((facet.weighted(chance), transmitted))
+    }
+}
+
+/// Scatters `ray` toward `direction` with the BSDF and density, in that order, that the material
+/// that picked the direction evaluates for it. Returns `None` if the material would never pick
+/// it, such as when a facet reflects light below the surface.
+pub(super) fn scatter_toward(
+    ray: &Ray,
+    hit_record: &RayHit,
+    direction: Vec3,
+    (bsdf, pdf): (Color, f64),
+) -> Option<ScatterRecord> {
+    (pdf > 0.).then(|| ScatterRecord {
+        attenuation: bsdf / pdf,
+        direction: ray.redirected(hit_record.p, direction),
+        pdf: Some(pdf),
+    })
+}
+
+/// Reflects `wo` about the facet normal `m`.
+pub(super) fn reflect(wo: &Vec3, m: &Vec3) -> Vec3 {
+    2. * wo.dot(m) * *m - *wo
+}
+
+/// Picks whether light from `wo` is reflected or refracted by the facet with normal `m` of a
+/// dielectric, where `eta` is the refractive index on the far side divided by that on the near
+/// side. Returns the new direction, or `None` if it is on the wrong side of the surface, where it
+/// would be blocked by neighboring facets.
+pub(super) fn sample_dielectric_facet(wo: &Vec3, m: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta = wo.dot(m);
+    if random::<f64>() < dielectric_reflectance(cos_theta, eta) {
+        Some(reflect(wo, m)).filter(|wi| wi.z() > 0.)
+    } else {
+        let cos_t = (1. - (1. - cos_theta * cos_theta) / (eta * eta)).sqrt();
+        Some((cos_theta / eta - cos_t) * *m - *wo / eta).filter(|wi| wi.z() < 0.)
+    }
+}
+
+/// Computes Schlick's approximation of the reflectance of a surface whose reflectance at normal
+/// incidence is `f0`, where `cos_theta` is the cosine of the angle of incidence.
+pub(super) fn schlick(f0: Color, cos_theta: f64) -> Color {
+    let s = (1. - cos_theta).clamp(0., 1.).powi(5);
+    f0 * (1. - s) + Color::new(s, s, s)
+}
+
+/// A rough surface made of tiny mirror-like facets whose normals follow the GGX distribution,
+/// with shadowing between facets from the Smith model. Like a rough [`Metal`], this produces the
+/// long-tailed highlights and darkened edges of real rough metal. The surface can also let light
+/// through, which makes it frosted glass, or be rougher along one direction than the other, which
+/// makes it brushed metal.
+///
+/// [`Metal`]: super::Metal
+#[derive(Clone)]
+pub struct Microfacet {
+    albedo: Arc<dyn Texture>,
+    /// The perceptual roughness along the tangent and bitangent of the surface.
+    roughness: (f64, f64),
+    refractive_index: Option<f64>,
+}
+
+impl Microfacet {
+    /// Creates a new opaque microfacet material. The albedo is the color reflected straight
+    /// back at normal incidence, and reflections become whiter toward grazing angles.
+    /// `roughness` is the perceptual roughness shared by all glossy materials, from `0.0` for a
+    /// perfect mirror to `1.0` for a very rough surface.
+    pub fn new<T>(albedo: Arc<T>, roughness: f64) -> Self
+    where
+        T: Texture + 'static,
+    {
+        Self::anisotropic(albedo, roughness, roughness)
+    }
+
+    /// Creates a new opaque microfacet material like [`new()`] that is `tangent_roughness` rough
+    /// across the tangent of the surface and `bitangent_roughness` rough across the bitangent.
+    /// Highlights are stretched along the direction that is rougher, so a brushed surface is
+    /// rougher across the direction of the brush strokes. The tangent follows the surface
+    /// coordinates, so on a [`Sphere`](crate::object::Sphere) it runs around the equator.
+    ///
+    /// [`new()`]: Self::new()
+    pub fn anisotropic<T>(albedo: Arc<T>, tangent_roughness: f64, bitangent_roughness: f64) -> Self
+    where
+        T: Texture + 'static,
+    {
+        Self {
+            albedo,
+            roughness: (tangent_roughness, bitangent_roughness),
+            refractive_index: None,
+        }
+    }
+
+    /// Creates a new opaque microfacet material with the same albedo everywhere.
+    pub fn from_color(albedo: Color, roughness: f64) -> Self {
+        Self::new(Arc::new(SolidColor::new(albedo)), roughness)
+    }
+
+    /// Creates a new microfacet material that lets light through like a [`Dielectric`] with the
+    /// given refractive index. Light that passes through the surface is tinted by the albedo,
+    /// and light that is reflected is not.
+    ///
+    /// [`Dielectric`]: super::Dielectric
+    pub fn transmissive<T>(albedo: Arc<T>, roughness: f64, refractive_index: f64) -> Self
+    where
+        T: Texture + 'static,
+    {
+        Self {
+            albedo,
+            roughness: (roughness, roughness),
+            refractive_index: Some(refractive_index),
+        }
+    }
+}
+
+impl Debug for Microfacet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Microfacet")
+            .field("albedo", &self.albedo.name())
+            .field("roughness", &self.roughness)
+            .field("refractive_index", &self.refractive_index)
+            .finish()
+    }
+}
+
+impl Microfacet {
+    /// Gets the refractive index on the far side of the surface divided by that on the near side
+    /// for light that passes through the surface.
+    fn eta(&self, frame: &LocalFrame) -> Option<f64> {
+        self.refractive_index.map(|refractive_index| {
+            if frame.entering {
+                refractive_index
+            } else {
+                refractive_index.recip()
+            }
+        })
+    }
+
+    /// Evaluates the BSDF and the density of scattering `ray` toward `direction`.
+    fn evaluate(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Option<(Color, f64)> {
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let ggx = Ggx::from_roughness(self.roughness.0, self.roughness.1);
+        let wi = frame.world_to_local(&direction.normalized());
+        let albedo = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
+        match self.eta(&frame) {
+            None => {
+                let facet = frame.reflection(&ggx, &wi)?;
+                Some((
+                    facet.value * schlick(albedo, frame.wo.dot(&facet.m)),
+                    facet.pdf,
+                ))
+            }
+            Some(eta) => {
+                let (facet, transmitted) = frame.dielectric(&ggx, &wi, eta)?;
+                // Only the light that passes through the surface is tinted.
+                let tint = if transmitted {
+                    albedo
+                } else {
+                    Color::new(1., 1., 1.)
+                };
+                Some((facet.value * tint, facet.pdf))
+            }
+        }
+    }
+}
+
+impl Material for Microfacet {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let ggx = Ggx::from_roughness(self.roughness.0, self.roughness.1);
+        let m = ggx.sample_visible_normal(&frame.wo);
+        let wi = match self.eta(&frame) {
+            None => reflect(&frame.wo, &m),
+            Some(eta) => sample_dielectric_facet(&frame.wo, &m, eta)?,
+        };
+        let direction = frame.local_to_world(&wi);
+        scatter_toward(
+            ray,
+            hit_record,
+            direction,
+            self.evaluate(ray, hit_record, &direction)?,
+        )
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.evaluate(ray, hit_record, direction)
+            .map_or_else(Color::default, |(bsdf, _)| bsdf)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.evaluate(ray, hit_record, direction)
+            .map_or(0., |(_, pdf)| pdf)
+    }
+
+    fn name(&self) -> &'static str {
+        "microfacet"
+    }
+}
@@ -1,16 +1,114 @@
-use rand::random;
+use std::{
+    f64::consts::PI,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
 
-use crate::{ray::RayHit, Color, Ray, Vec3};
+use crate::{
+    angle::Angle,
+    ray::{Medium, RayHit},
+    rng::random,
+    texture::{SolidColor, Texture},
+    Color, Point3, Ray, Vec3,
+};
+
+mod clearcoat;
+pub use clearcoat::Clearcoat;
+
+mod microfacet;
+pub use microfacet::Microfacet;
+use microfacet::{
+    dielectric_reflectance, reflect, sample_dielectric_facet, scatter_toward, schlick, Ggx,
+    LocalFrame,
+};
+
+mod principled;
+pub use principled::{Principled, PrincipledParameters};
 
 /// A description of how rays scatter off of a surface.
+///
+/// Only [`Dielectric`] keeps track on each ray of which transparent objects the ray is inside of.
+/// Other materials that let light through, such as a transmissive [`Microfacet`] or a
+/// [`Principled`] material with transmission, treat both sides of their surfaces as air, so they
+/// don't bend light by the refractive index of a surrounding object and can't be given priorities.
 pub trait Material: Send + Sync {
     /// Scatters the given ray off of this material with the specified hit.
     fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord>;
 
+    /// Evaluates how much of the light arriving at the hit from `direction` is scattered back
+    /// along `ray`, per unit of solid angle. This is the BSDF of the surface multiplied by the
+    /// cosine of the angle between `direction` and the normal, or the phase function of a medium.
+    /// Materials that only scatter light into isolated directions, such as mirrors and smooth
+    /// glass, return black.
+    fn bsdf(&self, _ray: &Ray, _hit_record: &RayHit, _direction: &Vec3) -> Color {
+        Color::default()
+    }
+
+    /// Computes the probability density per unit of solid angle with which
+    /// [`scatter()`](Self::scatter()) chooses to scatter `ray` toward `direction`. For every
+    /// direction with a positive density, [`bsdf()`](Self::bsdf()) divided by this density is the
+    /// attenuation that `scatter()` reports. Materials that return black from `bsdf()` return `0`.
+    fn scattering_pdf(&self, _ray: &Ray, _hit_record: &RayHit, _direction: &Vec3) -> f64 {
+        0.
+    }
+
+    /// Gets the fraction of the light arriving at the hit that is reflected if this material is
+    /// perfectly diffuse there, so that it reflects light equally in every direction above the
+    /// surface no matter where the light comes from. Returns `None` for every other material.
+    fn diffuse_albedo(&self, _hit_record: &RayHit) -> Option<Color> {
+        None
+    }
+
+    /// The light emitted by this material toward the origin of `ray` at the given hit. Most
+    /// materials do not emit any light.
+    fn emitted(&self, _ray: &Ray, _hit_record: &RayHit) -> Color {
+        Color::default()
+    }
+
+    /// Overrides the maximum number of times that a path can scatter before it is considered to
+    /// be absorbed, for paths that scatter off of this material. This lets glass be given more
+    /// bounces than the rest of the scene, or an expensive material fewer. Returns `None` to use
+    /// the limit of the integrator, which most materials do.
+    fn max_depth(&self) -> Option<usize> {
+        None
+    }
+
     /// The name of the material.
     fn name(&self) -> &'static str;
 }
 
+/// Lets a material that was chosen at runtime be given to the constructors of objects, which take
+/// a concrete material.
+impl Material for Arc<dyn Material> {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        (**self).scatter(ray, hit_record)
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        (**self).bsdf(ray, hit_record, direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        (**self).scattering_pdf(ray, hit_record, direction)
+    }
+
+    fn diffuse_albedo(&self, hit_record: &RayHit) -> Option<Color> {
+        (**self).diffuse_albedo(hit_record)
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        (**self).emitted(ray, hit_record)
+    }
+
+    fn max_depth(&self) -> Option<usize> {
+        (**self).max_depth()
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+}
+
 /// The information produced by calling [`Material::scatter()`].
 #[derive(Clone, Copy, Debug)]
 pub struct ScatterRecord {
@@ -18,69 +116,444 @@ pub struct ScatterRecord {
     pub attenuation: Color,
     /// The direction that this particular ray is scattered.
     pub direction: Ray,
+    /// The probability density per unit of solid angle with which the direction was chosen, as
+    /// given by [`Material::scattering_pdf()`]. This is `None` if the direction was chosen from
+    /// a distribution without a density, such as a mirror reflection, or together with the color
+    /// channel that the rest of the path carries.
+    pub pdf: Option<f64>,
 }
 
 /// A dielectric material allows light to pass through it but will change the angle at its surface
 /// according to its refractive index. The refractive index of air is defined to be 1.
+///
+/// Objects made of dielectrics can be put inside of each other, like an ice cube in a glass of
+/// water, and light will be bent by the ratio of the refractive indices on the two sides of each
+/// surface. Where such objects overlap, such as where the water meets the glass, the space is
+/// considered to be filled by the one with the highest priority. Objects are told apart even if
+/// they share a material, so surfaces that bound the same object, like the two spheres of a
+/// hollow glass sphere, should be grouped into a [`Solid`](crate::object::Solid).
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Dielectric {
     refractive_index: f64,
+    roughness: f64,
+    /// Which material fills the space where objects overlap.
+    priority: u32,
+    /// The coefficient `B` of Cauchy's equation `n = A + B / λ²` in square micrometers.
+    dispersion: f64,
+    /// Whether the surface is the outside of a thin sheet rather than the boundary of a solid.
+    thin: bool,
 }
 
 impl Dielectric {
     /// Creates a new Dielectric material. `refractive_index` is a measure of how much light is
     /// biased toward the inward normal when it enters the material.
     pub fn new(refractive_index: f64) -> Self {
-        Self { refractive_index }
+        Self::rough(refractive_index, 0.)
     }
 
-    fn reflectance(cos_theta: f64, refractive_ratio: f64) -> f64 {
-        // Use Schlick's approximation for reflectance
-        let r0 = ((1. - refractive_ratio) / (1. + refractive_ratio)).powi(2);
-        r0 + (1. - r0) * (1. - cos_theta).powi(5)
+    /// Creates a new Dielectric material whose surface is covered in tiny facets, like frosted
+    /// glass. `roughness` is the perceptual roughness shared by all glossy materials, from `0.0`
+    /// for perfectly smooth glass to `1.0` for glass that scatters light almost diffusely.
+    pub fn rough(refractive_index: f64, roughness: f64) -> Self {
+        Self {
+            refractive_index,
+            roughness: roughness.max(0.),
+            priority: 0,
+            dispersion: 0.,
+            thin: false,
+        }
     }
-}
 
-impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
-        let (eta, eta_prime, normal) = if ray.direction().dot(&hit_record.normal) < 0. {
-            (1., self.refractive_index, hit_record.normal)
-        } else {
-            (self.refractive_index, 1., -hit_record.normal)
+    /// Sets which material fills the space where objects overlap. Where this object overlaps
+    /// one with a lower priority, its surface hides the surface of the other one, and where it
+    /// overlaps one with a higher priority, its own surface is hidden. Materials are created with
+    /// a priority of `0`.
+    pub fn with_priority(self, priority: u32) -> Self {
+        Self { priority, ..self }
+    }
+
+    /// Makes the surface behave like a sheet of the material so thin that light comes out of the
+    /// far side where it went into the near side, like a soap bubble or a window pane. Light that
+    /// passes through isn't bent, and the light reflected back and forth between the two sides of
+    /// the sheet is added to the light reflected by the near side. The surface doesn't need a
+    /// second surface inside it to close the sheet.
+    pub fn with_thin_walls(self) -> Self {
+        Self { thin: true, ..self }
+    }
+
+    /// Computes the fraction of light that a thin sheet reflects, given the fraction
+    /// `reflectance` reflected by each of its sides.
+    fn thin_reflectance(reflectance: f64) -> f64 {
+        // The sum of the light that bounces between the sides an even number of times.
+        2. * reflectance / (1. + reflectance)
+    }
+
+    /// The wavelength in nanometers of the sodium D line, at which the refractive index of a
+    /// dispersive material is given.
+    const D_LINE: f64 = 589.3;
+    /// The wavelengths in nanometers of the hydrogen F and C lines, between which the Abbe number
+    /// measures the change in refractive index.
+    const F_AND_C_LINES: (f64, f64) = (486.1, 656.3);
+    /// The wavelengths in nanometers that stand in for the red, green, and blue channels.
+    const CHANNEL_WAVELENGTHS: [f64; 3] = [610., 550., 465.];
+
+    /// Makes the refractive index vary with the wavelength of light, which splits white light
+    /// into rainbow fringes. The refractive index given when the material was created becomes the
+    /// index for yellow light, and `abbe_number` is how little the index changes from blue to red,
+    /// from about `20` for very dispersive glass through `55` for diamond to `90` for fluorite.
+    /// An infinite `abbe_number` turns dispersion back off.
+    ///
+    /// Rays only carry red, green, and blue light, so the first time that a path passes through a
+    /// dispersive surface, only one of the channels is kept and the others are traced by other
+    /// paths. The path keeps that channel at every dispersive surface after that.
+    pub fn with_abbe_number(self, abbe_number: f64) -> Self {
+        let (f, c) = Self::F_AND_C_LINES;
+        let dispersion = (self.refractive_index - 1.)
+            / (abbe_number * ((f / 1000.).powi(-2) - (c / 1000.).powi(-2)));
+        Self {
+            dispersion: if dispersion.is_finite() {
+                dispersion.max(0.)
+            } else {
+                0.
+            },
+            ..self
+        }
+    }
+
+    /// Gets the refractive index for light with a wavelength of `wavelength` nanometers.
+    pub fn refractive_index_at(&self, wavelength: f64) -> f64 {
+        let inverse_square = |nanometers: f64| (nanometers / 1000.).powi(-2);
+        self.refractive_index
+            + self.dispersion * (inverse_square(wavelength) - inverse_square(Self::D_LINE))
+    }
+
+    /// Scatters `ray` as though the material had a refractive index of `inside` and the medium
+    /// around it had a refractive index of `outside`, attenuating the scattered light by
+    /// `attenuation`.
+    fn scatter_between(
+        &self,
+        ray: &Ray,
+        hit_record: &RayHit,
+        (outside, inside): (f64, f64),
+        attenuation: Color,
+    ) -> Option<ScatterRecord> {
+        if self.roughness > 0. {
+            return self.scatter_rough(ray, hit_record, (outside, inside), attenuation);
+        }
+        let entering = ray.direction().dot(&hit_record.normal) < 0.;
+        let (eta, eta_prime, normal) = match (entering, self.thin) {
+            (true, _) => (outside, inside, hit_record.normal),
+            // Both sides of a thin sheet are outside of it.
+            (false, true) => (outside, inside, -hit_record.normal),
+            (false, false) => (inside, outside, -hit_record.normal),
         };
         let unit_direction = ray.direction().normalized();
         let reflectance =
             Self::reflectance(-unit_direction.dot(&normal.normalized()), eta / eta_prime);
+        let reflectance = if self.thin {
+            Self::thin_reflectance(reflectance)
+        } else {
+            reflectance
+        };
         let direction = if reflectance > random::<f64>() {
             unit_direction.reflect_about(&normal)
+        } else if self.thin {
+            unit_direction
         } else {
             unit_direction.refract(&normal, eta, eta_prime)
         };
-        let direction = Ray::new(hit_record.p, direction);
+        let direction = ray.redirected(hit_record.p, direction);
         Some(ScatterRecord {
             attenuation,
             direction,
+            pdf: None,
         })
     }
 
+    /// Scatters `ray` off of a randomly chosen facet of a rough surface.
+    fn scatter_rough(
+        &self,
+        ray: &Ray,
+        hit_record: &RayHit,
+        (outside, inside): (f64, f64),
+        attenuation: Color,
+    ) -> Option<ScatterRecord> {
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let ggx = Ggx::from_roughness(self.roughness, self.roughness);
+        let m = ggx.sample_visible_normal(&frame.wo);
+        let wi = if self.thin {
+            let reflectance = dielectric_reflectance(frame.wo.dot(&m), inside / outside);
+            let wi = Some(reflect(&frame.wo, &m)).filter(|wi| wi.z() > 0.)?;
+            if random::<f64>() < Self::thin_reflectance(reflectance) {
+                wi
+            } else {
+                // The light leaves the far side of the sheet mirrored across it.
+                Vec3::new(wi.x(), wi.y(), -wi.z())
+            }
+        } else {
+            sample_dielectric_facet(&frame.wo, &m, Self::eta(&frame, (outside, inside)))?
+        };
+        let direction = frame.local_to_world(&wi);
+        let (bsdf, pdf) = self.evaluate_rough(&frame, (outside, inside), &direction)?;
+        scatter_toward(ray, hit_record, direction, (bsdf * attenuation, pdf))
+    }
+
+    /// Gets the refractive index on the far side of the surface divided by that on the near side.
+    fn eta(frame: &LocalFrame, (outside, inside): (f64, f64)) -> f64 {
+        if frame.entering {
+            inside / outside
+        } else {
+            outside / inside
+        }
+    }
+
+    /// Evaluates the BSDF and the density of scattering toward `direction` off of a rough surface
+    /// as [`scatter_rough()`](Self::scatter_rough()) does, without the attenuation.
+    fn evaluate_rough(
+        &self,
+        frame: &LocalFrame,
+        (outside, inside): (f64, f64),
+        direction: &Vec3,
+    ) -> Option<(f64, f64)> {
+        let ggx = Ggx::from_roughness(self.roughness, self.roughness);
+        let wi = frame.world_to_local(&direction.normalized());
+        if !self.thin {
+            let (facet, _) = frame.dielectric(&ggx, &wi, Self::eta(frame, (outside, inside)))?;
+            return Some((facet.value, facet.pdf));
+        }
+        // Light that passes through the sheet was reflected by a facet and mirrored across it.
+        let facet = frame.reflection(&ggx, &Vec3::new(wi.x(), wi.y(), wi.z().abs()))?;
+        let reflectance = Self::thin_reflectance(dielectric_reflectance(
+            frame.wo.dot(&facet.m),
+            inside / outside,
+        ));
+        let facet = facet.weighted(if wi.z() < 0. {
+            1. - reflectance
+        } else {
+            reflectance
+        });
+        Some((facet.value, facet.pdf))
+    }
+
+    /// Evaluates the BSDF and the density of scattering `ray` toward `direction`. Returns `None`
+    /// for smooth surfaces, for surfaces hidden inside of objects that take precedence, and for
+    /// the dispersive surface that picks the channel that a path carries, which scatters each
+    /// channel differently.
+    fn evaluate(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Option<(Color, f64)> {
+        let dispersive = self.dispersion != 0. && !self.thin;
+        if self.roughness == 0. || (dispersive && ray.channel().is_none()) {
+            return None;
+        }
+        let surrounding = ray.media().current(hit_record.object);
+        if !self.thin && surrounding.is_some_and(|medium| medium.priority > self.priority) {
+            return None;
+        }
+        let outside = surrounding.map_or(1., |medium| medium.refractive_index);
+        let (inside, attenuation) = match ray.channel().filter(|_| dispersive) {
+            Some(channel) => {
+                let mut attenuation = [0.; 3];
+                attenuation[channel] = 1.;
+                let [r, g, b] = attenuation;
+                (
+                    self.refractive_index_at(Self::CHANNEL_WAVELENGTHS[channel]),
+                    Color::new(r, g, b),
+                )
+            }
+            None => (self.refractive_index, Color::new(1., 1., 1.)),
+        };
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let (bsdf, pdf) = self.evaluate_rough(&frame, (outside, inside), direction)?;
+        Some((bsdf * attenuation, pdf))
+    }
+
+    fn reflectance(cos_theta: f64, refractive_ratio: f64) -> f64 {
+        // Use Schlick's approximation for reflectance
+        let r0 = ((1. - refractive_ratio) / (1. + refractive_ratio)).powi(2);
+        r0 + (1. - r0) * (1. - cos_theta).powi(5)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let id = hit_record.object;
+        let entering = ray.direction().dot(&hit_record.normal) < 0.;
+        let surrounding = ray.media().current(id);
+        let outside = surrounding.map_or(1., |medium| medium.refractive_index);
+        let medium = Medium {
+            id,
+            refractive_index: self.refractive_index,
+            priority: self.priority,
+        };
+        if !self.thin && surrounding.is_some_and(|medium| medium.priority > self.priority) {
+            // The surface is inside of an object that takes precedence, so the ray passes
+            // through it unchanged.
+            let mut direction = ray.redirected(hit_record.p, *ray.direction());
+            if entering {
+                direction.media_mut().enter(medium);
+            } else {
+                direction.media_mut().leave(id);
+            }
+            return Some(ScatterRecord {
+                attenuation: Color::new(1., 1., 1.),
+                direction,
+                pdf: None,
+            });
+        }
+        // Light that passes through a thin sheet isn't bent, so it isn't split into colors.
+        let (inside, attenuation, channel) = if self.dispersion == 0. || self.thin {
+            (self.refractive_index, Color::new(1., 1., 1.), None)
+        } else {
+            // Keep one channel for the rest of the path, chosen uniformly the first time, and
+            // scale it up to make up for the other two.
+            let (channel, weight) = match ray.channel() {
+                Some(channel) => (channel, 1.),
+                None => (random::<usize>() % 3, 3.),
+            };
+            let mut attenuation = [0.; 3];
+            attenuation[channel] = weight;
+            let [r, g, b] = attenuation;
+            let inside = self.refractive_index_at(Self::CHANNEL_WAVELENGTHS[channel]);
+            (inside, Color::new(r, g, b), Some(channel))
+        };
+        let mut record = self.scatter_between(ray, hit_record, (outside, inside), attenuation)?;
+        if let Some(channel) = channel {
+            if ray.channel().is_none() {
+                // The density doesn't account for picking the channel.
+                record.pdf = None;
+            }
+            record.direction.set_channel(channel);
+        }
+        let transmitted = (record.direction.direction().dot(&hit_record.normal) < 0.) == entering;
+        if transmitted && !self.thin {
+            if entering {
+                record.direction.media_mut().enter(medium);
+            } else {
+                record.direction.media_mut().leave(id);
+            }
+        }
+        Some(record)
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.evaluate(ray, hit_record, direction)
+            .map_or_else(Color::default, |(bsdf, _)| bsdf)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.evaluate(ray, hit_record, direction)
+            .map_or(0., |(_, pdf)| pdf)
+    }
+
     fn name(&self) -> &'static str {
         "Dielectric"
     }
 }
 
-/// A Lambertian material appears equally bright from all angles.
+/// A diffuse light emits the same light in every direction from every point on its surface and
+/// does not reflect any light.
 #[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    /// Creates a new diffuse light. Since channels of `emit` may be greater than `1.0`, the
+    /// brightness of the light can be adjusted by scaling its color.
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+
+    /// Creates a new diffuse light with the hue of `color` and a luminance of `nits` candelas per
+    /// square meter. A channel value of `1.0` stands for [`Color::WHITE_LUMINANCE`].
+    pub fn from_luminance(color: Color, nits: f64) -> Self {
+        let luminance = color.luminance();
+        if luminance > 0. {
+            Self::new(color * (nits / (Color::WHITE_LUMINANCE * luminance)))
+        } else {
+            Self::new(Color::default())
+        }
+    }
+
+    /// Creates a new diffuse light with the color of a blackbody at `kelvin` degrees Kelvin, such
+    /// as `2700` for a warm incandescent bulb or `6500` for overcast daylight, and a luminance of
+    /// `nits` candelas per square meter.
+    pub fn from_temperature(kelvin: f64, nits: f64) -> Self {
+        Self::from_luminance(Color::from_temperature(kelvin), nits)
+    }
+
+    /// Creates a new diffuse light with the hue of `color` for a surface of `area` square meters
+    /// that gives off `lumens` lumens, which is how bulbs and fixtures are rated. A unit of length
+    /// in the scene is taken to be a meter, and only the light given off by one side of the
+    /// surface is counted, so a sphere should be given its surface area.
+    pub fn from_lumens(color: Color, lumens: f64, area: f64) -> Self {
+        Self::from_luminance(color, lumens / (PI * area))
+    }
+
+    /// Creates a new diffuse light like [`from_lumens()`] for a light that draws `watts` watts and
+    /// gives off `efficacy` lumens for each of them, from about `15` for an incandescent bulb to
+    /// about `100` for an LED.
+    ///
+    /// [`from_lumens()`]: Self::from_lumens()
+    pub fn from_watts(color: Color, watts: f64, efficacy: f64, area: f64) -> Self {
+        Self::from_lumens(color, watts * efficacy, area)
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit_record: &RayHit) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit_record: &RayHit) -> Color {
+        self.emit
+    }
+
+    fn name(&self) -> &'static str {
+        "diffuse light"
+    }
+}
+
+/// A Lambertian material appears equally bright from all angles.
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     /// Creates a new Lambertian material. The albedo is the amount of light in each channel that
     /// gets reflected in a scatter event.
-    pub fn new(albedo: Color) -> Self {
+    pub fn new<T>(albedo: Arc<T>) -> Self
+    where
+        T: Texture + 'static,
+    {
         Self { albedo }
     }
+
+    /// Creates a new Lambertian material with the same albedo everywhere.
+    pub fn from_color(albedo: Color) -> Self {
+        Self::new(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// Computes the cosine of the angle between `direction` and the normal on the side of the
+    /// surface that `ray` arrived from, divided by pi, which is both the density with which
+    /// directions are scattered and the BSDF times the cosine for a white surface.
+    fn cosine_pdf(ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        let normal = hit_record.normal.normalized();
+        let facing = if normal.dot(ray.direction()) < 0. {
+            normal
+        } else {
+            -normal
+        };
+        (facing.dot(&direction.normalized()) / PI).max(0.)
+    }
+}
+
+impl Debug for Lambertian {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lambertian")
+            .field("albedo", &self.albedo.name())
+            .finish()
+    }
 }
 
 impl Material for Lambertian {
@@ -95,46 +568,267 @@ impl Material for Lambertian {
             scatter_direction = hit_record.normal;
         }
         Some(ScatterRecord {
-            attenuation: self.albedo,
-            direction: Ray::new(hit_record.p, scatter_direction),
+            attenuation: self.albedo.value(hit_record.u, hit_record.v, &hit_record.p),
+            direction: ray.redirected(hit_record.p, scatter_direction),
+            pdf: Some(Self::cosine_pdf(ray, hit_record, &scatter_direction)),
         })
     }
 
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        Self::cosine_pdf(ray, hit_record, direction)
+            * self.albedo.value(hit_record.u, hit_record.v, &hit_record.p)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        Self::cosine_pdf(ray, hit_record, direction)
+    }
+
+    fn diffuse_albedo(&self, hit_record: &RayHit) -> Option<Color> {
+        Some(self.albedo.value(hit_record.u, hit_record.v, &hit_record.p))
+    }
+
     fn name(&self) -> &'static str {
         "lambertian"
     }
 }
 
-/// A Metal material reflects nearly all light that hits it about its normal vector.
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// A Metal material reflects nearly all light that hits it about its normal vector. Like real
+/// metals, it reflects more light at grazing angles than head-on, and the color of its reflections
+/// fades toward white at the edges of objects.
+#[derive(Clone)]
 pub struct Metal {
-    albedo: Color,
-    fuzziness: f64,
+    reflectance: MetalReflectance,
+    finish: MetalFinish,
+}
+
+/// How blurry the reflections of a [`Metal`] are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetalFinish {
+    /// Reflections are offset by a random point in a sphere with this radius.
+    Fuzzy(f64),
+    /// The surface is made of facets with the perceptual roughness shared by all glossy
+    /// materials.
+    Rough(f64),
+}
+
+/// How much light a [`Metal`] reflects.
+#[derive(Clone)]
+enum MetalReflectance {
+    /// The reflectance at normal incidence, extended to other angles by Schlick's approximation.
+    Albedo(Arc<dyn Texture>),
+    /// The complex refractive index of a conductor at the wavelengths of red, green, and blue
+    /// light, from which the Fresnel equations give the reflectance at every angle.
+    Conductor {
+        /// The real part of the refractive index.
+        eta: Color,
+        /// The imaginary part of the refractive index, which measures how quickly light is
+        /// absorbed inside the metal.
+        k: Color,
+    },
 }
 
 impl Metal {
-    /// Creates a new Metal material. The albedo is the amount of light in each channel that gets
-    /// reflected in a scatter event.
-    pub fn new(albedo: Color, fuzziness: f64) -> Self {
+    /// Creates a new Metal material whose reflections are blurred by offsetting them by a random
+    /// point in a sphere with a radius of `fuzziness`. The albedo is the amount of light in each
+    /// channel that gets reflected in a scatter event when the light hits the surface head-on.
+    #[deprecated(
+        since = "0.1.0",
+        note = "fuzziness doesn't match the roughness of other materials; use `Metal::rough()`"
+    )]
+    pub fn new<T>(albedo: Arc<T>, fuzziness: f64) -> Self
+    where
+        T: Texture + 'static,
+    {
         Self {
-            albedo,
-            fuzziness: fuzziness.clamp(0., 1.),
+            reflectance: MetalReflectance::Albedo(albedo),
+            finish: MetalFinish::Fuzzy(fuzziness.clamp(0., 1.)),
+        }
+    }
+
+    /// Creates a new Metal material with the same albedo everywhere whose reflections are blurred
+    /// by `fuzziness` as described by [`new()`].
+    ///
+    /// [`new()`]: Self::new()
+    #[deprecated(
+        since = "0.1.0",
+        note = "fuzziness doesn't match the roughness of other materials; use \
+                `Metal::rough_from_color()`"
+    )]
+    pub fn from_color(albedo: Color, fuzziness: f64) -> Self {
+        #[allow(deprecated)]
+        Self::new(Arc::new(SolidColor::new(albedo)), fuzziness)
+    }
+
+    /// Creates a new Metal material whose surface is made of tiny facets following the GGX
+    /// distribution. The albedo is the amount of light in each channel that gets reflected in a
+    /// scatter event when the light hits the surface head-on. `roughness` is the perceptual
+    /// roughness shared by all glossy materials, from `0.0` for a perfect mirror to `1.0` for a
+    /// very rough surface, so a metal reflects as blurrily as a [`Dielectric`] or [`Principled`]
+    /// material with the same roughness.
+    pub fn rough<T>(albedo: Arc<T>, roughness: f64) -> Self
+    where
+        T: Texture + 'static,
+    {
+        Self {
+            reflectance: MetalReflectance::Albedo(albedo),
+            finish: MetalFinish::Rough(roughness.clamp(0., 1.)),
+        }
+    }
+
+    /// Creates a new Metal material with the same albedo everywhere that is as rough as
+    /// `roughness`, as described by [`rough()`].
+    ///
+    /// [`rough()`]: Self::rough()
+    pub fn rough_from_color(albedo: Color, roughness: f64) -> Self {
+        Self::rough(Arc::new(SolidColor::new(albedo)), roughness)
+    }
+
+    /// Creates a new Metal material from the complex refractive index `eta + ik` of a conductor
+    /// at the wavelengths of red, green, and blue light that is as rough as `roughness`, as
+    /// described by [`rough()`]. Measured values for many metals can be found in tables of optical
+    /// constants.
+    ///
+    /// [`rough()`]: Self::rough()
+    pub fn conductor(eta: Color, k: Color, roughness: f64) -> Self {
+        Self {
+            reflectance: MetalReflectance::Conductor { eta, k },
+            finish: MetalFinish::Rough(roughness.clamp(0., 1.)),
+        }
+    }
+
+    /// Creates a new Metal material with the optical constants of gold.
+    pub fn gold(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    /// Creates a new Metal material with the optical constants of silver.
+    pub fn silver(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    /// Creates a new Metal material with the optical constants of copper.
+    pub fn copper(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    /// Creates a new Metal material with the optical constants of aluminum.
+    pub fn aluminum(roughness: f64) -> Self {
+        Self::conductor(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    /// Computes the fraction of each channel of the light that is reflected at the hit, where
+    /// `cos_theta` is the cosine of the angle of incidence.
+    fn reflectance(&self, hit_record: &RayHit, cos_theta: f64) -> Color {
+        match &self.reflectance {
+            MetalReflectance::Albedo(albedo) => schlick(
+                albedo.value(hit_record.u, hit_record.v, &hit_record.p),
+                cos_theta,
+            ),
+            MetalReflectance::Conductor { eta, k } => {
+                let channel = |i: usize| conductor_reflectance(cos_theta, eta[i], k[i]);
+                Color::new(channel(0), channel(1), channel(2))
+            }
         }
     }
+
+    /// Evaluates the BSDF and the density of scattering `ray` toward `direction` off of the facets
+    /// of a rough surface. Returns `None` for fuzzy and perfectly smooth surfaces, which can't be
+    /// evaluated.
+    fn evaluate_rough(
+        &self,
+        ray: &Ray,
+        hit_record: &RayHit,
+        direction: &Vec3,
+    ) -> Option<(Color, f64)> {
+        let MetalFinish::Rough(roughness) = self.finish else {
+            return None;
+        };
+        if roughness == 0. {
+            return None;
+        }
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let ggx = Ggx::from_roughness(roughness, roughness);
+        let facet = frame.reflection(&ggx, &frame.world_to_local(&direction.normalized()))?;
+        Some((
+            facet.value * self.reflectance(hit_record, frame.wo.dot(&facet.m)),
+            facet.pdf,
+        ))
+    }
+}
+
+/// Computes the fraction of unpolarized light that is reflected by a conductor with the complex
+/// refractive index `eta + ik`, where `cos_theta` is the cosine of the angle of incidence.
+fn conductor_reflectance(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos_squared = cos_theta.clamp(0., 1.).powi(2);
+    let sin_squared = 1. - cos_squared;
+    let t0 = eta * eta - k * k - sin_squared;
+    let a_squared_plus_b_squared = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let t1 = a_squared_plus_b_squared + cos_squared;
+    let a = (0.5 * (a_squared_plus_b_squared + t0)).max(0.).sqrt();
+    let t2 = 2. * cos_theta.clamp(0., 1.) * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+    let t3 = cos_squared * a_squared_plus_b_squared + sin_squared * sin_squared;
+    let t4 = t2 * sin_squared;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+    (0.5 * (perpendicular + parallel)).clamp(0., 1.)
+}
+
+impl Debug for Metal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Metal");
+        match &self.reflectance {
+            MetalReflectance::Albedo(albedo) => debug.field("albedo", &albedo.name()),
+            MetalReflectance::Conductor { eta, k } => debug.field("eta", eta).field("k", k),
+        };
+        match self.finish {
+            MetalFinish::Fuzzy(fuzziness) => debug.field("fuzziness", &fuzziness),
+            MetalFinish::Rough(roughness) => debug.field("roughness", &roughness),
+        };
+        debug.finish()
+    }
 }
 
 impl Material for Metal {
     fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
-        let reflected = ray
-            .direction()
-            .normalized()
-            .reflect_about(&hit_record.normal);
+        let fuzz = match self.finish {
+            MetalFinish::Fuzzy(fuzziness) => fuzziness * Vec3::random_in_unit_sphere(),
+            MetalFinish::Rough(roughness) if roughness > 0. => {
+                let frame = LocalFrame::new(ray, hit_record)?;
+                let ggx = Ggx::from_roughness(roughness, roughness);
+                let m = ggx.sample_visible_normal(&frame.wo);
+                let direction = frame.local_to_world(&reflect(&frame.wo, &m));
+                return scatter_toward(
+                    ray,
+                    hit_record,
+                    direction,
+                    self.evaluate_rough(ray, hit_record, &direction)?,
+                );
+            }
+            MetalFinish::Rough(_) => Vec3::default(),
+        };
+        let unit_direction = ray.direction().normalized();
+        let reflected = unit_direction.reflect_about(&hit_record.normal);
+        let cos_theta = unit_direction.dot(&hit_record.normal.normalized()).abs();
         Some(ScatterRecord {
-            attenuation: self.albedo,
-            direction: Ray::new(
-                hit_record.p,
-                reflected + self.fuzziness * Vec3::random_in_unit_sphere(),
-            ),
+            attenuation: self.reflectance(hit_record, cos_theta),
+            direction: ray.redirected(hit_record.p, reflected + fuzz),
+            pdf: None,
         })
         .filter(|rec| {
             0. < rec
@@ -144,7 +838,485 @@ impl Material for Metal {
         })
     }
 
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.evaluate_rough(ray, hit_record, direction)
+            .map_or_else(Color::default, |(bsdf, _)| bsdf)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.evaluate_rough(ray, hit_record, direction)
+            .map_or(0., |(_, pdf)| pdf)
+    }
+
     fn name(&self) -> &'static str {
         "metal"
     }
 }
+
+/// An isotropic material scatters light equally in all directions. It is meant to be used as the
+/// phase function of participating media such as smoke and fog rather than on surfaces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    /// Creates a new Isotropic material. The albedo is the amount of light in each channel that
+    /// survives a scatter event.
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            direction: ray.redirected(hit_record.p, Vec3::random_unit_vector()),
+            pdf: Some(1. / (4. * PI)),
+        })
+    }
+
+    fn bsdf(&self, _ray: &Ray, _hit_record: &RayHit, _direction: &Vec3) -> Color {
+        self.albedo / (4. * PI)
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _hit_record: &RayHit, _direction: &Vec3) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn name(&self) -> &'static str {
+        "isotropic"
+    }
+}
+
+/// A phase function for participating media that prefers scattering light forward or backward
+/// according to the Henyey-Greenstein distribution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HenyeyGreenstein {
+    albedo: Color,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    /// Creates a new Henyey-Greenstein phase function. The albedo is the amount of light in each
+    /// channel that survives a scatter event. `g` is the mean cosine of the scattering angle and is
+    /// clamped to `(-1.0, 1.0)`: positive values scatter light forward, as in clouds, negative
+    /// values scatter it back toward its source, and `0.0` is the same as [`Isotropic`].
+    pub fn new(albedo: Color, g: f64) -> Self {
+        Self {
+            albedo,
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+
+    /// Samples the cosine of the angle between the incoming and scattered directions.
+    fn sample_cos_theta(&self) -> f64 {
+        let xi = random::<f64>();
+        if self.g.abs() < 1e-3 {
+            1. - 2. * xi
+        } else {
+            let g = self.g;
+            let s = (1. - g * g) / (1. - g + 2. * g * xi);
+            ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+        }
+    }
+
+    /// Evaluates the distribution of directions scattered from `ray` toward `direction`.
+    fn phase(&self, ray: &Ray, direction: &Vec3) -> f64 {
+        let cos_theta = ray
+            .direction()
+            .normalized()
+            .dot(&direction.normalized())
+            .clamp(-1., 1.);
+        let g = self.g;
+        (1. - g * g) / (4. * PI * (1. + g * g - 2. * g * cos_theta).powf(1.5))
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let forward = ray.direction().normalized();
+        let (u, v) = forward.orthonormal_basis();
+        let cos_theta = self.sample_cos_theta();
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let (sin_phi, cos_phi) = Angle::Radians(2. * PI * random::<f64>()).sin_cos();
+        let direction = sin_theta * cos_phi * u + sin_theta * sin_phi * v + cos_theta * forward;
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            direction: ray.redirected(hit_record.p, direction),
+            pdf: Some(self.phase(ray, &direction)),
+        })
+    }
+
+    fn bsdf(&self, ray: &Ray, _hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.phase(ray, direction) * self.albedo
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, _hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.phase(ray, direction)
+    }
+
+    fn name(&self) -> &'static str {
+        "henyey-greenstein"
+    }
+}
+
+/// Wraps another material and bends the normal of every hit according to a normal map before the
+/// wrapped material sees it. Each color in the normal map encodes a unit vector in the tangent
+/// space of the surface, with red along the tangent, green along the bitangent, and blue along the
+/// normal, so the flat color `(0.5, 0.5, 1.0)` leaves the normal unchanged.
+#[derive(Clone)]
+pub struct NormalMapped {
+    material: Arc<dyn Material>,
+    normal_map: Arc<dyn Texture>,
+}
+
+impl NormalMapped {
+    /// Creates a new material that behaves like `material` with its normals perturbed by
+    /// `normal_map`.
+    pub fn new<M, T>(material: Arc<M>, normal_map: Arc<T>) -> Self
+    where
+        M: Material + 'static,
+        T: Texture + 'static,
+    {
+        Self {
+            material,
+            normal_map,
+        }
+    }
+
+    /// Replaces the normal of `hit_record` with the normal from the normal map.
+    fn perturb(&self, hit_record: &RayHit) -> RayHit {
+        let encoded = self
+            .normal_map
+            .value(hit_record.u, hit_record.v, &hit_record.p);
+        let local = Vec3::new(
+            2. * encoded.red() - 1.,
+            2. * encoded.green() - 1.,
+            2. * encoded.blue() - 1.,
+        );
+        let (tangent, bitangent, normal) = tangent_frame(hit_record);
+        let perturbed = local.x() * tangent + local.y() * bitangent + local.z() * normal;
+        let mut hit_record = hit_record.clone();
+        if !perturbed.near_zero() {
+            hit_record.normal = perturbed.normalized();
+        }
+        hit_record
+    }
+}
+
+/// Computes an orthonormal tangent, bitangent, and normal at the hit.
+fn tangent_frame(hit_record: &RayHit) -> (Vec3, Vec3, Vec3) {
+    let normal = hit_record.normal.normalized();
+    // Transformations can skew the tangent, so make it perpendicular to the normal again.
+    let tangent = hit_record.tangent - hit_record.tangent.dot(&normal) * normal;
+    let tangent = if tangent.near_zero() {
+        normal.orthonormal_basis().0
+    } else {
+        tangent.normalized()
+    };
+    (tangent, normal.cross(&tangent), normal)
+}
+
+impl Debug for NormalMapped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NormalMapped")
+            .field("material", &self.material.name())
+            .field("normal_map", &self.normal_map.name())
+            .finish()
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.perturb(hit_record))
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.material
+            .bsdf(ray, &self.perturb(hit_record), direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.material
+            .scattering_pdf(ray, &self.perturb(hit_record), direction)
+    }
+
+    fn diffuse_albedo(&self, _hit_record: &RayHit) -> Option<Color> {
+        // The irradiance cache and the photon map gather light around the geometric normal, so
+        // they would shade this material as though it were flat.
+        None
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        self.material.emitted(ray, &self.perturb(hit_record))
+    }
+
+    fn max_depth(&self) -> Option<usize> {
+        self.material.max_depth()
+    }
+
+    fn name(&self) -> &'static str {
+        "normal mapped"
+    }
+}
+
+/// Wraps another material and tilts the normal of every hit as if the surface were raised by a
+/// height field before the wrapped material sees it. The height at each point is the average of
+/// the channels of a texture. The slope of the height field is measured by sampling the texture at
+/// nearby points in space, so only textures that vary with position, such as
+/// [`NoiseTexture`](crate::texture::NoiseTexture), produce bumps.
+#[derive(Clone)]
+pub struct Bumped {
+    material: Arc<dyn Material>,
+    height: Arc<dyn Texture>,
+    strength: f64,
+}
+
+impl Bumped {
+    /// The distance between the samples used to measure the slope of the height field.
+    const EPSILON: f64 = 1e-4;
+
+    /// Creates a new material that behaves like `material` with its normals perturbed by the
+    /// heights from `height` multiplied by `strength`.
+    pub fn new<M, T>(material: Arc<M>, height: Arc<T>, strength: f64) -> Self
+    where
+        M: Material + 'static,
+        T: Texture + 'static,
+    {
+        Self {
+            material,
+            height,
+            strength,
+        }
+    }
+
+    fn height_at(&self, hit_record: &RayHit, p: &Point3) -> f64 {
+        let color = self.height.value(hit_record.u, hit_record.v, p);
+        self.strength * (color.red() + color.green() + color.blue()) / 3.
+    }
+
+    /// Replaces the normal of `hit_record` with the normal of the bumpy surface.
+    fn perturb(&self, hit_record: &RayHit) -> RayHit {
+        let (tangent, bitangent, normal) = tangent_frame(hit_record);
+        let slope = |direction: Vec3| {
+            let offset = Self::EPSILON * direction;
+            (self.height_at(hit_record, &(hit_record.p + offset))
+                - self.height_at(hit_record, &(hit_record.p - offset)))
+                / (2. * Self::EPSILON)
+        };
+        let perturbed = normal - slope(tangent) * tangent - slope(bitangent) * bitangent;
+        let mut hit_record = hit_record.clone();
+        hit_record.normal = perturbed.normalized();
+        hit_record
+    }
+}
+
+impl Debug for Bumped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bumped")
+            .field("material", &self.material.name())
+            .field("height", &self.height.name())
+            .field("strength", &self.strength)
+            .finish()
+    }
+}
+
+impl Material for Bumped {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        self.material.scatter(ray, &self.perturb(hit_record))
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.material
+            .bsdf(ray, &self.perturb(hit_record), direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.material
+            .scattering_pdf(ray, &self.perturb(hit_record), direction)
+    }
+
+    fn diffuse_albedo(&self, _hit_record: &RayHit) -> Option<Color> {
+        // The irradiance cache and the photon map gather light around the geometric normal, so
+        // they would shade this material as though it were flat.
+        None
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        self.material.emitted(ray, &self.perturb(hit_record))
+    }
+
+    fn max_depth(&self) -> Option<usize> {
+        self.material.max_depth()
+    }
+
+    fn name(&self) -> &'static str {
+        "bumped"
+    }
+}
+
+/// Wraps another material and gives the paths that scatter off of it their own maximum number of
+/// scatterings, such as a higher limit for glass so that light makes it through many panes while
+/// the rest of the scene stops bouncing early.
+#[derive(Clone)]
+pub struct DepthOverride {
+    material: Arc<dyn Material>,
+    max_depth: usize,
+}
+
+impl DepthOverride {
+    /// Creates a new material that behaves like `material` except that paths scattering off of it
+    /// are absorbed once they have scattered `max_depth` times.
+    pub fn new<M>(material: Arc<M>, max_depth: usize) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            material,
+            max_depth,
+        }
+    }
+}
+
+impl Debug for DepthOverride {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DepthOverride")
+            .field("material", &self.material.name())
+            .field("max_depth", &self.max_depth)
+            .finish()
+    }
+}
+
+impl Material for DepthOverride {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        self.material.scatter(ray, hit_record)
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.material.bsdf(ray, hit_record, direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.material.scattering_pdf(ray, hit_record, direction)
+    }
+
+    fn diffuse_albedo(&self, hit_record: &RayHit) -> Option<Color> {
+        self.material.diffuse_albedo(hit_record)
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        self.material.emitted(ray, hit_record)
+    }
+
+    fn max_depth(&self) -> Option<usize> {
+        Some(self.max_depth)
+    }
+
+    fn name(&self) -> &'static str {
+        "depth override"
+    }
+}
+
+/// Blends two materials by picking one of them at random each time light scatters off of the
+/// surface. The chance of picking the second material is the average of the channels of a
+/// texture, so the blend can vary across the surface, like paint that has worn down to the metal
+/// underneath in places.
+#[derive(Clone)]
+pub struct Mix {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    factor: Arc<dyn Texture>,
+}
+
+impl Mix {
+    /// Creates a new material that behaves like `first` where `factor` is black, like `second`
+    /// where `factor` is white, and like a blend of the two in between.
+    pub fn new<A, B, T>(first: Arc<A>, second: Arc<B>, factor: Arc<T>) -> Self
+    where
+        A: Material + 'static,
+        B: Material + 'static,
+        T: Texture + 'static,
+    {
+        Self {
+            first,
+            second,
+            factor,
+        }
+    }
+
+    /// Creates a new material that behaves like `second` a fraction `factor` of the time and like
+    /// `first` the rest of the time everywhere on the surface.
+    pub fn from_factor<A, B>(first: Arc<A>, second: Arc<B>, factor: f64) -> Self
+    where
+        A: Material + 'static,
+        B: Material + 'static,
+    {
+        Self::new(
+            first,
+            second,
+            Arc::new(SolidColor::new(Color::new(factor, factor, factor))),
+        )
+    }
+
+    /// Gets the chance of picking the second material at the hit.
+    fn factor_at(&self, hit_record: &RayHit) -> f64 {
+        let color = self.factor.value(hit_record.u, hit_record.v, &hit_record.p);
+        ((color.red() + color.green() + color.blue()) / 3.).clamp(0., 1.)
+    }
+}
+
+impl Debug for Mix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mix")
+            .field("first", &self.first.name())
+            .field("second", &self.second.name())
+            .field("factor", &self.factor.name())
+            .finish()
+    }
+}
+
+impl Material for Mix {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let record = if random::<f64>() < self.factor_at(hit_record) {
+            self.second.scatter(ray, hit_record)
+        } else {
+            self.first.scatter(ray, hit_record)
+        }?;
+        if record.pdf.is_none() {
+            return Some(record);
+        }
+        // Either material could have chosen the direction, so weight it by both of them.
+        let direction = *record.direction.direction();
+        let pdf = self.scattering_pdf(ray, hit_record, &direction);
+        (pdf > 0.).then(|| ScatterRecord {
+            attenuation: self.bsdf(ray, hit_record, &direction) / pdf,
+            pdf: Some(pdf),
+            ..record
+        })
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        let factor = self.factor_at(hit_record);
+        (1. - factor) * self.first.bsdf(ray, hit_record, direction)
+            + factor * self.second.bsdf(ray, hit_record, direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        let factor = self.factor_at(hit_record);
+        (1. - factor) * self.first.scattering_pdf(ray, hit_record, direction)
+            + factor * self.second.scattering_pdf(ray, hit_record, direction)
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &RayHit) -> Color {
+        // Emission doesn't need a random choice, so the light of both materials is blended.
+        let factor = self.factor_at(hit_record);
+        (1. - factor) * self.first.emitted(ray, hit_record)
+            + factor * self.second.emitted(ray, hit_record)
+    }
+
+    fn name(&self) -> &'static str {
+        "mix"
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    f64::consts::PI,
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+use crate::{
+    ray::RayHit,
+    rng::random,
+    texture::{SolidColor, Texture},
+    Color, Ray, Vec3,
+};
+
+use super::{
+    microfacet::{reflect, sample_dielectric_facet, scatter_toward, schlick, Ggx, LocalFrame},
+    Material, ScatterRecord,
+};
+
+/// The sliders of a [`Principled`] material. Each slider goes from `0.0` to `1.0`, and they mean
+/// the same thing as the ones of the same names in Blender and the metallic-roughness materials
+/// of glTF.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrincipledParameters {
+    /// How much the surface behaves like a metal, which reflects light tinted by the base color,
+    /// instead of like a dielectric, which is diffuse or transparent under a clear glossy coat.
+    pub metallic: f64,
+    /// How blurry the glossy reflections and refractions are. The width of their GGX distribution
+    /// is the square of this.
+    pub roughness: f64,
+    /// The strength of the glossy reflections of dielectrics. The default of `0.5` is a
+    /// reflectance of 4% at normal incidence, which matches a refractive index of 1.5.
+    pub specular: f64,
+    /// How much of the light that gets past the glossy coat of a dielectric passes through the
+    /// surface instead of being scattered diffusely.
+    pub transmission: f64,
+}
+
+impl Default for PrincipledParameters {
+    fn default() -> Self {
+        Self {
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            transmission: 0.,
+        }
+    }
+}
+
+/// A single material that can stand in for diffuse, metallic, glossy, and glassy surfaces and
+/// blends between them with a few sliders, in the spirit of the principled BSDF of Burley
+/// (2012). It is a stochastic mixture of a metal, a rough dielectric, and a diffuse base under a
+/// glossy coat, so every combination of sliders reflects no more light than it receives.
+#[derive(Clone)]
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    parameters: PrincipledParameters,
+}
+
+impl Principled {
+    /// Creates a new principled material. The base color is the color of diffuse reflections,
+    /// of metallic reflections at normal incidence, and of light that passes through the surface.
+    pub fn new<T>(base_color: Arc<T>, parameters: PrincipledParameters) -> Self
+    where
+        T: Texture + 'static,
+    {
+        let clamp = |slider: f64| slider.clamp(0., 1.);
+        Self {
+            base_color,
+            parameters: PrincipledParameters {
+                metallic: clamp(parameters.metallic),
+                roughness: clamp(parameters.roughness),
+                specular: clamp(parameters.specular),
+                transmission: clamp(parameters.transmission),
+            },
+        }
+    }
+
+    /// Creates a new principled material with the same base color everywhere.
+    pub fn from_color(base_color: Color, parameters: PrincipledParameters) -> Self {
+        Self::new(Arc::new(SolidColor::new(base_color)), parameters)
+    }
+
+    /// Gets the sliders of the material.
+    pub fn parameters(&self) -> &PrincipledParameters {
+        &self.parameters
+    }
+
+    /// The reflectance at normal incidence of the glossy coat of dielectrics.
+    fn dielectric_f0(&self) -> f64 {
+        0.08 * self.parameters.specular
+    }
+
+    /// Computes the fraction of light that the glossy coat of dielectrics reflects, where
+    /// `cos_theta` is the cosine of the angle of incidence.
+    fn coat_reflectance(&self, cos_theta: f64) -> f64 {
+        let f0 = self.dielectric_f0();
+        schlick(Color::new(f0, f0, f0), cos_theta).green()
+    }
+
+    /// Gets the refractive index on the far side of the surface divided by that on the near side
+    /// for light that passes through the surface.
+    fn eta(&self, frame: &LocalFrame) -> f64 {
+        // The refractive index whose reflectance at normal incidence matches the coat.
+        let root_f0 = self.dielectric_f0().sqrt();
+        let refractive_index = (1. + root_f0) / (1. - root_f0);
+        if frame.entering {
+            refractive_index
+        } else {
+            refractive_index.recip()
+        }
+    }
+
+    /// Evaluates the BSDF and the density of scattering `ray` toward `direction`, adding up the
+    /// metal, the rough dielectric, the coat, and the diffuse base, each weighted by the chance
+    /// that [`scatter()`](Material::scatter()) picks it.
+    fn evaluate(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Option<(Color, f64)> {
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let ggx = Ggx::from_roughness(self.parameters.roughness, self.parameters.roughness);
+        let wi = frame.world_to_local(&direction.normalized());
+        let base_color = self
+            .base_color
+            .value(hit_record.u, hit_record.v, &hit_record.p);
+        let PrincipledParameters {
+            metallic,
+            transmission,
+            ..
+        } = self.parameters;
+        let opaque = (1. - metallic) * (1. - transmission);
+        let coat = self.coat_reflectance(frame.wo.z());
+        let mut bsdf = Color::default();
+        let mut pdf = 0.;
+        if let Some(facet) = frame.reflection(&ggx, &wi) {
+            let cos_theta = frame.wo.dot(&facet.m);
+            let reflectance = self.coat_reflectance(cos_theta);
+            bsdf += metallic * facet.value * schlick(base_color, cos_theta)
+                + opaque * facet.value * Color::new(reflectance, reflectance, reflectance);
+            pdf += (metallic + opaque * coat) * facet.pdf;
+            let cosine_pdf = wi.z() / PI;
+            bsdf += opaque * (1. - coat) * cosine_pdf * base_color;
+            pdf += opaque * (1. - coat) * cosine_pdf;
+        }
+        if let Some((facet, transmitted)) = frame.dielectric(&ggx, &wi, self.eta(&frame)) {
+            let tint = if transmitted {
+                base_color
+            } else {
+                Color::new(1., 1., 1.)
+            };
+            bsdf += (1. - metallic) * transmission * facet.value * tint;
+            pdf += (1. - metallic) * transmission * facet.pdf;
+        }
+        Some((bsdf, pdf))
+    }
+}
+
+impl Debug for Principled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Principled")
+            .field("base_color", &self.base_color.name())
+            .field("parameters", &self.parameters)
+            .finish()
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit_record: &RayHit) -> Option<ScatterRecord> {
+        let frame = LocalFrame::new(ray, hit_record)?;
+        let ggx = Ggx::from_roughness(self.parameters.roughness, self.parameters.roughness);
+        let m = ggx.sample_visible_normal(&frame.wo);
+        let wi = if random::<f64>() < self.parameters.metallic {
+            reflect(&frame.wo, &m)
+        } else if random::<f64>() < self.parameters.transmission {
+            sample_dielectric_facet(&frame.wo, &m, self.eta(&frame))?
+        } else if random::<f64>() < self.coat_reflectance(frame.wo.z()) {
+            reflect(&frame.wo, &m)
+        } else {
+            let wi = Vec3::random_unit_vector() + Vec3::new(0., 0., 1.);
+            if wi.near_zero() {
+                Vec3::new(0., 0., 1.)
+            } else {
+                wi
+            }
+        };
+        let direction = frame.local_to_world(&wi);
+        scatter_toward(
+            ray,
+            hit_record,
+            direction,
+            self.evaluate(ray, hit_record, &direction)?,
+        )
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> Color {
+        self.evaluate(ray, hit_record, direction)
+            .map_or_else(Color::default, |(bsdf, _)| bsdf)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_record: &RayHit, direction: &Vec3) -> f64 {
+        self.evaluate(ray, hit_record, direction)
+            .map_or(0., |(_, pdf)| pdf)
+    }
+
+    fn name(&self) -> &'static str {
+        "principled"
+    }
+}
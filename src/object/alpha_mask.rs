@@ -0,0 +1,82 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{Hittable, RayHit},
+    Material, Ray, Texture,
+};
+
+/// An object with holes cut out of its surface by an opacity texture. Rays pass straight through
+/// the parts of the surface where the opacity is below a threshold, which is cheaper than modeling
+/// the holes for things like leaves and fences.
+#[derive(Clone)]
+pub struct AlphaMask {
+    object: Arc<dyn Hittable>,
+    alpha: Arc<dyn Texture>,
+    threshold: f64,
+}
+
+impl AlphaMask {
+    /// The maximum number of cut out hits that a single ray can pass through before the object is
+    /// considered to be missed.
+    const MAX_PASSES: usize = 64;
+
+    /// Wraps `object` so that its surface is only solid where the average of the channels of
+    /// `alpha` is at least `threshold`.
+    pub fn new<T>(object: Arc<dyn Hittable>, alpha: Arc<T>, threshold: f64) -> Self
+    where
+        T: Texture + 'static,
+    {
+        Self {
+            object,
+            alpha,
+            threshold,
+        }
+    }
+
+    /// Gets the opacity below which the surface is cut away.
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Checks whether the surface is solid at the hit.
+    fn is_opaque(&self, hit: &RayHit) -> bool {
+        let alpha = self.alpha.value(hit.u, hit.v, &hit.p);
+        (alpha.red() + alpha.green() + alpha.blue()) / 3. >= self.threshold
+    }
+}
+
+impl Debug for AlphaMask {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlphaMask")
+            .field("alpha", &self.alpha.name())
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for AlphaMask {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let mut start = *valid_t.start();
+        for _ in 0..Self::MAX_PASSES {
+            let hit = self.object.hit_by(ray, start..=*valid_t.end())?;
+            if self.is_opaque(&hit) {
+                return Some(hit);
+            }
+            start = hit.t.next_up();
+        }
+        None
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.object.bounding_box(time)
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.object.materials()
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{object_id, Hittable, RayHit},
+    Material, Point3, Ray, Vec3,
+};
+
+/// An object with everything on one side of a plane cut away, so that its interior can be seen
+/// in a cutaway view. Where the plane passes through the inside of a closed object, the cut can
+/// be capped with a flat cross section made of a material of its own. Capping relies on the
+/// normals of the object pointing out of it, and on the object not overlapping itself.
+#[derive(Clone)]
+pub struct Clipped {
+    object: Arc<dyn Hittable>,
+    point: Point3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    cap: Option<Arc<dyn Material>>,
+}
+
+impl Clipped {
+    /// The maximum number of cut away hits that a single ray can pass through before the object
+    /// is considered to be missed.
+    const MAX_PASSES: usize = 64;
+
+    /// Wraps `object` so that the part of it on the side of the plane through `point` that
+    /// `normal` points toward is cut away.
+    pub fn new(object: Arc<dyn Hittable>, point: Point3, normal: Vec3) -> Self {
+        let normal = normal.normalized();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Self {
+            object,
+            point,
+            normal,
+            tangent,
+            bitangent,
+            cap: None,
+        }
+    }
+
+    /// Fills the cross section where the plane cuts through the inside of the object with
+    /// `material`.
+    pub fn with_cap<M>(self, material: Arc<M>) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            cap: Some(material),
+            ..self
+        }
+    }
+
+    /// Checks whether `p` is in the part of the object that is kept.
+    fn is_kept(&self, p: &Point3) -> bool {
+        (*p - self.point).dot(&self.normal) <= 0.
+    }
+
+    /// Builds the hit of `ray` on the cap at `t`.
+    fn cap_hit(&self, ray: &Ray, t: f64, material: &Arc<dyn Material>) -> RayHit {
+        let p = ray.at(t);
+        let offset = p - self.point;
+        RayHit {
+            p,
+            normal: self.normal,
+            tangent: self.tangent,
+            material: Arc::clone(material),
+            object: object_id(self),
+            t,
+            u: offset.dot(&self.tangent),
+            v: offset.dot(&self.bitangent),
+            velocity: Vec3::default(),
+            curvature: 0.,
+        }
+    }
+}
+
+impl Debug for Clipped {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clipped")
+            .field("point", &self.point)
+            .field("normal", &self.normal)
+            .field("cap", &self.cap.as_ref().map(|cap| cap.name()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Clipped {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let approach = ray.direction().dot(&self.normal);
+        let crossing = (approach != 0.)
+            .then(|| (self.point - *ray.origin()).dot(&self.normal) / approach)
+            .filter(|t| valid_t.contains(t));
+        let mut start = *valid_t.start();
+        for _ in 0..Self::MAX_PASSES {
+            let hit = self.object.hit_by(ray, start..=*valid_t.end());
+            if let (Some(cap), Some(crossing)) = (&self.cap, crossing) {
+                // The ray is inside the object where it crosses the plane if the next surface
+                // that it hits faces away from it.
+                let inside = hit
+                    .as_ref()
+                    .is_some_and(|hit| crossing <= hit.t && hit.normal.dot(ray.direction()) > 0.);
+                if start <= crossing && inside {
+                    return Some(self.cap_hit(ray, crossing, cap));
+                }
+            }
+            let mut hit = hit?;
+            if self.is_kept(&hit.p) {
+                // The cap and the rest of the surface enclose the same space.
+                hit.object = object_id(self);
+                return Some(hit);
+            }
+            start = hit.t.next_up();
+        }
+        None
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.object.bounding_box(time)
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        let mut materials = self.object.materials();
+        materials.extend(self.cap.iter().cloned());
+        materials
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    f64::consts::PI,
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    angle::Angle,
+    bvh::Aabb,
+    ray::{object_id, Hittable, RayHit, Surface},
+    Material, Point3, Ray, Vec3,
+};
+
+/// A finite right circular cylinder.
+#[derive(Clone)]
+pub struct Cylinder {
+    base: Point3,
+    axis: Vec3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    /// Creates a new cylinder whose bottom face is centered at `base` and which extends `height`
+    /// units in the direction of `axis`. If `capped` is `false`, the ends of the cylinder are left
+    /// open so that the inside of the tube is visible.
+    pub fn new<M>(
+        base: Point3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: Arc<M>,
+    ) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            base,
+            axis: axis.normalized(),
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+
+    /// Gets the center of the bottom face of the cylinder.
+    pub fn base(&self) -> Point3 {
+        self.base
+    }
+
+    /// Gets the unit vector from the bottom face of the cylinder toward the top face.
+    pub fn axis(&self) -> Vec3 {
+        self.axis
+    }
+
+    /// Gets the radius of the cylinder.
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Gets the distance between the two faces of the cylinder.
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// Whether the ends of the cylinder are closed.
+    pub fn capped(&self) -> bool {
+        self.capped
+    }
+
+    /// Computes the component of `v` that is perpendicular to the axis of the cylinder.
+    fn perpendicular(&self, v: Vec3) -> Vec3 {
+        v - v.dot(&self.axis) * self.axis
+    }
+
+    /// Finds the lowest `t` in `valid_t` at which the ray hits the curved side of the cylinder.
+    fn side_hit(&self, ray: &Ray, valid_t: &RangeInclusive<f64>) -> Option<(f64, Vec3)> {
+        let co = *ray.origin() - self.base;
+        let co_perp = self.perpendicular(co);
+        let d_perp = self.perpendicular(*ray.direction());
+        let a = d_perp.length_squared();
+        if a == 0. {
+            // The ray is parallel to the axis so it can only hit a cap.
+            return None;
+        }
+        let half_b = co_perp.dot(&d_perp);
+        let c = co_perp.length_squared() - self.radius.powi(2);
+        let quarter_discriminant = half_b * half_b - a * c;
+        if quarter_discriminant < 0. {
+            return None;
+        }
+        let half_sdiscriminant = quarter_discriminant.sqrt();
+        let t0 = (-half_b - half_sdiscriminant) / a;
+        let t1 = (-half_b + half_sdiscriminant) / a;
+        [t0, t1]
+            .into_iter()
+            .filter(|t| valid_t.contains(t))
+            .find(|&t| (0.0..=self.height).contains(&(ray.at(t) - self.base).dot(&self.axis)))
+            .map(|t| {
+                let normal = self.perpendicular(ray.at(t) - self.base) / self.radius;
+                (t, normal)
+            })
+    }
+
+    /// Finds the lowest `t` in `valid_t` at which the ray hits one of the caps of the cylinder.
+    fn cap_hit(&self, ray: &Ray, valid_t: &RangeInclusive<f64>) -> Option<(f64, Vec3)> {
+        if !self.capped {
+            return None;
+        }
+        let speed = ray.direction().dot(&self.axis);
+        if speed == 0. {
+            return None;
+        }
+        let start = (*ray.origin() - self.base).dot(&self.axis);
+        [(0., -self.axis), (self.height, self.axis)]
+            .into_iter()
+            .map(|(offset, normal)| ((offset - start) / speed, normal))
+            .filter(|(t, _)| valid_t.contains(t))
+            .filter(|(t, _)| {
+                self.perpendicular(ray.at(*t) - self.base).length_squared() <= self.radius.powi(2)
+            })
+            .min_by(|(t0, _), (t1, _)| t0.total_cmp(t1))
+    }
+}
+
+impl Debug for Cylinder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cylinder")
+            .field("base", &self.base)
+            .field("axis", &self.axis)
+            .field("radius", &self.radius)
+            .field("height", &self.height)
+            .field("capped", &self.capped)
+            .field("material", &self.material.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let (t, normal) = match (self.side_hit(ray, &valid_t), self.cap_hit(ray, &valid_t)) {
+            (Some(side), Some(cap)) => {
+                if side.0 <= cap.0 {
+                    side
+                } else {
+                    cap
+                }
+            }
+            (Some(hit), None) | (None, Some(hit)) => hit,
+            (None, None) => return None,
+        };
+        Some(RayHit {
+            p: ray.at(t),
+            normal,
+            tangent: normal.orthonormal_basis().0,
+            t,
+            material: Arc::clone(&self.material),
+            object: object_id(self),
+            u: 0.,
+            v: 0.,
+            velocity: Vec3::default(),
+            curvature: if normal.dot(&self.axis).abs() > 0.5 {
+                0.
+            } else {
+                0.5 / self.radius
+            },
+        })
+    }
+
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        // How far the rim of each end reaches along each coordinate axis.
+        let reach =
+            |component: f64| self.radius.abs() * (1. - component * component).max(0.).sqrt();
+        let reach = Vec3::new(
+            reach(self.axis.x()),
+            reach(self.axis.y()),
+            reach(self.axis.z()),
+        );
+        let top = self.base + self.height * self.axis;
+        Some(
+            Aabb::new(self.base - reach, self.base + reach)
+                .union(&Aabb::new(top - reach, top + reach)),
+        )
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.material)]
+    }
+}
+
+impl Surface for Cylinder {
+    fn area(&self) -> f64 {
+        let side = 2. * PI * self.radius * self.height;
+        if self.capped {
+            side + 2. * PI * self.radius.powi(2)
+        } else {
+            side
+        }
+    }
+
+    fn sample_surface(&self, rng: &mut dyn RngCore) -> (Point3, Vec3) {
+        let (u, v) = self.axis.orthonormal_basis();
+        let (sin_phi, cos_phi) = Angle::Radians(2. * PI * rng.gen::<f64>()).sin_cos();
+        let radial = cos_phi * u + sin_phi * v;
+        let side = 2. * PI * self.radius * self.height;
+        if rng.gen::<f64>() * self.area() < side {
+            let p = self.base + self.radius * radial + rng.gen::<f64>() * self.height * self.axis;
+            (p, radial)
+        } else {
+            // Taking the square root keeps the points uniform over the area of the cap.
+            let p = self.base + self.radius * rng.gen::<f64>().sqrt() * radial;
+            if rng.gen() {
+                (p + self.height * self.axis, self.axis)
+            } else {
+                (p, -self.axis)
+            }
+        }
+    }
+}
+
+impl PartialEq for Cylinder {
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base
+            && self.axis == other.axis
+            && self.radius == other.radius
+            && self.height == other.height
+            && self.capped == other.capped
+            && self.material.name() == other.material.name()
+    }
+}
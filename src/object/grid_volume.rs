@@ -0,0 +1,254 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{object_id, Hittable, RayHit},
+    rng, Material, Point3, Ray, Vec3,
+};
+
+/// A 3D grid of density samples spanning an axis-aligned box. Densities between the samples are
+/// interpolated trilinearly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityGrid {
+    min: Point3,
+    max: Point3,
+    resolution: [usize; 3],
+    densities: Vec<f64>,
+    max_density: f64,
+}
+
+impl DensityGrid {
+    /// Creates a new grid spanning the box with opposite corners `min` and `max`. `densities`
+    /// holds `resolution[0] * resolution[1] * resolution[2]` samples with the x index varying
+    /// fastest and the z index slowest. Returns `None` if the number of samples is wrong, if any
+    /// dimension has fewer than two samples, or if any density is negative.
+    pub fn new(
+        min: Point3,
+        max: Point3,
+        resolution: [usize; 3],
+        densities: Vec<f64>,
+    ) -> Option<Self> {
+        if resolution.iter().any(|&n| n < 2)
+            || densities.len() != resolution.iter().product()
+            || densities
+                .iter()
+                .any(|&density| density.is_nan() || density < 0.)
+        {
+            return None;
+        }
+        let max_density = densities.iter().copied().fold(0., f64::max);
+        let (min, max) = (
+            Point3::new(
+                min.x().min(max.x()),
+                min.y().min(max.y()),
+                min.z().min(max.z()),
+            ),
+            Point3::new(
+                min.x().max(max.x()),
+                min.y().max(max.y()),
+                min.z().max(max.z()),
+            ),
+        );
+        Some(Self {
+            min,
+            max,
+            resolution,
+            densities,
+            max_density,
+        })
+    }
+
+    /// Creates a new grid by sampling `density` at each grid point. Negative densities are treated
+    /// as zero.
+    pub fn from_fn(
+        min: Point3,
+        max: Point3,
+        resolution: [usize; 3],
+        density: impl Fn(Point3) -> f64,
+    ) -> Option<Self> {
+        let [nx, ny, nz] = resolution;
+        if resolution.iter().any(|&n| n < 2) {
+            return None;
+        }
+        let size = max - min;
+        let densities = (0..nz)
+            .flat_map(|k| (0..ny).flat_map(move |j| (0..nx).map(move |i| (i, j, k))))
+            .map(|(i, j, k)| {
+                let p = min
+                    + Vec3::new(
+                        size.x() * i as f64 / (nx - 1) as f64,
+                        size.y() * j as f64 / (ny - 1) as f64,
+                        size.z() * k as f64 / (nz - 1) as f64,
+                    );
+                density(p).max(0.)
+            })
+            .collect();
+        Self::new(min, max, resolution, densities)
+    }
+
+    /// Gets the largest density anywhere in the grid.
+    pub fn max_density(&self) -> f64 {
+        self.max_density
+    }
+
+    /// Computes the density at `p`. Points outside of the grid have a density of zero.
+    pub fn density(&self, p: &Point3) -> f64 {
+        let mut indices = [0; 3];
+        let mut fractions = [0.; 3];
+        for axis in 0..3 {
+            let extent = self.max[axis] - self.min[axis];
+            let relative = if extent == 0. {
+                0.
+            } else {
+                (p[axis] - self.min[axis]) / extent
+            };
+            if !(0.0..=1.).contains(&relative) {
+                return 0.;
+            }
+            let scaled = relative * (self.resolution[axis] - 1) as f64;
+            let index = (scaled.floor() as usize).min(self.resolution[axis] - 2);
+            indices[axis] = index;
+            fractions[axis] = scaled - index as f64;
+        }
+        let sample = |di: usize, dj: usize, dk: usize| {
+            let [nx, ny, _] = self.resolution;
+            let [i, j, k] = indices;
+            self.densities[(i + di) + nx * ((j + dj) + ny * (k + dk))]
+        };
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let [fx, fy, fz] = fractions;
+        let c00 = lerp(sample(0, 0, 0), sample(1, 0, 0), fx);
+        let c10 = lerp(sample(0, 1, 0), sample(1, 1, 0), fx);
+        let c01 = lerp(sample(0, 0, 1), sample(1, 0, 1), fx);
+        let c11 = lerp(sample(0, 1, 1), sample(1, 1, 1), fx);
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+
+    /// Finds the times between which `ray` is inside the box spanned by the grid.
+    fn entry_exit(&self, ray: &Ray, valid_t: &RangeInclusive<f64>) -> Option<(f64, f64)> {
+        let mut start = *valid_t.start();
+        let mut end = *valid_t.end();
+        for axis in 0..3 {
+            let inverse_speed = ray.direction()[axis].recip();
+            let mut t0 = (self.min[axis] - ray.origin()[axis]) * inverse_speed;
+            let mut t1 = (self.max[axis] - ray.origin()[axis]) * inverse_speed;
+            if inverse_speed < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // `NaN`s from rays that travel within a face of the box are ignored by `max`/`min`.
+            start = start.max(t0);
+            end = end.min(t1);
+            if end < start {
+                return None;
+            }
+        }
+        Some((start, end))
+    }
+}
+
+/// A participating medium, such as smoke or cloud, whose density varies throughout space
+/// according to a [`DensityGrid`]. Rays are scattered at random points inside the medium chosen
+/// by delta tracking.
+#[derive(Clone)]
+pub struct GridVolume {
+    grid: DensityGrid,
+    phase_function: Arc<dyn Material>,
+}
+
+impl GridVolume {
+    /// Creates a new medium. `phase_function` determines how rays scatter once they interact
+    /// with the medium, for which [`Isotropic`] is the usual choice.
+    ///
+    /// [`Isotropic`]: crate::material::Isotropic
+    pub fn new<M>(grid: DensityGrid, phase_function: Arc<M>) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            grid,
+            phase_function,
+        }
+    }
+
+    /// Gets the density grid of the medium.
+    pub fn grid(&self) -> &DensityGrid {
+        &self.grid
+    }
+
+    /// Estimates the fraction of light that passes through the medium along `ray` between the
+    /// times `valid_t.start()` and `valid_t.end()` by ratio tracking.
+    pub fn transmittance(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> f64 {
+        let majorant = self.grid.max_density();
+        let speed = ray.direction().length();
+        let (mut t, end) = match self.grid.entry_exit(ray, &valid_t) {
+            Some(range) if majorant > 0. && speed > 0. => range,
+            _ => return 1.,
+        };
+        let mut transmittance = 1.;
+        loop {
+            t -= (1. - rng::random::<f64>()).ln() / (majorant * speed);
+            if t >= end {
+                break transmittance;
+            }
+            transmittance *= 1. - self.grid.density(&ray.at(t)) / majorant;
+        }
+    }
+}
+
+impl Debug for GridVolume {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GridVolume")
+            .field("min", &self.grid.min)
+            .field("max", &self.grid.max)
+            .field("resolution", &self.grid.resolution)
+            .field("phase_function", &self.phase_function.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for GridVolume {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let majorant = self.grid.max_density();
+        let speed = ray.direction().length();
+        if majorant <= 0. || speed == 0. {
+            return None;
+        }
+        let (mut t, end) = self.grid.entry_exit(ray, &valid_t)?;
+        // Delta tracking: sample collisions against a homogeneous medium with the maximum density
+        // and accept each as real with probability proportional to the local density.
+        loop {
+            t -= (1. - rng::random::<f64>()).ln() / (majorant * speed);
+            if t >= end {
+                return None;
+            }
+            let p = ray.at(t);
+            if rng::random::<f64>() * majorant < self.grid.density(&p) {
+                return Some(RayHit {
+                    p,
+                    // Media have no surface, so the normal is arbitrary.
+                    normal: Vec3::new(1., 0., 0.),
+                    tangent: Vec3::new(0., 1., 0.),
+                    t,
+                    material: Arc::clone(&self.phase_function),
+                    object: object_id(self),
+                    u: 0.,
+                    v: 0.,
+                    velocity: Vec3::default(),
+                    curvature: 0.,
+                });
+            }
+        }
+    }
+
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        Some(Aabb::new(self.grid.min, self.grid.max))
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.phase_function)]
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{Hittable, RayHit},
+    transform::Transform,
+    Material, Ray,
+};
+
+use super::Transformed;
+
+/// A placement of shared geometry in the scene. Many instances can refer to the same geometry
+/// without duplicating it, and each can optionally override the material of that geometry.
+#[derive(Clone)]
+pub struct Instance {
+    placed: Transformed,
+    material: Option<Arc<dyn Material>>,
+}
+
+impl Instance {
+    /// Places `geometry` in the scene by moving it with `transform`. The geometry keeps its own
+    /// materials.
+    pub fn new(geometry: Arc<dyn Hittable>, transform: Transform) -> Self {
+        Self {
+            placed: Transformed::new(geometry, transform),
+            material: None,
+        }
+    }
+
+    /// Places `geometry` in the scene by moving it with `transform` and replaces all of its
+    /// materials with `material`.
+    pub fn with_material<M>(
+        geometry: Arc<dyn Hittable>,
+        transform: Transform,
+        material: Arc<M>,
+    ) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            placed: Transformed::new(geometry, transform),
+            material: Some(material),
+        }
+    }
+
+    /// Places `geometry` in the scene so that it moves from `start_transform` at the moment
+    /// `start_time` to `end_transform` at the moment `end_time`. The geometry keeps its own
+    /// materials.
+    pub fn moving(
+        geometry: Arc<dyn Hittable>,
+        start_transform: Transform,
+        start_time: f64,
+        end_transform: Transform,
+        end_time: f64,
+    ) -> Self {
+        Self {
+            placed: Transformed::moving(
+                geometry,
+                start_transform,
+                start_time,
+                end_transform,
+                end_time,
+            ),
+            material: None,
+        }
+    }
+
+    /// Places `geometry` in the scene so that it moves from `start_transform` at the moment
+    /// `start_time` to `end_transform` at the moment `end_time` and replaces all of its materials
+    /// with `material`.
+    pub fn moving_with_material<M>(
+        geometry: Arc<dyn Hittable>,
+        start_transform: Transform,
+        start_time: f64,
+        end_transform: Transform,
+        end_time: f64,
+        material: Arc<M>,
+    ) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            material: Some(material),
+            ..Self::moving(
+                geometry,
+                start_transform,
+                start_time,
+                end_transform,
+                end_time,
+            )
+        }
+    }
+
+    /// Gets the transformation that places the geometry in the scene. If the instance is moving,
+    /// this is where it starts.
+    pub fn transform(&self) -> &Transform {
+        self.placed.transform()
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("transform", self.transform())
+            .field(
+                "material",
+                &self.material.as_ref().map(|material| material.name()),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Instance {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        self.placed.hit_by(ray, valid_t).map(|mut hit| {
+            if let Some(material) = &self.material {
+                hit.material = Arc::clone(material);
+            }
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.placed.bounding_box(time)
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        match &self.material {
+            Some(material) => vec![Arc::clone(material)],
+            None => self.placed.materials(),
+        }
+    }
+}
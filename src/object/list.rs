@@ -5,8 +5,9 @@ use std::{
 };
 
 use crate::{
+    bvh::Aabb,
     ray::{Hittable, RayHit},
-    Ray,
+    Material, Ray,
 };
 
 /// A list of multiple objects that could be hit by a ray.
@@ -21,6 +22,11 @@ impl List {
         self.objects.clear();
     }
 
+    /// Gets the objects in the list.
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
+    }
+
     /// Adds a new object to the back of the list.
     pub fn push(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object);
@@ -44,4 +50,19 @@ impl Hittable for List {
                 (Some(acc), object) => object.hit_by(ray, *valid_t.start()..=acc.t).or(Some(acc)),
             })
     }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.objects
+            .iter()
+            .map(|object| object.bounding_box(time.clone()))
+            .reduce(|a, b| Some(a?.union(&b?)))
+            .flatten()
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.objects
+            .iter()
+            .flat_map(|object| object.materials())
+            .collect()
+    }
 }
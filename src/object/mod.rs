@@ -1,5 +1,44 @@
+mod alpha_mask;
+pub use alpha_mask::AlphaMask;
+
+mod clipped;
+pub use clipped::Clipped;
+
+mod cylinder;
+pub use cylinder::Cylinder;
+
+mod grid_volume;
+pub use grid_volume::{DensityGrid, GridVolume};
+
+mod instance;
+pub use instance::Instance;
+
+mod moving_sphere;
+pub use moving_sphere::MovingSphere;
+
+mod quadric;
+pub use quadric::Quadric;
+
+mod rotate;
+pub use rotate::Rotate;
+
+mod sdf;
+pub use sdf::{DistanceFunction, Sdf};
+
+mod solid;
+pub use solid::Solid;
+
 mod sphere;
 pub use sphere::Sphere;
 
 mod list;
 pub use list::List;
+
+mod torus;
+pub use torus::Torus;
+
+mod transformed;
+pub use transformed::Transformed;
+
+mod translate;
+pub use translate::Translate;
@@ -0,0 +1,121 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{object_id, Hittable, RayHit},
+    Material, Point3, Ray,
+};
+
+use super::sphere::{hit_sphere, sphere_box};
+
+/// A sphere that moves in a straight line at a constant speed.
+#[derive(Clone)]
+pub struct MovingSphere {
+    start_center: Point3,
+    start_time: f64,
+    end_center: Point3,
+    end_time: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    /// Creates a new sphere with a radius of `radius` that is centered at `start_center` at the
+    /// moment `start_time` and at `end_center` at the moment `end_time`.
+    pub fn new<M>(
+        start_center: Point3,
+        start_time: f64,
+        end_center: Point3,
+        end_time: f64,
+        radius: f64,
+        material: Arc<M>,
+    ) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            start_center,
+            start_time,
+            end_center,
+            end_time,
+            radius,
+            material,
+        }
+    }
+
+    /// Gets the center point of the sphere at the moment `time`.
+    pub fn center(&self, time: f64) -> Point3 {
+        let duration = self.end_time - self.start_time;
+        if duration == 0. {
+            return self.start_center;
+        }
+        let progress = (time - self.start_time) / duration;
+        self.start_center + progress * (self.end_center - self.start_center)
+    }
+
+    /// Gets the radius of the sphere.
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+impl Debug for MovingSphere {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MovingSphere")
+            .field("start_center", &self.start_center)
+            .field("start_time", &self.start_time)
+            .field("end_center", &self.end_center)
+            .field("end_time", &self.end_time)
+            .field("radius", &self.radius)
+            .field("material", &self.material.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        hit_sphere(
+            self.center(ray.time()),
+            self.radius,
+            &self.material,
+            object_id(self),
+            ray,
+            valid_t,
+        )
+        .map(|mut hit| {
+            let duration = self.end_time - self.start_time;
+            if duration != 0. {
+                hit.velocity = (self.end_center - self.start_center) / duration;
+            }
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        // The sphere moves in a straight line, so it stays between where it starts and ends.
+        let (start, end) = time.into_inner();
+        Some(
+            sphere_box(self.center(start), self.radius)
+                .union(&sphere_box(self.center(end), self.radius)),
+        )
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.material)]
+    }
+}
+
+impl PartialEq for MovingSphere {
+    fn eq(&self, other: &Self) -> bool {
+        self.start_center == other.start_center
+            && self.start_time == other.start_time
+            && self.end_center == other.end_center
+            && self.end_time == other.end_time
+            && self.radius == other.radius
+            && self.material.name() == other.material.name()
+    }
+}
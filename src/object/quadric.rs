@@ -0,0 +1,163 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    poly,
+    ray::{object_id, Hittable, RayHit},
+    Material, Point3, Ray, Vec3,
+};
+
+/// A surface defined by the implicit equation
+/// `Ax^2 + By^2 + Cz^2 + Dxy + Exz + Fyz + Gx + Hy + Iz + J = 0`. This covers ellipsoids,
+/// paraboloids, hyperboloids, cones, and infinite cylinders among others.
+#[derive(Clone)]
+pub struct Quadric {
+    coefficients: [f64; 10],
+    material: Arc<dyn Material>,
+}
+
+impl Quadric {
+    /// Creates a new quadric surface. `coefficients` holds `[A, B, C, D, E, F, G, H, I, J]` from
+    /// the equation in the type-level documentation. The normal vector of the surface points
+    /// toward the side where the left-hand side of the equation is positive.
+    pub fn new<M>(coefficients: [f64; 10], material: Arc<M>) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            coefficients,
+            material,
+        }
+    }
+
+    /// Creates an axis-aligned ellipsoid centered at `center` whose semi-axes along the x-, y-,
+    /// and z-axes are given by the corresponding components of `radii`.
+    pub fn ellipsoid<M>(center: Point3, radii: Vec3, material: Arc<M>) -> Self
+    where
+        M: Material + 'static,
+    {
+        let [a, b, c] = [0, 1, 2].map(|i| radii[i].powi(2).recip());
+        let [g, h, i] = [(a, 0), (b, 1), (c, 2)].map(|(k, i)| -2. * k * center[i]);
+        let j = a * center.x().powi(2) + b * center.y().powi(2) + c * center.z().powi(2) - 1.;
+        Self::new([a, b, c, 0., 0., 0., g, h, i, j], material)
+    }
+
+    /// Creates an infinite plane through `point` whose normal vector is `normal`.
+    pub fn plane<M>(point: Point3, normal: Vec3, material: Arc<M>) -> Self
+    where
+        M: Material + 'static,
+    {
+        let normal = normal.normalized();
+        let j = -normal.dot(&point);
+        Self::new(
+            [
+                0.,
+                0.,
+                0.,
+                0.,
+                0.,
+                0.,
+                normal.x(),
+                normal.y(),
+                normal.z(),
+                j,
+            ],
+            material,
+        )
+    }
+
+    /// Gets the coefficients `[A, B, C, D, E, F, G, H, I, J]` of the implicit equation.
+    pub fn coefficients(&self) -> [f64; 10] {
+        self.coefficients
+    }
+
+    /// Evaluates the quadratic part of the equation as a symmetric bilinear form.
+    fn quadratic_form(&self, u: &Vec3, v: &Vec3) -> f64 {
+        let [a, b, c, d, e, f, ..] = self.coefficients;
+        a * u.x() * v.x()
+            + b * u.y() * v.y()
+            + c * u.z() * v.z()
+            + 0.5 * d * (u.x() * v.y() + u.y() * v.x())
+            + 0.5 * e * (u.x() * v.z() + u.z() * v.x())
+            + 0.5 * f * (u.y() * v.z() + u.z() * v.y())
+    }
+
+    /// Gets the linear part of the equation as a vector.
+    fn linear_part(&self) -> Vec3 {
+        let [.., g, h, i, _] = self.coefficients;
+        Vec3::new(g, h, i)
+    }
+
+    /// Computes the gradient of the left-hand side of the equation at `p`.
+    fn gradient(&self, p: Point3) -> Vec3 {
+        let [a, b, c, d, e, f, g, h, i, _] = self.coefficients;
+        Vec3::new(
+            2. * a * p.x() + d * p.y() + e * p.z() + g,
+            2. * b * p.y() + d * p.x() + f * p.z() + h,
+            2. * c * p.z() + e * p.x() + f * p.y() + i,
+        )
+    }
+
+    /// Computes the normal vector at `p` assuming that `p` is on the surface.
+    fn normal(&self, p: Point3) -> Vec3 {
+        self.gradient(p).normalized()
+    }
+
+    /// Computes the mean curvature at `p` assuming that `p` is on the surface.
+    fn curvature(&self, p: Point3) -> f64 {
+        let [a, b, c, ..] = self.coefficients;
+        let gradient = self.gradient(p);
+        let length_squared = gradient.length_squared();
+        // The Hessian of the equation is twice the matrix of the quadratic form.
+        (length_squared * (a + b + c) - self.quadratic_form(&gradient, &gradient))
+            / length_squared.powf(1.5)
+    }
+}
+
+impl Debug for Quadric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Quadric")
+            .field("coefficients", &self.coefficients)
+            .field("material", &self.material.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Quadric {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let o = ray.origin();
+        let d = ray.direction();
+        let linear = self.linear_part();
+        let a = self.quadratic_form(d, d);
+        let b = 2. * self.quadratic_form(o, d) + linear.dot(d);
+        let c = self.quadratic_form(o, o) + linear.dot(o) + self.coefficients[9];
+        let t = poly::real_roots(&[c, b, a], valid_t).into_iter().next()?;
+        let p = ray.at(t);
+        let normal = self.normal(p);
+        Some(RayHit {
+            p,
+            normal,
+            tangent: normal.orthonormal_basis().0,
+            t,
+            material: Arc::clone(&self.material),
+            object: object_id(self),
+            u: 0.,
+            v: 0.,
+            velocity: Vec3::default(),
+            curvature: self.curvature(p),
+        })
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.material)]
+    }
+}
+
+impl PartialEq for Quadric {
+    fn eq(&self, other: &Self) -> bool {
+        self.coefficients == other.coefficients && self.material.name() == other.material.name()
+    }
+}
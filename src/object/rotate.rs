@@ -0,0 +1,94 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    angle::Angle,
+    bvh::Aabb,
+    ray::{placed_id, Hittable, RayHit},
+    Material, Ray, Vec3,
+};
+
+/// An object that has been rotated about an axis through the origin.
+#[derive(Clone)]
+pub struct Rotate {
+    object: Arc<dyn Hittable>,
+    axis: Vec3,
+    angle: Angle,
+    sin: f64,
+    cos: f64,
+}
+
+impl Rotate {
+    /// Wraps `object` so that it appears to be rotated counterclockwise by `angle` about `axis` as
+    /// seen when `axis` points toward the viewer.
+    pub fn new(object: Arc<dyn Hittable>, axis: Vec3, angle: Angle) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            object,
+            axis: axis.normalized(),
+            angle,
+            sin,
+            cos,
+        }
+    }
+
+    /// Wraps `object` so that it appears to be rotated by `angle` about the y-axis.
+    pub fn about_y(object: Arc<dyn Hittable>, angle: Angle) -> Self {
+        Self::new(object, Vec3::new(0., 1., 0.), angle)
+    }
+
+    /// Gets the unit vector that the wrapped object is rotated about.
+    pub fn axis(&self) -> Vec3 {
+        self.axis
+    }
+
+    /// Gets the angle that the wrapped object is rotated by.
+    pub fn angle(&self) -> Angle {
+        self.angle
+    }
+
+    /// Rotates `v` about the axis by the angle whose sine is `sin` using Rodrigues' formula.
+    fn rotate(&self, v: &Vec3, sin: f64) -> Vec3 {
+        self.cos * v + sin * self.axis.cross(v) + (1. - self.cos) * self.axis.dot(v) * self.axis
+    }
+}
+
+impl Debug for Rotate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rotate")
+            .field("axis", &self.axis)
+            .field("angle", &self.angle)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Rotate {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        // Rotate the ray into the object's frame, then rotate the hit back out of it.
+        let rotated = Ray::with_time(
+            self.rotate(ray.origin(), -self.sin),
+            self.rotate(ray.direction(), -self.sin),
+            ray.time(),
+        );
+        self.object.hit_by(&rotated, valid_t).map(|mut hit| {
+            hit.p = self.rotate(&hit.p, self.sin);
+            hit.normal = self.rotate(&hit.normal, self.sin);
+            hit.tangent = self.rotate(&hit.tangent, self.sin);
+            hit.velocity = self.rotate(&hit.velocity, self.sin);
+            hit.object = placed_id(hit.object, self);
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        let inner = self.object.bounding_box(time)?;
+        Aabb::around_points(inner.corners().map(|corner| self.rotate(&corner, self.sin)))
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.object.materials()
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    ray::{object_id, Hittable, RayHit},
+    Material, Point3, Ray, Vec3,
+};
+
+/// A signed distance function. The value at a point is the distance from that point to the
+/// nearest point of the surface, negated if the point is inside the surface. Functions that only
+/// ever underestimate the magnitude of that distance are also acceptable at the cost of slower
+/// rendering.
+pub trait DistanceFunction: Send + Sync {
+    /// Computes the signed distance from `p` to the surface.
+    fn distance(&self, p: &Point3) -> f64;
+}
+
+impl<F> DistanceFunction for F
+where
+    F: Fn(&Point3) -> f64 + Send + Sync,
+{
+    fn distance(&self, p: &Point3) -> f64 {
+        self(p)
+    }
+}
+
+/// A surface defined implicitly by a signed distance function and rendered by sphere tracing.
+#[derive(Clone)]
+pub struct Sdf {
+    distance: Arc<dyn DistanceFunction>,
+    material: Arc<dyn Material>,
+}
+
+impl Sdf {
+    /// The distance from the surface at which a marching ray is considered to have hit it. This is
+    /// also the step size used to estimate normal vectors.
+    const EPSILON: f64 = 1e-4;
+    /// The maximum number of steps that a ray may take before it is considered to have missed.
+    const MAX_STEPS: usize = 512;
+    /// The farthest distance from its origin that a ray will be marched.
+    const MAX_DISTANCE: f64 = 1e4;
+
+    /// Creates a new surface from its signed distance function.
+    pub fn new<D, M>(distance: D, material: Arc<M>) -> Self
+    where
+        D: DistanceFunction + 'static,
+        M: Material + 'static,
+    {
+        Self {
+            distance: Arc::new(distance),
+            material,
+        }
+    }
+
+    /// Computes the signed distance from `p` to the surface.
+    pub fn distance(&self, p: &Point3) -> f64 {
+        self.distance.distance(p)
+    }
+
+    /// Estimates the normal vector at `p` from the gradient of the distance function.
+    fn normal(&self, p: Point3) -> Vec3 {
+        let offset = |i: usize| {
+            let mut h = Vec3::default();
+            h[i] = Self::EPSILON;
+            self.distance(&(p + h)) - self.distance(&(p - h))
+        };
+        Vec3::new(offset(0), offset(1), offset(2)).normalized()
+    }
+
+    /// Estimates the mean curvature at `p`, which is half of the Laplacian of the distance
+    /// function when it measures true distances.
+    fn curvature(&self, p: Point3) -> f64 {
+        const STEP: f64 = 1e-3;
+        let center = self.distance(&p);
+        let laplacian = (0..3)
+            .map(|i| {
+                let mut h = Vec3::default();
+                h[i] = STEP;
+                self.distance(&(p + h)) + self.distance(&(p - h)) - 2. * center
+            })
+            .sum::<f64>()
+            / (STEP * STEP);
+        laplacian / 2.
+    }
+}
+
+impl Debug for Sdf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sdf")
+            .field("material", &self.material.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let speed = ray.direction().length();
+        if speed == 0. {
+            return None;
+        }
+        let end = valid_t.end().min(Self::MAX_DISTANCE / speed);
+        let mut t = *valid_t.start();
+        // A ray that starts within the distance at which hits are accepted, such as one scattered
+        // off of the surface, would hit the point that it starts at. It must leave that shell
+        // first, in steps that double so that rays that graze the surface leave it quickly.
+        let mut step = Self::EPSILON / speed;
+        while self.distance(&ray.at(t)).abs() < Self::EPSILON {
+            if t > end {
+                return None;
+            }
+            t += step;
+            step *= 2.;
+        }
+        // Rays that start inside the surface march toward its boundary from the inside.
+        let side = self.distance(&ray.at(t)).signum();
+        for _ in 0..Self::MAX_STEPS {
+            if t > end {
+                return None;
+            }
+            let p = ray.at(t);
+            let distance = side * self.distance(&p);
+            if distance < Self::EPSILON {
+                let normal = self.normal(p);
+                return Some(RayHit {
+                    p,
+                    normal,
+                    tangent: normal.orthonormal_basis().0,
+                    t,
+                    material: Arc::clone(&self.material),
+                    object: object_id(self),
+                    u: 0.,
+                    v: 0.,
+                    velocity: Vec3::default(),
+                    curvature: self.curvature(p),
+                });
+            }
+            t += distance / speed;
+        }
+        None
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.material)]
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{object_id, Hittable, RayHit},
+    Material, Ray,
+};
+
+/// A single transparent object bounded by several surfaces, such as a hollow glass sphere made of
+/// an outer sphere and an inner sphere with a negative radius. Rays keep track of which
+/// transparent objects they are inside of by the object that they hit, so without this the two
+/// spheres of a hollow sphere would be two overlapping objects and light wouldn't be bent where
+/// it passes into the hollow.
+#[derive(Clone)]
+pub struct Solid {
+    surfaces: Arc<dyn Hittable>,
+}
+
+impl Solid {
+    /// Treats every surface of `surfaces` as the boundary of the same object.
+    pub fn new(surfaces: Arc<dyn Hittable>) -> Self {
+        Self { surfaces }
+    }
+}
+
+impl Debug for Solid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Solid").finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Solid {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        self.surfaces.hit_by(ray, valid_t).map(|mut hit| {
+            hit.object = object_id(self);
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.surfaces.bounding_box(time)
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.surfaces.materials()
+    }
+}
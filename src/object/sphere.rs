@@ -1,12 +1,17 @@
 use std::{
+    f64::consts::PI,
     fmt::{self, Debug, Formatter},
     ops::RangeInclusive,
     sync::Arc,
 };
 
+use rand::{Rng, RngCore};
+
 use crate::{
-    ray::{Hittable, RayHit},
-    Material, Point3, Ray, Vec3,
+    angle::Angle,
+    bvh::Aabb,
+    ray::{object_id, Hittable, RayHit, Surface},
+    rng, Material, Point3, Ray, Vec3,
 };
 
 /// A sphere.
@@ -40,9 +45,47 @@ impl Sphere {
         self.radius
     }
 
-    /// Computes the normal vector at `p` assuming that `p` is on the surface of the sphere.
-    fn normal(&self, p: Point3) -> Vec3 {
-        (p - self.center()) / self.radius()
+    /// Computes the cosine of the half-angle of the cone of directions from `from` that hit the
+    /// sphere, or `None` if `from` is inside the sphere.
+    fn visible_cos_theta_max(&self, from: Point3) -> Option<f64> {
+        let distance_squared = (self.center - from).length_squared();
+        let radius_squared = self.radius.powi(2);
+        if distance_squared <= radius_squared {
+            None
+        } else {
+            Some((1. - radius_squared / distance_squared).sqrt())
+        }
+    }
+
+    /// Picks a direction from `from` toward the sphere uniformly from the cone of directions that
+    /// hit it. Sampling only the visible cone converges much faster for small, bright spheres than
+    /// sampling the whole surface does. Returns `None` if `from` is inside the sphere.
+    pub fn sample_solid_angle(&self, from: Point3) -> Option<Vec3> {
+        let cos_theta_max = self.visible_cos_theta_max(from)?;
+        let axis = (self.center - from).normalized();
+        let (u, v) = axis.orthonormal_basis();
+        let cos_theta = 1. + rng::random::<f64>() * (cos_theta_max - 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let (sin_phi, cos_phi) = Angle::Radians(2. * PI * rng::random::<f64>()).sin_cos();
+        Some(sin_theta * cos_phi * u + sin_theta * sin_phi * v + cos_theta * axis)
+    }
+
+    /// The probability density, with respect to solid angle, that [`sample_solid_angle()`]
+    /// produces `direction` when called with `from`.
+    ///
+    /// [`sample_solid_angle()`]: Self::sample_solid_angle()
+    pub fn solid_angle_pdf(&self, from: Point3, direction: &Vec3) -> f64 {
+        match self.visible_cos_theta_max(from) {
+            Some(cos_theta_max)
+                if direction
+                    .normalized()
+                    .dot(&(self.center - from).normalized())
+                    >= cos_theta_max =>
+            {
+                1. / (2. * PI * (1. - cos_theta_max))
+            }
+            _ => 0.,
+        }
     }
 }
 
@@ -58,40 +101,110 @@ impl Debug for Sphere {
 
 impl Hittable for Sphere {
     fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
-        let co = *ray.origin() - self.center();
-        let a = ray.direction().length_squared();
-        let half_b = co.dot(ray.direction());
-        let c = co.length_squared() - self.radius().powi(2);
-        let quarter_discriminant = half_b * half_b - a * c;
-        if quarter_discriminant < 0. {
-            None
+        hit_sphere(
+            self.center,
+            self.radius,
+            &self.material,
+            object_id(self),
+            ray,
+            valid_t,
+        )
+    }
+
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        Some(sphere_box(self.center, self.radius))
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.material)]
+    }
+}
+
+impl Surface for Sphere {
+    fn area(&self) -> f64 {
+        4. * PI * self.radius.powi(2)
+    }
+
+    fn sample_surface(&self, rng: &mut dyn RngCore) -> (Point3, Vec3) {
+        let z = 1. - 2. * rng.gen::<f64>();
+        let r = (1. - z * z).sqrt();
+        let (sin_phi, cos_phi) = Angle::Radians(2. * PI * rng.gen::<f64>()).sin_cos();
+        let outward = Vec3::new(r * cos_phi, r * sin_phi, z);
+        (
+            self.center + self.radius.abs() * outward,
+            self.radius.signum() * outward,
+        )
+    }
+}
+
+/// Checks whether `ray` hits the sphere centered at `center` with a radius of `radius` within
+/// `valid_t`. The hit is identified as being on `object`.
+pub(super) fn hit_sphere(
+    center: Point3,
+    radius: f64,
+    material: &Arc<dyn Material>,
+    object: usize,
+    ray: &Ray,
+    valid_t: RangeInclusive<f64>,
+) -> Option<RayHit> {
+    let co = *ray.origin() - center;
+    let a = ray.direction().length_squared();
+    let half_b = co.dot(ray.direction());
+    let c = co.length_squared() - radius.powi(2);
+    let quarter_discriminant = half_b * half_b - a * c;
+    if quarter_discriminant < 0. {
+        None
+    } else {
+        let half_sdiscriminant = quarter_discriminant.sqrt();
+        let t0 = (-half_b - half_sdiscriminant) / a;
+        let t1 = t0 + 2. * half_sdiscriminant / a;
+        let t = if valid_t.contains(&t0) {
+            t0
+        } else if valid_t.contains(&t1) {
+            t1
         } else {
-            let half_sdiscriminant = quarter_discriminant.sqrt();
-            let t0 = (-half_b - half_sdiscriminant) / a;
-            let t1 = t0 + 2. * half_sdiscriminant / a;
-            if valid_t.contains(&t0) {
-                let p = ray.at(t0);
-                Some(RayHit {
-                    p,
-                    normal: self.normal(p),
-                    t: t0,
-                    material: Arc::clone(&self.material),
-                })
-            } else if valid_t.contains(&t1) {
-                let p = ray.at(t1);
-                Some(RayHit {
-                    p,
-                    normal: self.normal(p),
-                    t: t1,
-                    material: Arc::clone(&self.material),
-                })
+            return None;
+        };
+        let p = ray.at(t);
+        let outward = (p - center) / radius.abs();
+        let (u, v) = sphere_uv(&outward);
+        // The direction in which `u` increases, which is undefined at the poles.
+        let tangent = Vec3::new(outward.z(), 0., -outward.x());
+        Some(RayHit {
+            p,
+            normal: (p - center) / radius,
+            tangent: if tangent.near_zero() {
+                outward.orthonormal_basis().0
             } else {
-                None
-            }
-        }
+                tangent.normalized()
+            },
+            t,
+            material: Arc::clone(material),
+            object,
+            u,
+            v,
+            velocity: Vec3::default(),
+            curvature: radius.recip(),
+        })
     }
 }
 
+/// Computes the bounding box of the sphere centered at `center` with a radius of `radius`.
+pub(super) fn sphere_box(center: Point3, radius: f64) -> Aabb {
+    let radius = radius.abs();
+    let offset = Vec3::new(radius, radius, radius);
+    Aabb::new(center - offset, center + offset)
+}
+
+/// Computes the latitude and longitude of the point `p` on the unit sphere as surface coordinates
+/// in `[0, 1]`. `u` increases around the y-axis starting from `-x` and `v` increases from the
+/// bottom of the sphere to the top.
+fn sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1., 1.).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+    (phi / (2. * PI), theta / PI)
+}
+
 impl PartialEq for Sphere {
     fn eq(&self, other: &Self) -> bool {
         self.center == other.center
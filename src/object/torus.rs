@@ -0,0 +1,193 @@
+use std::{
+    f64::consts::PI,
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    angle::Angle,
+    bvh::Aabb,
+    poly,
+    ray::{object_id, Hittable, RayHit, Surface},
+    Material, Point3, Ray, Vec3,
+};
+
+/// A ring torus: the surface swept out by a circle revolving around an axis in its plane.
+#[derive(Clone)]
+pub struct Torus {
+    center: Point3,
+    axis: Vec3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    /// Creates a new torus centered at `center` that is rotationally symmetric about `axis`.
+    /// `major_radius` is the distance from `center` to the center of the tube and `minor_radius`
+    /// is the radius of the tube.
+    pub fn new<M>(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<M>,
+    ) -> Self
+    where
+        M: Material + 'static,
+    {
+        Self {
+            center,
+            axis: axis.normalized(),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    /// Gets the center point of the torus.
+    pub fn center(&self) -> Point3 {
+        self.center
+    }
+
+    /// Gets the unit vector that the torus is rotationally symmetric about.
+    pub fn axis(&self) -> Vec3 {
+        self.axis
+    }
+
+    /// Gets the distance from the center of the torus to the center of its tube.
+    pub fn major_radius(&self) -> f64 {
+        self.major_radius
+    }
+
+    /// Gets the radius of the tube of the torus.
+    pub fn minor_radius(&self) -> f64 {
+        self.minor_radius
+    }
+
+    /// Computes the normal vector at `p` assuming that `p` is on the surface of the torus.
+    fn normal(&self, p: Point3) -> Vec3 {
+        let cp = p - self.center;
+        let in_plane = cp - cp.dot(&self.axis) * self.axis;
+        let tube_center = self.major_radius * in_plane.normalized();
+        (cp - tube_center) / self.minor_radius
+    }
+
+    /// Computes the mean curvature at `p` given the normal vector there.
+    fn curvature(&self, normal: Vec3, p: Point3) -> f64 {
+        let cp = p - self.center;
+        let radial = (cp - cp.dot(&self.axis) * self.axis).normalized();
+        let cos_theta = normal.dot(&radial);
+        // One principal curvature is around the tube and the other is around the axis.
+        0.5 * (self.minor_radius.recip()
+            + cos_theta / (self.major_radius + self.minor_radius * cos_theta))
+    }
+}
+
+impl Debug for Torus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Torus")
+            .field("center", &self.center)
+            .field("axis", &self.axis)
+            .field("major_radius", &self.major_radius)
+            .field("minor_radius", &self.minor_radius)
+            .field("material", &self.material.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Torus {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        // A point `p` relative to the center is on the torus iff
+        // `(|p|^2 + R^2 - r^2)^2 = 4R^2 (|p|^2 - (p.a)^2)`.
+        let co = *ray.origin() - self.center;
+        let d = *ray.direction();
+        let r2 = self.major_radius.powi(2);
+        let alpha = d.length_squared();
+        let beta = 2. * co.dot(&d);
+        let gamma = co.length_squared() + r2 - self.minor_radius.powi(2);
+        let da = d.dot(&self.axis);
+        let oa = co.dot(&self.axis);
+        let coefficients = [
+            gamma * gamma - 4. * r2 * (co.length_squared() - oa * oa),
+            2. * beta * gamma - 8. * r2 * (co.dot(&d) - oa * da),
+            beta * beta + 2. * alpha * gamma - 4. * r2 * (alpha - da * da),
+            2. * alpha * beta,
+            alpha * alpha,
+        ];
+        let t = poly::real_roots(&coefficients, valid_t)
+            .into_iter()
+            .next()?;
+        let p = ray.at(t);
+        let normal = self.normal(p);
+        Some(RayHit {
+            p,
+            normal,
+            tangent: normal.orthonormal_basis().0,
+            t,
+            material: Arc::clone(&self.material),
+            object: object_id(self),
+            u: 0.,
+            v: 0.,
+            velocity: Vec3::default(),
+            curvature: self.curvature(normal, p),
+        })
+    }
+
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        // How far the tube reaches from the center along each coordinate axis.
+        let reach = |component: f64| {
+            self.major_radius.abs() * (1. - component * component).max(0.).sqrt()
+                + self.minor_radius.abs()
+        };
+        let reach = Vec3::new(
+            reach(self.axis.x()),
+            reach(self.axis.y()),
+            reach(self.axis.z()),
+        );
+        Some(Aabb::new(self.center - reach, self.center + reach))
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![Arc::clone(&self.material)]
+    }
+}
+
+impl Surface for Torus {
+    fn area(&self) -> f64 {
+        4. * PI * PI * self.major_radius * self.minor_radius
+    }
+
+    fn sample_surface(&self, rng: &mut dyn RngCore) -> (Point3, Vec3) {
+        let (u, v) = self.axis.orthonormal_basis();
+        // The outside of the tube has more area than the inside, so reject angles around the tube
+        // in proportion to how far they are from the axis.
+        let (sin_theta, cos_theta) = loop {
+            let (sin_theta, cos_theta) = Angle::Radians(2. * PI * rng.gen::<f64>()).sin_cos();
+            let distance = self.major_radius + self.minor_radius * cos_theta;
+            if rng.gen::<f64>() * (self.major_radius + self.minor_radius) <= distance {
+                break (sin_theta, cos_theta);
+            }
+        };
+        let (sin_phi, cos_phi) = Angle::Radians(2. * PI * rng.gen::<f64>()).sin_cos();
+        let radial = cos_phi * u + sin_phi * v;
+        let normal = cos_theta * radial + sin_theta * self.axis;
+        (
+            self.center + self.major_radius * radial + self.minor_radius * normal,
+            normal,
+        )
+    }
+}
+
+impl PartialEq for Torus {
+    fn eq(&self, other: &Self) -> bool {
+        self.center == other.center
+            && self.axis == other.axis
+            && self.major_radius == other.major_radius
+            && self.minor_radius == other.minor_radius
+            && self.material.name() == other.material.name()
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{placed_id, Hittable, RayHit},
+    transform::Transform,
+    Material, Point3, Ray, Vec3,
+};
+
+/// An object that has been moved by an arbitrary affine [`Transform`].
+#[derive(Clone)]
+pub struct Transformed {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    motion: Option<Motion>,
+}
+
+/// The end of the motion of a [`Transformed`] object over the exposure.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Motion {
+    start_time: f64,
+    end_transform: Transform,
+    end_time: f64,
+}
+
+impl Transformed {
+    /// Wraps `object` so that it appears to have been moved by `transform`.
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        Self {
+            object,
+            transform,
+            motion: None,
+        }
+    }
+
+    /// Wraps `object` so that it appears to have been moved by `start_transform` at the moment
+    /// `start_time` and by `end_transform` at the moment `end_time`. In between, the object moves
+    /// as described by [`Transform::interpolate()`].
+    pub fn moving(
+        object: Arc<dyn Hittable>,
+        start_transform: Transform,
+        start_time: f64,
+        end_transform: Transform,
+        end_time: f64,
+    ) -> Self {
+        Self {
+            object,
+            transform: start_transform,
+            motion: Some(Motion {
+                start_time,
+                end_transform,
+                end_time,
+            }),
+        }
+    }
+
+    /// Gets the transformation applied to the wrapped object. If the object is moving, this is
+    /// where it starts.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Gets the transformation applied to the wrapped object at the moment `time`. Returns `None`
+    /// if the object is squashed flat at that moment.
+    pub fn transform_at(&self, time: f64) -> Option<Transform> {
+        match self.motion {
+            None => Some(self.transform),
+            Some(Motion {
+                start_time,
+                end_transform,
+                end_time,
+            }) => {
+                let t = if end_time > start_time {
+                    (time - start_time) / (end_time - start_time)
+                } else {
+                    0.
+                };
+                self.transform.interpolate(&end_transform, t)
+            }
+        }
+    }
+
+    /// Estimates the velocity at the moment `time` of the point that starts at `local` in the
+    /// frame of the wrapped object.
+    fn velocity_of(&self, local: &Point3, time: f64) -> Vec3 {
+        let Some(Motion {
+            start_time,
+            end_time,
+            ..
+        }) = self.motion
+        else {
+            return Vec3::default();
+        };
+        let step = 1e-3 * (end_time - start_time);
+        let before = (time - step).max(start_time);
+        let after = (time + step).min(end_time);
+        if after <= before {
+            return Vec3::default();
+        }
+        match (self.transform_at(before), self.transform_at(after)) {
+            (Some(start), Some(end)) => {
+                (end.transform_point(local) - start.transform_point(local)) / (after - before)
+            }
+            _ => Vec3::default(),
+        }
+    }
+}
+
+impl Debug for Transformed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transformed")
+            .field("transform", &self.transform)
+            .field("motion", &self.motion)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        // The direction is not renormalized, so `t` means the same thing in both frames.
+        let transform = self.transform_at(ray.time())?;
+        let inverse = transform.inverse();
+        let local = Ray::with_time(
+            inverse.transform_point(ray.origin()),
+            inverse.transform_vector(ray.direction()),
+            ray.time(),
+        );
+        self.object.hit_by(&local, valid_t).map(|mut hit| {
+            let local = hit.p;
+            hit.p = transform.transform_point(&local);
+            // Approximate the change in curvature by how much the surface is stretched.
+            let bitangent = hit.normal.normalized().cross(&hit.tangent);
+            let stretch = (transform.transform_vector(&hit.tangent).length()
+                * transform.transform_vector(&bitangent).length())
+            .sqrt();
+            hit.curvature /= stretch;
+            hit.normal = transform.transform_normal(&hit.normal).normalized();
+            hit.tangent = transform.transform_vector(&hit.tangent).normalized();
+            hit.velocity =
+                transform.transform_vector(&hit.velocity) + self.velocity_of(&local, ray.time());
+            hit.object = placed_id(hit.object, self);
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        // Samples of the motion of a moving object. Its corners are assumed to travel no farther
+        // between samples than the distance between where they are at consecutive samples.
+        const SAMPLES: usize = 16;
+
+        let inner = self.object.bounding_box(time.clone())?;
+        let corners_at = |time: f64| {
+            self.transform_at(time).map(|transform| {
+                inner
+                    .corners()
+                    .map(|corner| transform.transform_point(&corner))
+            })
+        };
+        let (start, end) = time.into_inner();
+        if self.motion.is_none() {
+            return Aabb::around_points(corners_at(start)?);
+        }
+        let samples: Vec<[Point3; 8]> = (0..=SAMPLES)
+            .filter_map(|i| corners_at(start + (end - start) * i as f64 / SAMPLES as f64))
+            .collect();
+        let step = samples
+            .windows(2)
+            .flat_map(|pair| {
+                pair[0]
+                    .iter()
+                    .zip(&pair[1])
+                    .map(|(a, b)| (*b - *a).length())
+            })
+            .fold(0., f64::max);
+        Aabb::around_points(samples.into_iter().flatten()).map(|bounds| bounds.padded(step))
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.object.materials()
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    bvh::Aabb,
+    ray::{placed_id, Hittable, RayHit},
+    Material, Ray, Vec3,
+};
+
+/// An object that has been moved from its original position.
+#[derive(Clone)]
+pub struct Translate {
+    object: Arc<dyn Hittable>,
+    offset: Vec3,
+}
+
+impl Translate {
+    /// Wraps `object` so that it appears to be moved by `offset`.
+    pub fn new(object: Arc<dyn Hittable>, offset: Vec3) -> Self {
+        Self { object, offset }
+    }
+
+    /// Gets the displacement applied to the wrapped object.
+    pub fn offset(&self) -> Vec3 {
+        self.offset
+    }
+}
+
+impl Debug for Translate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Translate")
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for Translate {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let moved = Ray::with_time(*ray.origin() - self.offset, *ray.direction(), ray.time());
+        self.object.hit_by(&moved, valid_t).map(|mut hit| {
+            hit.p += self.offset;
+            hit.object = placed_id(hit.object, self);
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        let inner = self.object.bounding_box(time)?;
+        Some(Aabb::new(
+            inner.min() + self.offset,
+            inner.max() + self.offset,
+        ))
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.object.materials()
+    }
+}
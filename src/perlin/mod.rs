@@ -0,0 +1,95 @@
+use std::fmt::{self, Debug, Formatter};
+
+use rand::seq::SliceRandom;
+
+use crate::{rng, Point3, Vec3};
+
+/// The number of gradients in a [`Perlin`] generator. Must be a power of two.
+const POINT_COUNT: usize = 256;
+
+/// A generator of smoothly-varying pseudorandom noise.
+#[derive(Clone)]
+pub struct Perlin {
+    gradients: Box<[Vec3; POINT_COUNT]>,
+    permutations: [Box<[usize; POINT_COUNT]>; 3],
+}
+
+impl Perlin {
+    /// Creates a new noise generator with random gradients.
+    pub fn new() -> Self {
+        let gradients = Box::new([(); POINT_COUNT].map(|_| Vec3::random_unit_vector()));
+        let permutations = [(); 3].map(|_| {
+            let mut permutation = Box::new([0; POINT_COUNT]);
+            for (i, entry) in permutation.iter_mut().enumerate() {
+                *entry = i;
+            }
+            rng::with_rng(|rng| permutation.shuffle(rng));
+            permutation
+        });
+        Self {
+            gradients,
+            permutations,
+        }
+    }
+
+    /// Samples the noise at `p`. The result is roughly in `[-1, 1]` and is `0` at every point with
+    /// integer coordinates.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let floors = [p.x().floor(), p.y().floor(), p.z().floor()];
+        let fractions = [p.x() - floors[0], p.y() - floors[1], p.z() - floors[2]];
+        let cells = floors.map(|floor| floor as i64);
+        // Hermite smoothing hides the grid that the gradients lie on.
+        let [u, v, w] = fractions.map(|t| t * t * (3. - 2. * t));
+        let mut sum = 0.;
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let corner = [i, j, k];
+                    let hash = (0..3)
+                        .map(|axis| {
+                            let index = (cells[axis] + corner[axis]) as usize & (POINT_COUNT - 1);
+                            self.permutations[axis][index]
+                        })
+                        .fold(0, |acc, entry| acc ^ entry);
+                    let offset = Vec3::new(
+                        fractions[0] - i as f64,
+                        fractions[1] - j as f64,
+                        fractions[2] - k as f64,
+                    );
+                    let weight = [(i, u), (j, v), (k, w)]
+                        .iter()
+                        .map(|&(c, t)| if c == 1 { t } else { 1. - t })
+                        .product::<f64>();
+                    sum += weight * self.gradients[hash].dot(&offset);
+                }
+            }
+        }
+        sum
+    }
+
+    /// Sums `depth` octaves of noise at `p`, each with twice the frequency and half the amplitude
+    /// of the last, and takes the absolute value. The result is roughly in `[0, 2)`.
+    pub fn turbulence(&self, p: &Point3, depth: usize) -> f64 {
+        let mut sum = 0.;
+        let mut p = *p;
+        let mut weight = 1.;
+        for _ in 0..depth {
+            sum += weight * self.noise(&p);
+            weight *= 0.5;
+            p *= 2.;
+        }
+        sum.abs()
+    }
+}
+
+impl Debug for Perlin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Perlin").finish_non_exhaustive()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,256 @@
+use std::{f64::consts::PI, sync::OnceLock};
+
+use rayon::prelude::*;
+
+use crate::{
+    background::Background, material::ScatterRecord, ray::Hittable, rng::random, Color, Point3,
+    Ray, Vec3,
+};
+
+/// A map of the caustics that the background casts through mirrors and glass onto diffuse
+/// surfaces, as described by Jensen (1996). Photons carrying the light of the background are
+/// traced into the scene and stored where they land on a diffuse surface after being reflected
+/// or refracted specularly at least once, and the light arriving at a point is estimated from
+/// the density of the photons near it. Path tracing almost never finds these paths, since it
+/// would have to scatter from the diffuse surface through the glass into the small part of the
+/// background that is focused onto the point.
+///
+/// Photons are only aimed at a sphere around the objects that should cast caustics, so that they
+/// aren't wasted on the rest of the scene, and only the caustics whose specular bounces all lie
+/// within that sphere are stored. Caustics cast by mirrors and glass elsewhere are left to the
+/// path tracer, which finds them as it would without a map.
+///
+/// Only the background emits photons. Glowing objects such as
+/// [`DiffuseLight`](crate::material::DiffuseLight) aren't traced, so the caustics that they cast
+/// are also left to the path tracer.
+#[derive(Debug)]
+pub struct PhotonMap {
+    center: Point3,
+    radius: f64,
+    photons: usize,
+    neighbors: usize,
+    max_radius: f64,
+    /// The photons that have been stored, sorted into a balanced kd-tree in which the middle
+    /// photon of each range splits the rest of the range in two.
+    tree: OnceLock<Vec<Photon>>,
+}
+
+/// A bundle of light that was stored where it landed on a diffuse surface.
+#[derive(Clone, Copy, Debug)]
+struct Photon {
+    p: Point3,
+    /// The unit vector in the direction that the photon was traveling.
+    direction: Vec3,
+    power: Color,
+    /// The axis along which the photon splits the range of the kd-tree that it is the middle of.
+    axis: usize,
+}
+
+impl PhotonMap {
+    /// The number of photons that the light arriving at each point is estimated from by default.
+    const DEFAULT_NEIGHBORS: usize = 50;
+
+    /// The most times that a photon may be reflected or refracted before it is dropped.
+    const MAX_BOUNCES: usize = 16;
+
+    /// Creates a new, empty map that aims `photons` photons at the sphere with the given `center`
+    /// and `radius` when it is traced. The light arriving at a point is estimated from the
+    /// photons within `max_radius` of it.
+    pub fn new(center: Point3, radius: f64, photons: usize, max_radius: f64) -> Self {
+        Self {
+            center,
+            radius,
+            photons,
+            neighbors: Self::DEFAULT_NEIGHBORS,
+            max_radius,
+            tree: OnceLock::new(),
+        }
+    }
+
+    /// Estimates the light arriving at each point from at most `neighbors` photons instead of the
+    /// default. More photons give smoother but blurrier caustics.
+    pub fn with_neighbors(self, neighbors: usize) -> Self {
+        Self {
+            neighbors: neighbors.max(1),
+            ..self
+        }
+    }
+
+    /// Gets the number of photons that are aimed at the scene.
+    pub const fn photons(&self) -> usize {
+        self.photons
+    }
+
+    /// Gets the number of photons that were stored on diffuse surfaces, or `0` if the photons
+    /// haven't been traced yet.
+    pub fn len(&self) -> usize {
+        self.tree.get().map_or(0, Vec::len)
+    }
+
+    /// Checks whether no photons have been stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Traces the photons of the map from `background` through `world` and stores the ones that
+    /// land on diffuse surfaces as caustics. Only the first call traces photons, so the map
+    /// should be traced once the scene is complete. Returns `false` if the photons were already
+    /// traced.
+    pub fn trace(&self, world: &dyn Hittable, background: &dyn Background) -> bool {
+        if self.tree.get().is_some() {
+            return false;
+        }
+        let mut photons: Vec<Photon> = (0..self.photons)
+            .into_par_iter()
+            .filter_map(|_| self.emit(world, background))
+            .collect();
+        build_tree(&mut photons);
+        self.tree.set(photons).is_ok()
+    }
+
+    /// Checks whether `p` lies within the sphere that the photons are aimed at. A caustic is only
+    /// carried by the map if every specular bounce along it is within the sphere.
+    pub fn covers(&self, p: &Point3) -> bool {
+        (*p - self.center).length_squared() <= self.radius * self.radius
+    }
+
+    /// Estimates the caustic light arriving at `p` on a diffuse surface with the unit normal
+    /// vector `normal`, which points toward the side that the light is seen from. The light is the
+    /// average radiance over the hemisphere around `normal`, weighted by the cosine of the angle
+    /// from it, so a diffuse surface reflects the light times its albedo. This is black until the
+    /// photons have been traced.
+    pub fn lookup(&self, p: &Point3, normal: &Vec3) -> Color {
+        let Some(tree) = self.tree.get() else {
+            return Color::default();
+        };
+        let mut nearest = Vec::with_capacity(self.neighbors + 1);
+        let mut max_distance_squared = self.max_radius * self.max_radius;
+        find_nearest(
+            tree,
+            p,
+            self.neighbors,
+            &mut max_distance_squared,
+            &mut nearest,
+        );
+        let power = nearest
+            .iter()
+            .filter(|(_, photon)| photon.direction.dot(normal) < 0.)
+            .fold(Color::default(), |total, (_, photon)| total + photon.power);
+        // The irradiance is the power per unit of area of the disk that the photons were found
+        // in, and the hemisphere average is that divided by pi.
+        power / (PI * PI * max_distance_squared.max(1e-12))
+    }
+
+    /// Emits one photon from the background toward the target sphere and follows it through
+    /// `world`, returning it if it lands on a diffuse surface after one or more specular bounces
+    /// that all lie within the target sphere.
+    fn emit(&self, world: &dyn Hittable, background: &dyn Background) -> Option<Photon> {
+        let toward_light = Vec3::random_unit_vector();
+        let (u, v) = toward_light.orthonormal_basis();
+        let disk = Vec3::random_in_unit_disk();
+        let start = self.center + self.radius * (toward_light + disk.x() * u + disk.y() * v);
+        let time = random::<f64>();
+        // The background can't shine on the target past objects outside of it.
+        if world
+            .hit_by(
+                &Ray::with_time(start, toward_light, time),
+                0.001..=f64::INFINITY,
+            )
+            .is_some()
+        {
+            return None;
+        }
+        // Directions are chosen uniformly over the sphere and starting points uniformly over the
+        // disk that the target sphere casts a shadow through.
+        let area = PI * self.radius * self.radius;
+        let mut power = background.value(&toward_light) * (4. * PI * area / self.photons as f64);
+        let mut ray = Ray::with_time(start, -toward_light, time);
+        let mut specular = false;
+        for _ in 0..Self::MAX_BOUNCES {
+            let hit_record = world.hit_by(&ray, 0.001..=f64::INFINITY)?;
+            if hit_record.material.diffuse_albedo(&hit_record).is_some() {
+                return specular.then_some(Photon {
+                    p: hit_record.p,
+                    direction: ray.direction().normalized(),
+                    power,
+                    axis: 0,
+                });
+            }
+            let ScatterRecord {
+                attenuation,
+                direction,
+                pdf,
+            } = hit_record.material.scatter(&ray, &hit_record)?;
+            // Light scattered in every direction isn't focused into caustics, and the path tracer
+            // still traces the caustics that bounce outside of the target.
+            if pdf.is_some() || !self.covers(&hit_record.p) {
+                return None;
+            }
+            power = power.attenuate(&attenuation);
+            ray = direction;
+            specular = true;
+        }
+        None
+    }
+}
+
+/// Sorts `photons` into a balanced kd-tree, splitting each range along its longest axis.
+fn build_tree(photons: &mut [Photon]) {
+    if photons.len() <= 1 {
+        return;
+    }
+    let (mut min, mut max) = (photons[0].p, photons[0].p);
+    for photon in &photons[1..] {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(photon.p[axis]);
+            max[axis] = max[axis].max(photon.p[axis]);
+        }
+    }
+    let extent = max - min;
+    let axis = (0..3)
+        .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+        .unwrap_or_default();
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+    photons[middle].axis = axis;
+    let (below, above) = photons.split_at_mut(middle);
+    build_tree(below);
+    build_tree(&mut above[1..]);
+}
+
+/// Adds the photons of `tree` that are among the `count` closest to `p` and closer than the
+/// square root of `max_distance_squared` to `nearest`, sorted by their squared distances from
+/// `p`. Once `count` photons have been found, `max_distance_squared` shrinks to the squared
+/// distance of the farthest of them.
+fn find_nearest<'a>(
+    tree: &'a [Photon],
+    p: &Point3,
+    count: usize,
+    max_distance_squared: &mut f64,
+    nearest: &mut Vec<(f64, &'a Photon)>,
+) {
+    if tree.is_empty() {
+        return;
+    }
+    let middle = tree.len() / 2;
+    let photon = &tree[middle];
+    let offset = p[photon.axis] - photon.p[photon.axis];
+    let (near, far) = if offset < 0. {
+        (&tree[..middle], &tree[middle + 1..])
+    } else {
+        (&tree[middle + 1..], &tree[..middle])
+    };
+    find_nearest(near, p, count, max_distance_squared, nearest);
+    let distance_squared = (photon.p - *p).length_squared();
+    if distance_squared < *max_distance_squared {
+        let index = nearest.partition_point(|(other, _)| *other <= distance_squared);
+        nearest.insert(index, (distance_squared, photon));
+        nearest.truncate(count);
+        if nearest.len() == count {
+            *max_distance_squared = nearest[count - 1].0;
+        }
+    }
+    if offset * offset < *max_distance_squared {
+        find_nearest(far, p, count, max_distance_squared, nearest);
+    }
+}
@@ -0,0 +1,122 @@
+use std::ops::RangeInclusive;
+
+/// The maximum number of bisection steps used to refine a single root.
+const MAX_ITERATIONS: usize = 200;
+
+/// Evaluates the polynomial with the given coefficients at `x`. `coefficients[i]` is the
+/// coefficient of `x^i`.
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0., |acc, coefficient| acc * x + coefficient)
+}
+
+/// Computes the coefficients of the derivative of the polynomial with the given coefficients.
+pub fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(power, coefficient)| power as f64 * coefficient)
+        .collect()
+}
+
+/// Finds the real roots in `interval` of the polynomial with the given coefficients, sorted in
+/// ascending order. `coefficients[i]` is the coefficient of `x^i`. Roots of even multiplicity are
+/// only found if they are also roots of the derivative, which is always the case in exact
+/// arithmetic but may not be after rounding.
+pub fn real_roots(coefficients: &[f64], interval: RangeInclusive<f64>) -> Vec<f64> {
+    let degree = match coefficients.iter().rposition(|&c| c != 0.) {
+        Some(degree) => degree,
+        None => return vec![],
+    };
+    let coefficients = &coefficients[..=degree];
+    match degree {
+        0 => vec![],
+        1 => {
+            let root = -coefficients[0] / coefficients[1];
+            Some(root)
+                .filter(|root| interval.contains(root))
+                .into_iter()
+                .collect()
+        }
+        2 => quadratic_roots(coefficients[2], coefficients[1], coefficients[0])
+            .into_iter()
+            .filter(|root| interval.contains(root))
+            .collect(),
+        _ => {
+            // Every real root has a magnitude of at most `1 + max(|a_i / a_n|)`.
+            let bound = 1.
+                + coefficients[..degree]
+                    .iter()
+                    .map(|c| (c / coefficients[degree]).abs())
+                    .fold(0., f64::max);
+            let start = interval.start().max(-bound);
+            let end = interval.end().min(bound);
+            if start > end {
+                return vec![];
+            }
+            // The polynomial is monotonic between consecutive critical points, so each such
+            // segment contains at most one root.
+            let mut breakpoints = vec![start];
+            breakpoints.extend(real_roots(&derivative(coefficients), start..=end));
+            breakpoints.push(end);
+            let mut roots: Vec<f64> = breakpoints
+                .windows(2)
+                .filter_map(|segment| bisect(coefficients, segment[0], segment[1]))
+                .collect();
+            roots.dedup();
+            roots
+        }
+    }
+}
+
+/// Finds the real roots of `ax^2 + bx + c` in ascending order.
+fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        vec![]
+    } else if discriminant == 0. {
+        vec![-b / (2. * a)]
+    } else {
+        // Avoid catastrophic cancellation by never subtracting nearly equal values.
+        let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+        let (r0, r1) = if q == 0. { (0., 0.) } else { (q / a, c / q) };
+        if r0 <= r1 {
+            vec![r0, r1]
+        } else {
+            vec![r1, r0]
+        }
+    }
+}
+
+/// Finds the root of the polynomial in `[low, high]` assuming that the polynomial is monotonic on
+/// that interval.
+fn bisect(coefficients: &[f64], mut low: f64, mut high: f64) -> Option<f64> {
+    let mut low_value = evaluate(coefficients, low);
+    let high_value = evaluate(coefficients, high);
+    if low_value == 0. {
+        return Some(low);
+    } else if high_value == 0. {
+        return Some(high);
+    } else if low_value.signum() == high_value.signum() {
+        return None;
+    }
+    for _ in 0..MAX_ITERATIONS {
+        let mid = 0.5 * (low + high);
+        if mid <= low || mid >= high {
+            break;
+        }
+        let mid_value = evaluate(coefficients, mid);
+        if mid_value == 0. {
+            return Some(mid);
+        } else if mid_value.signum() == low_value.signum() {
+            low = mid;
+            low_value = mid_value;
+        } else {
+            high = mid;
+        }
+    }
+    Some(0.5 * (low + high))
+}
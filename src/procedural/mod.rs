@@ -0,0 +1,236 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use rand::{distributions::Uniform, rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    bvh::Aabb,
+    material::{Dielectric, Lambertian, Metal},
+    object::Sphere,
+    ray::{placed_id, Hittable, RayHit},
+    Color, Material, Point3, Ray,
+};
+
+/// A field of small spheres scattered over the plane `y = 0` like the one on the cover of the
+/// book, with one sphere in each unit square of the grid. The spheres are never stored. Instead,
+/// each ray walks the squares that it passes over and regenerates the spheres there from the
+/// seed, so a field of any size takes the same small amount of memory and renders the same way
+/// every time.
+#[derive(Clone)]
+pub struct SphereField {
+    seed: u64,
+    extent: Option<u32>,
+    clearings: Vec<(Point3, f64)>,
+    /// Shared by every glass sphere and used to test for hits before the material of the sphere
+    /// that was hit is generated.
+    glass: Arc<Dielectric>,
+}
+
+/// The sphere generated for one square of a [`SphereField`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    center: Point3,
+    kind: CellMaterial,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CellMaterial {
+    Diffuse(Color),
+    Metal(Color, f64),
+    Glass,
+}
+
+impl SphereField {
+    /// The radius of every sphere in the field.
+    pub const RADIUS: f64 = 0.2;
+
+    /// The most squares that a single ray walks over before giving up. This only limits rays
+    /// that skim along an unbounded field.
+    const MAX_STEPS: usize = 1 << 16;
+
+    /// Creates a new field of spheres generated from `seed`. If `extent` is present, the field
+    /// only covers the squares within that distance of the origin along the x- and z-axes.
+    /// Otherwise, it covers the whole plane.
+    pub fn new(seed: u64, extent: Option<u32>) -> Self {
+        Self {
+            seed,
+            extent,
+            clearings: vec![],
+            glass: Arc::new(Dielectric::new(1.5)),
+        }
+    }
+
+    /// Leaves out every sphere whose center is within `radius` of `center`, which makes room for
+    /// other objects.
+    pub fn add_clearing(&mut self, center: Point3, radius: f64) {
+        self.clearings.push((center, radius));
+    }
+
+    /// Generates the sphere in the square whose corner nearest negative infinity is `(a, b)`, if
+    /// there is one.
+    fn cell(&self, a: i64, b: i64) -> Option<Cell> {
+        if let Some(extent) = self.extent.map(i64::from) {
+            if !(-extent..extent).contains(&a) || !(-extent..extent).contains(&b) {
+                return None;
+            }
+        }
+        let mut rng = SmallRng::seed_from_u64(mix(self.seed
+            ^ mix(a as u64)
+            ^ mix(b as u64).rotate_left(32)));
+        let center = Point3::new(
+            a as f64 + 0.9 * rng.gen::<f64>(),
+            Self::RADIUS,
+            b as f64 + 0.9 * rng.gen::<f64>(),
+        );
+        if self
+            .clearings
+            .iter()
+            .any(|&(clearing, radius)| (center - clearing).length_squared() < radius * radius)
+        {
+            return None;
+        }
+        // The same mix of materials as the cover of the book.
+        let kind = match rng.gen_range(0..20) {
+            0..=15 => CellMaterial::Diffuse(rng.gen::<Color>().attenuate(&rng.gen())),
+            16..=18 => {
+                CellMaterial::Metal(rng.sample(Uniform::new(0.5, 1.)), 0.5 * rng.gen::<f64>())
+            }
+            _ => CellMaterial::Glass,
+        };
+        Some(Cell { center, kind })
+    }
+
+    /// Generates the material of the sphere in `cell`.
+    fn material(&self, cell: &Cell) -> Arc<dyn Material> {
+        match cell.kind {
+            CellMaterial::Diffuse(albedo) => Arc::new(Lambertian::from_color(albedo)),
+            CellMaterial::Metal(albedo, roughness) => {
+                Arc::new(Metal::rough_from_color(albedo, roughness))
+            }
+            CellMaterial::Glass => Arc::clone(&self.glass) as _,
+        }
+    }
+}
+
+/// Scrambles the bits of `x` with the finalizer of SplitMix64 so that neighboring squares get
+/// unrelated seeds.
+fn mix(x: u64) -> u64 {
+    let x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+impl Debug for SphereField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SphereField")
+            .field("seed", &self.seed)
+            .field("extent", &self.extent)
+            .field("clearings", &self.clearings)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hittable for SphereField {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        let (origin, direction) = (*ray.origin(), *ray.direction());
+        let (mut start, mut end) = valid_t.into_inner();
+        // Only look at the part of the ray that passes through the layer the spheres lie in and,
+        // if the field is bounded, over the squares that might have spheres.
+        let mut clip = |origin: f64, direction: f64, low: f64, high: f64| {
+            if direction == 0. {
+                if origin < low || origin > high {
+                    end = f64::NEG_INFINITY;
+                }
+            } else {
+                let (t0, t1) = ((low - origin) / direction, (high - origin) / direction);
+                start = start.max(t0.min(t1));
+                end = end.min(t0.max(t1));
+            }
+        };
+        clip(origin.y(), direction.y(), 0., 2. * Self::RADIUS);
+        if let Some(extent) = self.extent.map(f64::from) {
+            let (low, high) = (-extent - Self::RADIUS, extent + Self::RADIUS);
+            clip(origin.x(), direction.x(), low, high);
+            clip(origin.z(), direction.z(), low, high);
+        }
+        if start > end {
+            return None;
+        }
+
+        // Walk the squares under the ray in order with the algorithm of Amanatides and Woo
+        // (1987).
+        let entry = ray.at(start);
+        let (mut a, mut b) = (entry.x().floor() as i64, entry.z().floor() as i64);
+        let axis = |position: f64, direction: f64, cell: i64| {
+            if direction > 0. {
+                (
+                    1,
+                    start + (cell as f64 + 1. - position) / direction,
+                    1. / direction,
+                )
+            } else if direction < 0. {
+                (
+                    -1,
+                    start + (cell as f64 - position) / direction,
+                    -1. / direction,
+                )
+            } else {
+                (0, f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (step_a, mut next_a, delta_a) = axis(entry.x(), direction.x(), a);
+        let (step_b, mut next_b, delta_b) = axis(entry.z(), direction.z(), b);
+        let mut closest: Option<(RayHit, Cell)> = None;
+        for _ in 0..Self::MAX_STEPS {
+            // Spheres stick out of their own squares a little, so the neighbors must be checked
+            // too.
+            for (da, db) in (-1..=1).flat_map(|da| (-1..=1).map(move |db| (da, db))) {
+                let Some(cell) = self.cell(a + da, b + db) else {
+                    continue;
+                };
+                let limit = closest.as_ref().map_or(end, |(hit, _)| hit.t);
+                let sphere = Sphere::new(cell.center, Self::RADIUS, Arc::clone(&self.glass));
+                if let Some(hit) = sphere.hit_by(ray, start..=limit) {
+                    closest = Some((hit, cell));
+                }
+            }
+            // Every hit inside this square has been found, so a hit before leaving it must be the
+            // closest.
+            let exit = next_a.min(next_b).min(end);
+            if exit >= end || closest.as_ref().is_some_and(|(hit, _)| hit.t <= exit) {
+                break;
+            }
+            if next_a < next_b {
+                a += step_a;
+                next_a += delta_a;
+            } else {
+                b += step_b;
+                next_b += delta_b;
+            }
+        }
+        closest.map(|(mut hit, cell)| {
+            hit.material = self.material(&cell);
+            // The spheres are made anew for every ray, so they are told apart by where they are.
+            let center = cell.center;
+            let id = mix(center.x().to_bits() ^ center.z().to_bits().rotate_left(32));
+            hit.object = placed_id(id as usize, self);
+            hit
+        })
+    }
+
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.extent.map(f64::from).map(|extent| {
+            Aabb::new(
+                Point3::new(-extent - Self::RADIUS, 0., -extent - Self::RADIUS),
+                Point3::new(
+                    extent + Self::RADIUS,
+                    2. * Self::RADIUS,
+                    extent + Self::RADIUS,
+                ),
+            )
+        })
+    }
+}
@@ -0,0 +1,241 @@
+use std::ops::{Mul, Neg};
+
+use crate::{
+    angle::Angle,
+    transform::{Mat4, Transform},
+    Vec3,
+};
+
+/// A quaternion `w + xi + yj + zk`. Unit quaternions represent rotations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quat {
+    /// The quaternion that represents not rotating at all.
+    pub const IDENTITY: Self = Self::new(1., 0., 0., 0.);
+
+    /// Creates the quaternion `w + xi + yj + zk`.
+    pub const fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Creates the unit quaternion that represents a counterclockwise rotation by `angle` about
+    /// `axis` as seen when `axis` points toward the viewer.
+    pub fn from_axis_angle(axis: Vec3, angle: Angle) -> Self {
+        let (sin, cos) = (angle / 2.).sin_cos();
+        let axis = axis.normalized();
+        Self::new(cos, sin * axis.x(), sin * axis.y(), sin * axis.z())
+    }
+
+    /// Extracts the rotation matrix from the upper-left 3x3 block of `matrix` as a unit
+    /// quaternion. The block is assumed to be a pure rotation.
+    pub fn from_rotation_matrix(matrix: &Mat4) -> Self {
+        let m = |i, j| matrix[(i, j)];
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+        // Pick the largest of the four squared components to divide by for stability.
+        let ret = if trace > 0. {
+            let s = 2. * (trace + 1.).sqrt();
+            Self::new(
+                s / 4.,
+                (m(2, 1) - m(1, 2)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+                (m(1, 0) - m(0, 1)) / s,
+            )
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = 2. * (1. + m(0, 0) - m(1, 1) - m(2, 2)).sqrt();
+            Self::new(
+                (m(2, 1) - m(1, 2)) / s,
+                s / 4.,
+                (m(0, 1) + m(1, 0)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+            )
+        } else if m(1, 1) > m(2, 2) {
+            let s = 2. * (1. + m(1, 1) - m(0, 0) - m(2, 2)).sqrt();
+            Self::new(
+                (m(0, 2) - m(2, 0)) / s,
+                (m(0, 1) + m(1, 0)) / s,
+                s / 4.,
+                (m(1, 2) + m(2, 1)) / s,
+            )
+        } else {
+            let s = 2. * (1. + m(2, 2) - m(0, 0) - m(1, 1)).sqrt();
+            Self::new(
+                (m(1, 0) - m(0, 1)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+                (m(1, 2) + m(2, 1)) / s,
+                s / 4.,
+            )
+        };
+        ret.normalized()
+    }
+
+    /// Gets the real part of the quaternion.
+    pub const fn w(&self) -> f64 {
+        self.w
+    }
+
+    /// Gets the coefficient of `i`.
+    pub const fn x(&self) -> f64 {
+        self.x
+    }
+
+    /// Gets the coefficient of `j`.
+    pub const fn y(&self) -> f64 {
+        self.y
+    }
+
+    /// Gets the coefficient of `k`.
+    pub const fn z(&self) -> f64 {
+        self.z
+    }
+
+    /// Gets the imaginary part of the quaternion as a vector.
+    pub const fn vector(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// The dot product of the quaternions as 4D vectors.
+    pub fn dot(&self, rhs: &Self) -> f64 {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    /// The length of the quaternion as a 4D vector.
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Produces the quaternion with the same direction as `self` and a length of `1`.
+    pub fn normalized(&self) -> Self {
+        let length = self.length();
+        Self::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    /// Produces the conjugate `w - xi - yj - zk`. For unit quaternions, this is the inverse
+    /// rotation.
+    pub const fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Converts the rotation represented by this unit quaternion into an axis and the angle of
+    /// rotation about it. The identity rotation produces an arbitrary axis.
+    pub fn to_axis_angle(&self) -> (Vec3, Angle) {
+        let q = if self.w < 0. { -*self } else { *self };
+        let sin = q.vector().length();
+        let angle = Angle::atan2(q.w, sin) * 2.;
+        if sin == 0. {
+            (Vec3::new(1., 0., 0.), angle)
+        } else {
+            (q.vector() / sin, angle)
+        }
+    }
+
+    /// Rotates `v` by the rotation represented by this unit quaternion.
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        // This is an expansion of `q * v * q.conjugate()`.
+        let u = self.vector();
+        let t = 2. * u.cross(v);
+        v + self.w * t + u.cross(&t)
+    }
+
+    /// Interpolates along the shortest great-circle arc from `self` to `other`. Both quaternions
+    /// should be unit quaternions. `t` is clamped to `[0.0, 1.0]`.
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        let t = t.clamp(0., 1.);
+        let mut cos = self.dot(other);
+        // `q` and `-q` are the same rotation, so go the short way around.
+        let other = if cos < 0. {
+            cos = -cos;
+            -*other
+        } else {
+            *other
+        };
+        let (a, b) = if cos > 0.9995 {
+            // The quaternions are nearly parallel so linear interpolation is accurate and avoids
+            // dividing by a tiny sine.
+            (1. - t, t)
+        } else {
+            let theta = Angle::acos(cos);
+            let sin = theta.sin();
+            ((theta * (1. - t)).sin() / sin, (theta * t).sin() / sin)
+        };
+        Self::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalized()
+    }
+
+    /// Converts this unit quaternion into a rotation matrix.
+    pub fn to_matrix(&self) -> Mat4 {
+        let Self { w, x, y, z } = *self;
+        Mat4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<Quat> for Transform {
+    fn from(q: Quat) -> Self {
+        let (axis, angle) = q.to_axis_angle();
+        Self::rotation(axis, angle)
+    }
+}
+
+/// The Hamilton product. For unit quaternions, `a * b` is the rotation that applies `b` and then
+/// `a`.
+impl Mul for Quat {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+impl Neg for Quat {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
@@ -1,22 +1,55 @@
 use std::{
     fmt::{self, Debug, Formatter},
     ops::RangeInclusive,
+    slice,
     sync::Arc,
 };
 
-use crate::{Material, Point3, Vec3};
+use rand::RngCore;
+
+use crate::{bvh::Aabb, Material, Point3, Vec3};
 
 /// The path of a light ray.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f64,
+    media: Media,
+    channel: Option<usize>,
 }
 
 impl Ray {
-    /// Creates a new ray starting at `origin` and traveling by `direction` per unit time.
+    /// Creates a new ray starting at `origin` and traveling by `direction` per unit time. The ray
+    /// is cast at the moment the shutter opens.
     pub const fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self::with_time(origin, direction, 0.)
+    }
+
+    /// Creates a new ray starting at `origin` and traveling by `direction` per unit time that is
+    /// cast at the moment `time` of the exposure.
+    pub const fn with_time(origin: Point3, direction: Vec3, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            media: Media::EMPTY,
+            channel: None,
+        }
+    }
+
+    /// Creates a new ray starting at `origin` and traveling by `direction` per unit time that is
+    /// cast at the same moment as `self`, is inside the same transparent objects, and carries the
+    /// same color channel. Materials should scatter light with this so that objects inside of
+    /// other objects look right.
+    pub const fn redirected(&self, origin: Point3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction,
+            time: self.time,
+            media: self.media,
+            channel: self.channel,
+        }
     }
 
     /// The position of the ray at time 0.
@@ -30,17 +63,121 @@ impl Ray {
         &self.direction
     }
 
+    /// The moment during the exposure at which the ray was cast. This is independent of the
+    /// parameter of [`at()`] and is used to determine where moving objects are.
+    ///
+    /// [`at()`]: Self::at()
+    pub const fn time(&self) -> f64 {
+        self.time
+    }
+
     /// The position of the ray at time `time`.
     pub fn at(&self, time: f64) -> Point3 {
         self.origin + time * self.direction
     }
 
+    /// The transparent objects that the ray is inside of.
+    pub(crate) const fn media(&self) -> &Media {
+        &self.media
+    }
+
+    /// The transparent objects that the ray is inside of.
+    pub(crate) fn media_mut(&mut self) -> &mut Media {
+        &mut self.media
+    }
+
+    /// The only color channel whose light the ray carries, if a material that splits light into
+    /// colors has chosen one for it. Every later such material must keep the same channel, so
+    /// that the path is only weighted for the channels that it drops once.
+    pub(crate) const fn channel(&self) -> Option<usize> {
+        self.channel
+    }
+
+    /// Chooses the only color channel whose light the ray carries.
+    pub(crate) fn set_channel(&mut self, channel: usize) {
+        self.channel = Some(channel);
+    }
+
     /// Checks whether the ray hits `h`.
     pub fn hits(&self, h: &dyn Hittable) -> Option<RayHit> {
         h.hit_by(self, 0.0..=f64::MAX)
     }
 }
 
+/// A transparent object that a ray is inside of.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Medium {
+    /// Identifies the object, as given by [`RayHit::object`].
+    pub(crate) id: usize,
+    /// The refractive index of the object.
+    pub(crate) refractive_index: f64,
+    /// Where objects overlap, the ray is considered to be inside only the one with the highest
+    /// priority.
+    pub(crate) priority: u32,
+}
+
+/// The transparent objects that a ray is inside of, in the order that it entered them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Media {
+    entries: [Medium; Self::CAPACITY],
+    len: usize,
+}
+
+impl Media {
+    /// The most objects that a ray can be inside at once. If a ray enters more than this, the
+    /// ones that it entered first are forgotten.
+    const CAPACITY: usize = 4;
+
+    /// A ray that isn't inside of anything.
+    const EMPTY: Self = Self {
+        entries: [Medium {
+            id: 0,
+            refractive_index: 1.,
+            priority: 0,
+        }; Self::CAPACITY],
+        len: 0,
+    };
+
+    /// Iterates over the objects from the first entered to the last.
+    fn iter(&self) -> slice::Iter<'_, Medium> {
+        self.entries[..self.len].iter()
+    }
+
+    /// Finds the object that the ray is traveling through, ignoring the object identified by
+    /// `except`. This is the object with the highest priority, or the last one entered of those
+    /// that are tied. Returns `None` if the ray is only inside of air.
+    pub(crate) fn current(&self, except: usize) -> Option<&Medium> {
+        self.iter()
+            .rev()
+            .filter(|medium| medium.id != except)
+            .reduce(|best, medium| {
+                if medium.priority > best.priority {
+                    medium
+                } else {
+                    best
+                }
+            })
+    }
+
+    /// Records that the ray has entered `medium`.
+    pub(crate) fn enter(&mut self, medium: Medium) {
+        if self.len == Self::CAPACITY {
+            self.entries.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.entries[self.len] = medium;
+        self.len += 1;
+    }
+
+    /// Records that the ray has left the object identified by `id`.
+    pub(crate) fn leave(&mut self, id: usize) {
+        if let Some(index) = self.iter().rposition(|medium| medium.id == id) {
+            self.entries.copy_within(index + 1..self.len, index);
+            self.len -= 1;
+        }
+    }
+}
+
 /// The intersection of a ray with a [`Hittable`] object.
 #[derive(Clone)]
 pub struct RayHit {
@@ -48,10 +185,41 @@ pub struct RayHit {
     pub p: Point3,
     /// The normal vector to the surface of the [`Hittable`] object at `p`.
     pub normal: Vec3,
+    /// A unit vector tangent to the surface at `p`. If the surface has surface coordinates, this
+    /// points in the direction that `u` increases. Together with `normal`, this defines the
+    /// tangent space that normal maps are expressed in.
+    pub tangent: Vec3,
     /// The material of the [`Hittable`] object at `p`.
     pub material: Arc<dyn Material>,
+    /// Identifies the object that was hit, so that a ray can keep track of which transparent
+    /// objects it is inside of even where several of them share a material. Objects identify
+    /// their hits by [`object_id()`], and objects that place other objects in the scene replace
+    /// that with [`placed_id()`], since the same object may be placed more than once.
+    pub object: usize,
     /// The time at which the ray hit `p`.
     pub t: f64,
+    /// The first surface coordinate of `p`. Objects without a natural parameterization use `0`.
+    pub u: f64,
+    /// The second surface coordinate of `p`. Objects without a natural parameterization use `0`.
+    pub v: f64,
+    /// The velocity of the surface at `p` per unit of exposure time.
+    pub velocity: Vec3,
+    /// An estimate of the mean curvature of the surface at `p`, which is the reciprocal of the
+    /// radius of a sphere that bends the same way on average. It is positive where the surface
+    /// bends away from `normal`, as on the outside of a sphere, and zero where it is flat.
+    pub curvature: f64,
+}
+
+impl RayHit {
+    /// The cosine of the angle between `normal` and the direction back along `ray`. This is `1.0`
+    /// where the surface faces the ray head-on and falls to `0.0` at silhouettes, from either side
+    /// of the surface.
+    pub fn facing_ratio(&self, ray: &Ray) -> f64 {
+        self.normal
+            .normalized()
+            .dot(&ray.direction().normalized())
+            .abs()
+    }
 }
 
 impl Debug for RayHit {
@@ -59,15 +227,56 @@ impl Debug for RayHit {
         f.debug_struct("RayHit")
             .field("p", &self.p)
             .field("normal", &self.normal)
+            .field("tangent", &self.tangent)
             .field("material", &self.material.name())
+            .field("object", &self.object)
             .field("t", &self.t)
+            .field("u", &self.u)
+            .field("v", &self.v)
+            .field("velocity", &self.velocity)
+            .field("curvature", &self.curvature)
             .finish()
     }
 }
 
+/// Gets the identity of the hits on `object`.
+pub fn object_id<T>(object: &T) -> usize {
+    object as *const T as usize
+}
+
+/// Gets the identity of a hit on an object that `placement` places in the scene, given the
+/// identity `id` of the hit on the object itself.
+pub fn placed_id<T>(id: usize, placement: &T) -> usize {
+    id.wrapping_mul(0x9e37_79b9).rotate_left(16) ^ object_id(placement)
+}
+
 /// An object that can be hit by a [`Ray`].
 pub trait Hittable: Send + Sync {
     /// Checks whether the ray hits this object no earlier than `valid_t.start()` and no later than
     /// `valid_t.end()`. If it does, returns the lowest such value of `t`.
     fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit>;
+
+    /// Computes a box that contains the whole object at every moment in `time`, or `None` if the
+    /// object is unbounded. Objects without a box can't be sorted into a
+    /// [`Bvh`](crate::bvh::Bvh).
+    fn bounding_box(&self, _time: RangeInclusive<f64>) -> Option<Aabb> {
+        None
+    }
+
+    /// Lists the materials that the surfaces of the object are made of. Objects that make up
+    /// their materials as they are hit list none of those.
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        vec![]
+    }
+}
+
+/// A [`Hittable`] object with a finite surface that can be measured and sampled, such as the
+/// shape of an area light.
+pub trait Surface: Hittable {
+    /// The area of the surface.
+    fn area(&self) -> f64;
+
+    /// Picks a point uniformly from the surface and returns it along with the unit normal to the
+    /// surface at that point. The normal points the same way as the normals of hits.
+    fn sample_surface(&self, rng: &mut dyn RngCore) -> (Point3, Vec3);
 }
@@ -0,0 +1,56 @@
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+use rand::{distributions::Standard, prelude::Distribution, rngs::SmallRng, Rng, SeedableRng};
+
+/// The seed that every thread's generator is derived from, if one was chosen.
+static SEED: OnceLock<u64> = OnceLock::new();
+
+/// The number of threads that have created a generator so far.
+static THREADS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(new_thread_rng());
+}
+
+/// Creates the generator for the current thread.
+fn new_thread_rng() -> SmallRng {
+    match SEED.get() {
+        Some(&seed) => {
+            // Give each thread a distinct stream. Threads are numbered in the order that they first
+            // draw a number, so the thread that generates the scene always gets the same stream,
+            // but which stream traces which ray depends on how rayon hands out the work.
+            let index = THREADS.fetch_add(1, Ordering::Relaxed);
+            SmallRng::seed_from_u64(seed ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        }
+        None => SmallRng::from_entropy(),
+    }
+}
+
+/// Chooses the seed that the generators of all threads are derived from. This only affects
+/// threads that have not generated any random numbers yet, so it should be called before
+/// rendering starts. The random numbers drawn on the thread that calls this are reproducible, but
+/// the ones drawn on the threads of a parallel render are not, since the threads take their work
+/// in whatever order they become free. Returns `false` if a seed was already chosen.
+pub fn set_seed(seed: u64) -> bool {
+    SEED.set(seed).is_ok()
+}
+
+/// Runs `f` with the current thread's random number generator. `f` must not call back into this
+/// module.
+pub fn with_rng<T>(f: impl FnOnce(&mut SmallRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// Generates a random value from the current thread's random number generator.
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    with_rng(|rng| rng.gen())
+}
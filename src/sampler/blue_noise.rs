@@ -0,0 +1,168 @@
+use std::sync::OnceLock;
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use super::{
+    permute,
+    sobol::{mix_bits, owen_scramble, second_dimension, to_unit},
+    SampleIndex, Sampler,
+};
+
+/// A sampler that gives every pixel the same points of the Sobol sequence, shifted by an amount
+/// taken from a tile of blue noise that repeats across the image, as in "Blue-noise Dithered
+/// Sampling" by Georgiev and Fajardo (2016). Neighboring pixels get very different shifts, so the
+/// error left at low numbers of rays per pixel looks like fine, even grain instead of blotches,
+/// which is easier on the eye and easier to filter away. Each dimension or pair of dimensions
+/// shuffles and Owen-scrambles the points of the sequence by its own seed, so that the dimensions
+/// aren't correlated with each other, and reads the tile at a different offset. The seeds are
+/// shared by every pixel, since the shifts are what keep neighboring pixels apart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlueNoise {
+    seed: u32,
+}
+
+impl BlueNoise {
+    /// The width and height of the tile of blue noise in pixels.
+    const TILE_SIZE: u32 = 64;
+
+    /// Creates a new blue noise sampler whose offsets into the tile are chosen by `seed`.
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Gets the shift of the pixel of `sample` along `dimension`.
+    fn shift(&self, sample: SampleIndex, dimension: u32) -> f64 {
+        let offset = mix_bits(u64::from(self.seed) << 32 | u64::from(dimension));
+        let (x, y) = sample.pixel;
+        let x = x.wrapping_add(offset as u32) % Self::TILE_SIZE;
+        let y = y.wrapping_add((offset >> 32) as u32) % Self::TILE_SIZE;
+        tile()[(y * Self::TILE_SIZE + x) as usize]
+    }
+
+    /// Derives the seed for shuffling and scrambling the points of every pixel along `dimension`.
+    fn hash(&self, dimension: u32) -> u64 {
+        mix_bits(mix_bits(u64::from(self.seed) << 32 | u64::from(dimension)))
+    }
+
+    /// Finds where in the sequence the point of `sample` is taken from. Each set of rays takes
+    /// the next run of points of the sequence.
+    fn index(sample: SampleIndex, hash: u64) -> u32 {
+        let count = sample.count.max(1) as u32;
+        let index = sample.index as u32;
+        index - index % count + permute(index % count, count, hash as u32)
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn get_1d(&self, sample: SampleIndex, dimension: u32) -> f64 {
+        let hash = self.hash(dimension);
+        let index = Self::index(sample, hash);
+        let point = to_unit(owen_scramble(index.reverse_bits(), (hash >> 32) as u32));
+        (point + self.shift(sample, dimension)).fract()
+    }
+
+    fn get_2d(&self, sample: SampleIndex, dimension: u32) -> (f64, f64) {
+        let hash = self.hash(dimension);
+        let index = Self::index(sample, hash);
+        let second_seed = mix_bits(hash) as u32;
+        (
+            (to_unit(owen_scramble(index.reverse_bits(), (hash >> 32) as u32))
+                + self.shift(sample, dimension))
+            .fract(),
+            (to_unit(owen_scramble(second_dimension(index), second_seed))
+                + self.shift(sample, dimension + 1))
+            .fract(),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "blue noise"
+    }
+}
+
+/// Gets the tile of blue noise, generating it the first time it is needed. Each pixel holds a
+/// distinct value between `0` and `1`, and the pixels below any threshold are spread evenly over
+/// the tile.
+fn tile() -> &'static [f64] {
+    static TILE: OnceLock<Vec<f64>> = OnceLock::new();
+    TILE.get_or_init(|| void_and_cluster(BlueNoise::TILE_SIZE as usize))
+}
+
+/// Generates a `size` by `size` tile of blue noise by the void and cluster method of Ulichney
+/// (1993). Pixels are ranked by repeatedly filling the largest void of the pixels ranked so far,
+/// where the density around each pixel is measured with a Gaussian filter that wraps around the
+/// edges of the tile.
+fn void_and_cluster(size: usize) -> Vec<f64> {
+    const SIGMA: f64 = 1.5;
+    let pixels = size * size;
+    // The filter is smooth, so its weight between every pair of pixels depends only on how far
+    // apart they are.
+    let wrapped = |d: usize| d.min(size - d) as f64;
+    let weights: Vec<f64> = (0..pixels)
+        .map(|i| {
+            let (dx, dy) = (wrapped(i % size), wrapped(i / size));
+            (-(dx * dx + dy * dy) / (2. * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let mut energy = vec![0.; pixels];
+    let mut on = vec![false; pixels];
+    let toggle = |on: &mut [bool], energy: &mut [f64], i: usize| {
+        on[i] = !on[i];
+        let sign = if on[i] { 1. } else { -1. };
+        let (x, y) = (i % size, i / size);
+        for (j, e) in energy.iter_mut().enumerate() {
+            let dx = (j % size + size - x) % size;
+            let dy = (j / size + size - y) % size;
+            *e += sign * weights[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |on: &[bool], energy: &[f64]| {
+        (0..pixels)
+            .filter(|&i| on[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+    let largest_void = |on: &[bool], energy: &[f64]| {
+        (0..pixels)
+            .filter(|&i| !on[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+
+    // Start from random pixels and move them out of clusters into voids until they are spread
+    // evenly.
+    let mut rng = SmallRng::seed_from_u64(0x626c_7565);
+    let initial = (pixels / 10).max(1);
+    while on.iter().filter(|&&on| on).count() < initial {
+        let i = rng.gen_range(0..pixels);
+        if !on[i] {
+            toggle(&mut on, &mut energy, i);
+        }
+    }
+    loop {
+        let cluster = tightest_cluster(&on, &energy).expect("Some pixels are on");
+        toggle(&mut on, &mut energy, cluster);
+        let void = largest_void(&on, &energy).expect("Some pixels are off");
+        if void == cluster {
+            toggle(&mut on, &mut energy, cluster);
+            break;
+        }
+        toggle(&mut on, &mut energy, void);
+    }
+
+    let mut rank = vec![0; pixels];
+    // Rank the initial pixels by removing the tightest cluster until none are left.
+    let (mut prototype_on, mut prototype_energy) = (on.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = tightest_cluster(&prototype_on, &prototype_energy).expect("Pixels remain");
+        toggle(&mut prototype_on, &mut prototype_energy, cluster);
+        rank[cluster] = r;
+    }
+    // Rank the rest by filling the largest void until every pixel is on.
+    for r in initial..pixels {
+        let void = largest_void(&on, &energy).expect("Pixels remain");
+        toggle(&mut on, &mut energy, void);
+        rank[void] = r;
+    }
+    rank.into_iter()
+        .map(|r| (r as f64 + 0.5) / pixels as f64)
+        .collect()
+}
@@ -0,0 +1,84 @@
+use super::{grid, permute, scramble, set_seed, SampleIndex, Sampler};
+
+/// A sampler that spreads the rays through each pixel by correlated multi-jittered sampling, as in
+/// "Correlated Multi-Jittered Sampling" by Kensler (2013). Each pair of dimensions is divided into
+/// a grid with as many cells as there are rays, and the points are placed so that every row,
+/// every column, and every cell of the grid holds exactly one of them. The grid is the one closest
+/// to square whose rows and columns divide the rays evenly, which is a single row of strips if the
+/// number of rays is prime. Every point is computed
+/// from a hash of its pixel, dimension, and index alone, so no state is kept between rays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CorrelatedMultiJittered {
+    seed: u32,
+}
+
+impl CorrelatedMultiJittered {
+    /// Creates a new correlated multi-jittered sampler whose shuffles are chosen by `seed`.
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Derives the seed for shuffling the points of `sample` along `dimension`. Each set of rays
+    /// is shuffled differently.
+    fn hash(&self, sample: SampleIndex, dimension: u32) -> u32 {
+        scramble(sample, dimension) ^ set_seed(sample) ^ self.seed
+    }
+}
+
+impl Sampler for CorrelatedMultiJittered {
+    fn get_1d(&self, sample: SampleIndex, dimension: u32) -> f64 {
+        let count = sample.count.max(1) as u32;
+        let hash = self.hash(sample, dimension);
+        let index = permute(
+            sample.index as u32 % count,
+            count,
+            hash.wrapping_mul(0x68bc_21eb),
+        );
+        let jitter = jitter(index, hash.wrapping_mul(0x967a_889b));
+        (f64::from(index) + jitter) / f64::from(count)
+    }
+
+    fn get_2d(&self, sample: SampleIndex, dimension: u32) -> (f64, f64) {
+        let count = sample.count.max(1) as u32;
+        let (columns, rows) = grid(count);
+        let hash = self.hash(sample, dimension);
+        let index = permute(
+            sample.index as u32 % count,
+            count,
+            hash.wrapping_mul(0x5163_3e2d),
+        );
+        let (column, row) = (index % columns, index / columns);
+        // Every column is offset within its cells by the same shuffle of the rows, and every row
+        // by the same shuffle of the columns, so each of the narrow strips that the cells are
+        // divided into holds one point as well.
+        let shifted_column = permute(column, columns, hash.wrapping_mul(0xa511_e9b3));
+        let shifted_row = permute(row, rows, hash.wrapping_mul(0x63d8_3595));
+        let jitter_x = jitter(index, hash.wrapping_mul(0xa399_d265));
+        let jitter_y = jitter(index, hash.wrapping_mul(0x711a_d6a5));
+        let (columns, rows) = (f64::from(columns), f64::from(rows));
+        (
+            ((f64::from(column) + (f64::from(shifted_row) + jitter_x) / rows) / columns).min(1.),
+            ((f64::from(row) + (f64::from(shifted_column) + jitter_y) / columns) / rows).min(1.),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "correlated multi-jittered"
+    }
+}
+
+/// Hashes `index` and `seed` to a number between `0` and `1` for moving a point around within
+/// its strip.
+fn jitter(mut index: u32, seed: u32) -> f64 {
+    index ^= seed;
+    index ^= index >> 17;
+    index ^= index >> 10;
+    index = index.wrapping_mul(0xb365_34e5);
+    index ^= index >> 12;
+    index ^= index >> 21;
+    index = index.wrapping_mul(0x93fc_4795);
+    index ^= 0xdf6e_307f;
+    index ^= index >> 17;
+    index = index.wrapping_mul(1 | seed >> 18);
+    f64::from(index) / (1u64 << 32) as f64
+}
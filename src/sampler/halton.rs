@@ -0,0 +1,69 @@
+use super::{permute, scramble, SampleIndex, Sampler};
+
+/// A sampler that spreads the rays through each pixel over the Halton sequence, whose dimensions
+/// are the radical inverses of the index of each point in successive prime bases. Every digit of
+/// every point is shuffled by a permutation chosen separately for each pixel and dimension, which
+/// keeps the points even while breaking up the patterns that the higher bases would otherwise
+/// make.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Halton {
+    seed: u32,
+}
+
+impl Halton {
+    /// The bases of the dimensions of the sequence. Dimensions past the last prime wrap around to
+    /// the first ones.
+    const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+    /// Creates a new Halton sampler whose scrambling is chosen by `seed`.
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Computes the point of `sample` along `dimension`, whose digits are shuffled by
+    /// permutations chosen by `hash`, and with its index shuffled within its set of rays as well.
+    fn radical_inverse(&self, sample: SampleIndex, dimension: u32, hash: u32) -> f64 {
+        let base = Self::PRIMES[dimension as usize % Self::PRIMES.len()];
+        let count = sample.count.max(1) as u32;
+        let index = sample.index as u32;
+        let mut index = index - index % count + permute(index % count, count, hash);
+        let inverse_base = 1. / f64::from(base);
+        let mut scale = inverse_base;
+        let mut value = 0.;
+        let mut digit = 0u32;
+        // Every digit is permuted, so the trailing zeros contribute to the value too.
+        while scale > f64::EPSILON {
+            let shuffled = permute(
+                index % base,
+                base,
+                hash ^ (digit + 1).wrapping_mul(0x9e37_79b9),
+            );
+            value += f64::from(shuffled) * scale;
+            index /= base;
+            scale *= inverse_base;
+            digit += 1;
+        }
+        value.min(1. - f64::EPSILON / 2.)
+    }
+}
+
+impl Sampler for Halton {
+    fn get_1d(&self, sample: SampleIndex, dimension: u32) -> f64 {
+        let hash = scramble(sample, dimension) ^ self.seed;
+        self.radical_inverse(sample, dimension, hash)
+    }
+
+    fn get_2d(&self, sample: SampleIndex, dimension: u32) -> (f64, f64) {
+        // Both halves of the pair must take the same point of the sequence, so they share the
+        // shuffle of the index.
+        let hash = scramble(sample, dimension) ^ self.seed;
+        (
+            self.radical_inverse(sample, dimension, hash),
+            self.radical_inverse(sample, dimension + 1, hash),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "halton"
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    f64::consts::FRAC_PI_4,
+    fmt::{self, Debug, Formatter},
+};
+
+use crate::rng;
+
+mod blue_noise;
+pub use blue_noise::BlueNoise;
+
+mod cmj;
+pub use cmj::CorrelatedMultiJittered;
+
+mod halton;
+pub use halton::Halton;
+
+mod sobol;
+pub use sobol::Sobol;
+
+/// Identifies one of the rays traced through a pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleIndex {
+    /// The column and row of the pixel, counted from the left and bottom edges of the image.
+    pub pixel: (u32, u32),
+    /// Which of the rays through the pixel this is, counting from `0`. Rays from `count` on belong
+    /// to further sets of `count` rays, each of which is spread out on its own, so that more rays
+    /// can be traced through a pixel after the first set.
+    pub index: usize,
+    /// The number of rays in each set of rays traced through the pixel.
+    pub count: usize,
+}
+
+/// A source of the points that the rays through a pixel sample the pixel, the lens, and the
+/// exposure at. Each dimension of a sample is requested separately, and samplers may spread the
+/// points of the rays through a pixel out more evenly than independent random numbers would.
+pub trait Sampler: Send + Sync {
+    /// Gets a point between `0` and `1` for the dimension `dimension` of `sample`.
+    fn get_1d(&self, sample: SampleIndex, dimension: u32) -> f64;
+
+    /// Gets a point in the unit square for the pair of dimensions starting at `dimension` of
+    /// `sample`.
+    fn get_2d(&self, sample: SampleIndex, dimension: u32) -> (f64, f64);
+
+    /// The name of the sampler.
+    fn name(&self) -> &'static str;
+}
+
+impl Debug for dyn Sampler + '_ {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("name", &self.name())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The pair of dimensions of a sample for the position of its ray within its pixel.
+pub const PIXEL_DIMENSION: u32 = 0;
+/// The pair of dimensions of a sample for the position of its ray on the lens.
+pub const LENS_DIMENSION: u32 = 2;
+/// The dimension of a sample for the moment while the shutter is open that its ray is cast at.
+pub const TIME_DIMENSION: u32 = 4;
+
+/// A sampler that picks every point independently at random.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Independent;
+
+impl Sampler for Independent {
+    fn get_1d(&self, _sample: SampleIndex, _dimension: u32) -> f64 {
+        rng::random()
+    }
+
+    fn get_2d(&self, _sample: SampleIndex, _dimension: u32) -> (f64, f64) {
+        (rng::random(), rng::random())
+    }
+
+    fn name(&self) -> &'static str {
+        "independent"
+    }
+}
+
+/// A sampler that divides each dimension, or the unit square for each pair of dimensions, into as
+/// many equal cells as there are rays through the pixel and puts the point of each ray at a random
+/// position within its own cell. The cells of a pair of dimensions form the grid closest to square
+/// whose rows and columns divide the rays evenly, which is a single row of strips if the number of
+/// rays is prime. The cells are shuffled separately for every pixel and dimension so that the
+/// dimensions aren't correlated with each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stratified;
+
+impl Sampler for Stratified {
+    fn get_1d(&self, sample: SampleIndex, dimension: u32) -> f64 {
+        let count = sample.count.max(1) as u32;
+        let stratum = permute(
+            sample.index as u32 % count,
+            count,
+            scramble(sample, dimension) ^ set_seed(sample),
+        );
+        (f64::from(stratum) + rng::random::<f64>()) / f64::from(count)
+    }
+
+    fn get_2d(&self, sample: SampleIndex, dimension: u32) -> (f64, f64) {
+        let count = sample.count.max(1) as u32;
+        let (columns, rows) = grid(count);
+        let cell = permute(
+            sample.index as u32 % count,
+            count,
+            scramble(sample, dimension) ^ set_seed(sample),
+        );
+        (
+            (f64::from(cell % columns) + rng::random::<f64>()) / f64::from(columns),
+            (f64::from(cell / columns) + rng::random::<f64>()) / f64::from(rows),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "stratified"
+    }
+}
+
+/// Finds the numbers of columns and rows of the grid closest to square that has exactly `count`
+/// cells, so that no cell is left empty. This is a single row if `count` is prime.
+pub(crate) fn grid(count: u32) -> (u32, u32) {
+    let columns = (1..=f64::from(count).sqrt() as u32)
+        .rev()
+        .find(|&columns| count.is_multiple_of(columns))
+        .unwrap_or(1);
+    (columns, count / columns.max(1))
+}
+
+/// Derives a pseudorandom seed for shuffling the points of the pixel of `sample` along
+/// `dimension`.
+pub(crate) fn scramble(sample: SampleIndex, dimension: u32) -> u32 {
+    let (x, y) = sample.pixel;
+    let mut hash = x.wrapping_mul(0x8da6_b343)
+        ^ y.wrapping_mul(0xd816_3841)
+        ^ dimension.wrapping_mul(0xcb1a_b31f);
+    // The finalizer of MurmurHash3.
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+/// Derives a seed that differs between the sets of rays through the pixel of `sample`, so that
+/// each set is shuffled differently. The seed of the first set is `0`.
+pub(crate) fn set_seed(sample: SampleIndex) -> u32 {
+    ((sample.index / sample.count.max(1)) as u32).wrapping_mul(0x2c1b_3c6d)
+}
+
+/// Maps `index` to its position in a pseudorandom shuffle of the integers from `0` up to but
+/// excluding `length` that is chosen by `seed`, without storing the shuffle. This is the hashing
+/// permutation from "Correlated Multi-Jittered Sampling" by Kensler (2013).
+pub(crate) fn permute(mut index: u32, length: u32, seed: u32) -> u32 {
+    if length <= 1 {
+        return 0;
+    }
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            break index.wrapping_add(seed) % length;
+        }
+    }
+}
+
+/// Maps the point `(u, v)` of the unit square onto the unit disk by the concentric mapping of
+/// Shirley and Chiu (1997), which keeps points that are evenly spread over the square evenly
+/// spread over the disk.
+pub fn concentric_disk((u, v): (f64, f64)) -> (f64, f64) {
+    let (x, y) = (2. * u - 1., 2. * v - 1.);
+    if x == 0. && y == 0. {
+        return (0., 0.);
+    }
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, 2. * FRAC_PI_4 - FRAC_PI_4 * (x / y))
+    };
+    (radius * theta.cos(), radius * theta.sin())
+}
@@ -0,0 +1,99 @@
+use super::{permute, scramble, SampleIndex, Sampler};
+
+/// A sampler that spreads the rays through each pixel over the first two dimensions of the Sobol
+/// sequence, which cover the unit square far more evenly than random points do. Each dimension or
+/// pair of dimensions is shuffled and Owen-scrambled separately for every pixel, as in the padded
+/// Sobol sampler of pbrt, so that neither the dimensions nor neighboring pixels are correlated.
+/// The points are most even when the number of rays through a pixel is a power of two.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sobol {
+    seed: u32,
+}
+
+impl Sobol {
+    /// Creates a new Sobol sampler whose scrambling is chosen by `seed`.
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Derives the seed for shuffling and scrambling the points of `sample` along `dimension`.
+    fn hash(&self, sample: SampleIndex, dimension: u32) -> u64 {
+        mix_bits(u64::from(self.seed) << 32 | u64::from(scramble(sample, dimension)))
+    }
+
+    /// Finds where in the sequence the point of `sample` is taken from. Each set of rays takes
+    /// the next run of points of the sequence.
+    fn index(sample: SampleIndex, hash: u64) -> u32 {
+        let count = sample.count.max(1) as u32;
+        let index = sample.index as u32;
+        index - index % count + permute(index % count, count, hash as u32)
+    }
+}
+
+impl Sampler for Sobol {
+    fn get_1d(&self, sample: SampleIndex, dimension: u32) -> f64 {
+        let hash = self.hash(sample, dimension);
+        let index = Self::index(sample, hash);
+        to_unit(owen_scramble(index.reverse_bits(), (hash >> 32) as u32))
+    }
+
+    fn get_2d(&self, sample: SampleIndex, dimension: u32) -> (f64, f64) {
+        let hash = self.hash(sample, dimension);
+        let index = Self::index(sample, hash);
+        let second_seed = mix_bits(hash) as u32;
+        (
+            to_unit(owen_scramble(index.reverse_bits(), (hash >> 32) as u32)),
+            to_unit(owen_scramble(second_dimension(index), second_seed)),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "sobol"
+    }
+}
+
+/// Computes the bits of the point at `index` in the second dimension of the Sobol sequence, whose
+/// direction numbers are generated by the primitive polynomial `x + 1`.
+pub(super) fn second_dimension(index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut bits = 0;
+    let mut index = index;
+    while index != 0 {
+        if index & 1 == 1 {
+            bits ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+    bits
+}
+
+/// Applies a nested uniform scramble to the bits of a point, which randomizes it while keeping
+/// the points of the sequence just as evenly spread. Each bit is flipped or not depending on a
+/// hash of all the bits above it.
+pub(super) fn owen_scramble(mut bits: u32, seed: u32) -> u32 {
+    if seed & 1 == 1 {
+        bits ^= 1 << 31;
+    }
+    for b in 1..32 {
+        let mask = u32::MAX << (32 - b);
+        if mix_bits(u64::from((bits & mask) ^ seed)) & (1 << b) != 0 {
+            bits ^= 1 << (31 - b);
+        }
+    }
+    bits
+}
+
+/// Scrambles the bits of `v` so that similar inputs have unrelated outputs.
+pub(super) fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^ (v >> 33)
+}
+
+/// Converts the bits of a point of the sequence to a number between `0` and `1`.
+pub(super) fn to_unit(bits: u32) -> f64 {
+    f64::from(bits) / (1u64 << 32) as f64
+}
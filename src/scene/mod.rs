@@ -0,0 +1,337 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use crate::{
+    background::{Background, Gradient},
+    bvh::{Aabb, Bvh, BvhBuildOptions},
+    object::{Clipped, List, Quadric, Sphere},
+    ray::{Hittable, RayHit},
+    Material, Point3, Ray, Vec3,
+};
+
+/// A collection of objects that can be rendered or queried, along with the background that is
+/// seen behind them.
+#[derive(Clone)]
+pub struct Scene {
+    objects: List,
+    background: Arc<dyn Background>,
+    /// The objects that were given names, in the order that they were added.
+    named_objects: Vec<(String, Arc<dyn Hittable>)>,
+    /// The materials that were given names, in the order that they were named.
+    named_materials: Vec<(String, Arc<dyn Material>)>,
+    /// The glowing spheres that were added with [`push_light()`](Self::push_light()).
+    lights: Vec<Sphere>,
+}
+
+/// A handle to an object that was added to a [`Scene`] with [`Scene::push_named()`]. A handle
+/// only refers to an object of the scene that it came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId(usize);
+
+/// A handle to a material that was named in a [`Scene`] with [`Scene::name_material()`]. A
+/// handle only refers to a material of the scene that it came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(usize);
+
+impl Scene {
+    /// The distance from either endpoint within which [`occluded()`] ignores hits. This keeps the
+    /// surfaces that the endpoints lie on from occluding the segment between them.
+    ///
+    /// [`occluded()`]: Self::occluded()
+    const OCCLUSION_EPSILON: f64 = 0.001;
+
+    /// The name of the object that the rest of a scene stands on, such as a floor or a huge
+    /// sphere, if the scene has one. [`add_ground_plane()`](Self::add_ground_plane()) replaces it.
+    pub const GROUND: &'static str = "ground";
+
+    /// Creates a new scene containing the given objects in front of the default sky.
+    pub fn new(objects: List) -> Self {
+        Self {
+            objects,
+            background: Arc::new(Gradient::default()),
+            named_objects: vec![],
+            named_materials: vec![],
+            lights: vec![],
+        }
+    }
+
+    /// Gets the objects in the scene.
+    pub fn objects(&self) -> &List {
+        &self.objects
+    }
+
+    /// Gets the background of the scene.
+    pub fn background(&self) -> &dyn Background {
+        &*self.background
+    }
+
+    /// Replaces the background of the scene.
+    pub fn set_background<B>(&mut self, background: Arc<B>)
+    where
+        B: Background + 'static,
+    {
+        self.background = background;
+    }
+
+    /// Adds a new object to the scene.
+    pub fn push(&mut self, object: Arc<dyn Hittable>) {
+        self.objects.push(object);
+    }
+
+    /// Adds a glowing sphere to the scene that integrators can aim rays at, such as
+    /// [`DirectLighting`](crate::integrator::DirectLighting), which lights the rest of the scene
+    /// with less noise than waiting for scattered rays to hit it.
+    pub fn push_light(&mut self, light: Sphere) {
+        self.push(Arc::new(light.clone()));
+        self.lights.push(light);
+    }
+
+    /// Gets the glowing spheres that were added with [`push_light()`](Self::push_light()).
+    pub fn lights(&self) -> &[Sphere] {
+        &self.lights
+    }
+
+    /// Adds a new object to the scene that can be found again by `name` with
+    /// [`find_object()`](Self::find_object()). Returns the handle of the object.
+    pub fn push_named(&mut self, name: impl Into<String>, object: Arc<dyn Hittable>) -> ObjectId {
+        let id = ObjectId(self.named_objects.len());
+        self.named_objects.push((name.into(), Arc::clone(&object)));
+        self.push(object);
+        id
+    }
+
+    /// Finds the handle of the object that was added with [`push_named()`](Self::push_named())
+    /// under `name`. If several objects share the name, the first one added is found.
+    pub fn find_object(&self, name: &str) -> Option<ObjectId> {
+        self.named_objects
+            .iter()
+            .position(|(object_name, _)| object_name == name)
+            .map(ObjectId)
+    }
+
+    /// Gets the object that `id` refers to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` came from another scene with fewer named objects.
+    pub fn object(&self, id: ObjectId) -> &Arc<dyn Hittable> {
+        &self.named_objects[id.0].1
+    }
+
+    /// Gets the name that the object that `id` refers to was added under.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` came from another scene with fewer named objects.
+    pub fn object_name(&self, id: ObjectId) -> &str {
+        &self.named_objects[id.0].0
+    }
+
+    /// Iterates over the handles of the objects that were given names, in the order that they
+    /// were added.
+    pub fn named_objects(&self) -> impl Iterator<Item = ObjectId> {
+        (0..self.named_objects.len()).map(ObjectId)
+    }
+
+    /// Gives `material` a name so that the objects made of it can be found with
+    /// [`objects_with_material()`](Self::objects_with_material()). Returns the handle of the
+    /// material.
+    pub fn name_material<M>(&mut self, name: impl Into<String>, material: Arc<M>) -> MaterialId
+    where
+        M: Material + 'static,
+    {
+        let id = MaterialId(self.named_materials.len());
+        self.named_materials.push((name.into(), material));
+        id
+    }
+
+    /// Finds the handle of the material that was given `name` with
+    /// [`name_material()`](Self::name_material()). If several materials share the name, the first
+    /// one named is found.
+    pub fn find_material(&self, name: &str) -> Option<MaterialId> {
+        self.named_materials
+            .iter()
+            .position(|(material_name, _)| material_name == name)
+            .map(MaterialId)
+    }
+
+    /// Gets the material that `id` refers to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` came from another scene with fewer named materials.
+    pub fn material(&self, id: MaterialId) -> &Arc<dyn Material> {
+        &self.named_materials[id.0].1
+    }
+
+    /// Gets the name that the material that `id` refers to was given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` came from another scene with fewer named materials.
+    pub fn material_name(&self, id: MaterialId) -> &str {
+        &self.named_materials[id.0].0
+    }
+
+    /// Iterates over the handles of the named objects that have a surface made of the material
+    /// that `id` refers to. Objects are matched by the materials that they report from
+    /// [`Hittable::materials()`].
+    pub fn objects_with_material(&self, id: MaterialId) -> impl Iterator<Item = ObjectId> + '_ {
+        let material = self.material(id);
+        self.named_objects().filter(move |&object| {
+            self.object(object)
+                .materials()
+                .iter()
+                .any(|candidate| Arc::ptr_eq(candidate, material))
+        })
+    }
+
+    /// Sorts the objects in the scene into a [`Bvh`] built with `options`, which speeds up
+    /// rendering scenes with many objects. The hierarchy is only valid for rays cast at moments in
+    /// `time`, and objects added afterward are tested against every ray until the hierarchy is
+    /// rebuilt.
+    pub fn build_bvh(&mut self, time: RangeInclusive<f64>, options: &BvhBuildOptions) {
+        let bvh = Bvh::new(self.objects.objects().to_vec(), time, options);
+        self.objects.clear();
+        self.objects.push(Arc::new(bvh));
+    }
+
+    /// Cuts away the parts of every object in the scene on the side of the plane through `point`
+    /// that `normal` points toward. Objects added afterward aren't cut, and named objects refer to
+    /// what is left of them.
+    pub fn clip(&mut self, point: Point3, normal: Vec3) {
+        self.clip_objects(|object| Clipped::new(object, point, normal));
+    }
+
+    /// Cuts the objects of the scene like [`clip()`], and fills the cross section of each closed
+    /// object that the plane cuts through with `cap`.
+    ///
+    /// [`clip()`]: Self::clip()
+    pub fn clip_with_cap<M>(&mut self, point: Point3, normal: Vec3, cap: Arc<M>)
+    where
+        M: Material + 'static,
+    {
+        self.clip_objects(|object| Clipped::new(object, point, normal).with_cap(Arc::clone(&cap)));
+    }
+
+    /// Adds a horizontal ground plane made of `material` at `gap` beneath the lowest point of the
+    /// objects in the scene at any moment in `time`, so that they stand on it. The object named
+    /// [`GROUND`](Self::GROUND), if there is one, is replaced by the plane, and the rest of the
+    /// objects stand on the plane instead. Returns the height of the plane, or `None` without
+    /// changing the scene if there are no other objects or one of them has no bounding box, such
+    /// as another plane.
+    pub fn add_ground_plane<M>(
+        &mut self,
+        material: Arc<M>,
+        gap: f64,
+        time: RangeInclusive<f64>,
+    ) -> Option<f64>
+    where
+        M: Material + 'static,
+    {
+        let ground = self.find_object(Self::GROUND);
+        let mut standing = List::default();
+        for object in self.objects.objects() {
+            if !ground.is_some_and(|ground| Arc::ptr_eq(self.object(ground), object)) {
+                standing.push(Arc::clone(object));
+            }
+        }
+        let height = standing.bounding_box(time)?.min().y() - gap;
+        let plane: Arc<dyn Hittable> = Arc::new(Quadric::plane(
+            Point3::new(0., height, 0.),
+            Vec3::new(0., 1., 0.),
+            material,
+        ));
+        standing.push(Arc::clone(&plane));
+        self.objects = standing;
+        match ground {
+            Some(ground) => self.named_objects[ground.0].1 = plane,
+            None => {
+                self.named_objects.push((Self::GROUND.to_owned(), plane));
+            }
+        }
+        Some(height)
+    }
+
+    /// Replaces every object in the scene with the result of `clip` on it.
+    fn clip_objects(&mut self, clip: impl Fn(Arc<dyn Hittable>) -> Clipped) {
+        let objects = self.objects.objects().to_vec();
+        self.objects.clear();
+        for object in objects {
+            let clipped: Arc<dyn Hittable> = Arc::new(clip(Arc::clone(&object)));
+            for (_, named) in &mut self.named_objects {
+                if Arc::ptr_eq(named, &object) {
+                    *named = Arc::clone(&clipped);
+                }
+            }
+            self.objects.push(clipped);
+        }
+    }
+
+    /// Finds the first object in the scene that `ray` hits at a non-negative time.
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        ray.hits(&self.objects)
+    }
+
+    /// Checks whether any object in the scene lies on the line segment between `a` and `b`.
+    pub fn occluded(&self, a: Point3, b: Point3) -> bool {
+        let direction = b - a;
+        let length = direction.length();
+        if length <= 2. * Self::OCCLUSION_EPSILON {
+            return false;
+        }
+        let epsilon = Self::OCCLUSION_EPSILON / length;
+        self.objects
+            .hit_by(&Ray::new(a, direction), epsilon..=1. - epsilon)
+            .is_some()
+    }
+}
+
+impl Debug for Scene {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scene")
+            .field("objects", &self.objects)
+            .field("background", &self.background.name())
+            .field(
+                "named_objects",
+                &self
+                    .named_objects
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "named_materials",
+                &self
+                    .named_materials
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("lights", &self.lights)
+            .finish()
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new(List::default())
+    }
+}
+
+impl Hittable for Scene {
+    fn hit_by(&self, ray: &Ray, valid_t: RangeInclusive<f64>) -> Option<RayHit> {
+        self.objects.hit_by(ray, valid_t)
+    }
+
+    fn bounding_box(&self, time: RangeInclusive<f64>) -> Option<Aabb> {
+        self.objects.bounding_box(time)
+    }
+
+    fn materials(&self) -> Vec<Arc<dyn Material>> {
+        self.objects.materials()
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    io::{self, BufRead},
+    sync::Arc,
+};
+
+use crate::{perlin::Perlin, Color, Image, Point3};
+
+/// A description of how a color varies across a surface.
+pub trait Texture: Send + Sync {
+    /// Gets the color of the texture at the surface coordinates `(u, v)`, which correspond to the
+    /// point `p` in space.
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    /// The name of the texture.
+    fn name(&self) -> &'static str;
+}
+
+/// A texture that is the same color everywhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    /// Creates a new texture that is `color` everywhere.
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl From<Color> for SolidColor {
+    fn from(color: Color) -> Self {
+        Self::new(color)
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+
+    fn name(&self) -> &'static str {
+        "solid color"
+    }
+}
+
+/// A 3D checkerboard that alternates between two other textures in cubes.
+#[derive(Clone)]
+pub struct Checker {
+    inverse_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    /// Creates a new checkerboard made of cubes with side length `scale`. The cube containing the
+    /// origin uses `even` and its neighbors use `odd`.
+    pub fn new<E, O>(scale: f64, even: Arc<E>, odd: Arc<O>) -> Self
+    where
+        E: Texture + 'static,
+        O: Texture + 'static,
+    {
+        Self {
+            inverse_scale: scale.recip(),
+            even,
+            odd,
+        }
+    }
+
+    /// Creates a new checkerboard made of cubes with side length `scale` that alternate between
+    /// two solid colors.
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Debug for Checker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checker")
+            .field("scale", &self.inverse_scale.recip())
+            .field("even", &self.even.name())
+            .field("odd", &self.odd.name())
+            .finish()
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let cell = [p.x(), p.y(), p.z()]
+            .map(|coordinate| (coordinate * self.inverse_scale).floor() as i64)
+            .iter()
+            .sum::<i64>();
+        if cell.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "checker"
+    }
+}
+
+/// A texture that wraps a bitmap around a surface using its surface coordinates. `(0, 0)` is the
+/// bottom-left corner of the image and `(1, 1)` is the top-right corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageTexture {
+    image: Image,
+}
+
+impl ImageTexture {
+    /// Creates a new texture from an image whose pixels are already in linear color space.
+    pub fn new(image: Image) -> Self {
+        Self { image }
+    }
+
+    /// Reads a PPM image that was gamma-corrected the same way rendered images are and converts
+    /// it back to linear color space.
+    pub fn read_ppm(input: &mut dyn BufRead) -> io::Result<Self> {
+        let image = Image::read_ppm(input)?;
+        let pixels = image
+            .pixels()
+            .iter()
+            .map(|pixel| pixel.attenuate(pixel))
+            .collect();
+        let image = Image::from_pixels(image.width(), image.height(), pixels)
+            .expect("Decoding gamma should not change the number of pixels");
+        Ok(Self::new(image))
+    }
+
+    /// Gets the image that the texture samples from.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let (width, height) = (self.image.width(), self.image.height());
+        if width == 0 || height == 0 {
+            // Make missing textures obvious.
+            return Color::new(0., 1., 1.);
+        }
+        let u = u.clamp(0., 1.);
+        let v = 1. - v.clamp(0., 1.);
+        let x = ((u * width as f64) as u32).min(width - 1);
+        let y = ((v * height as f64) as u32).min(height - 1);
+        self.image.get(x, y)
+    }
+
+    fn name(&self) -> &'static str {
+        "image"
+    }
+}
+
+/// The way that a [`NoiseTexture`] turns noise into brightness.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NoisePattern {
+    Smooth,
+    Turbulent { depth: usize },
+    Marble { depth: usize },
+}
+
+/// A grayscale texture made from Perlin noise.
+#[derive(Clone, Debug)]
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+    pattern: NoisePattern,
+}
+
+impl NoiseTexture {
+    /// Creates a new texture of smooth noise. Larger values of `scale` make the features of the
+    /// noise smaller.
+    pub fn new(scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale,
+            pattern: NoisePattern::Smooth,
+        }
+    }
+
+    /// Creates a new texture of turbulent noise made by summing `depth` octaves of noise.
+    pub fn turbulent(scale: f64, depth: usize) -> Self {
+        Self {
+            pattern: NoisePattern::Turbulent { depth },
+            ..Self::new(scale)
+        }
+    }
+
+    /// Creates a new texture of stripes along the z-axis whose phase is disturbed by `depth`
+    /// octaves of turbulence, which looks like marble.
+    pub fn marble(scale: f64, depth: usize) -> Self {
+        Self {
+            pattern: NoisePattern::Marble { depth },
+            ..Self::new(scale)
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let scaled = self.scale * *p;
+        let brightness = match self.pattern {
+            NoisePattern::Smooth => 0.5 * (1. + self.noise.noise(&scaled)),
+            NoisePattern::Turbulent { depth } => self.noise.turbulence(&scaled, depth),
+            NoisePattern::Marble { depth } => {
+                0.5 * (1. + (scaled.z() + 10. * self.noise.turbulence(p, depth)).sin())
+            }
+        };
+        brightness * Color::new(1., 1., 1.)
+    }
+
+    fn name(&self) -> &'static str {
+        "noise"
+    }
+}
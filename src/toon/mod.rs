@@ -0,0 +1,129 @@
+use rayon::prelude::*;
+
+use crate::{angle::Angle, camera::Camera, ray::Hittable, Color, Image, Scene, Vec3};
+
+/// The look of an image rendered by [`render()`].
+#[derive(Clone, Copy, Debug)]
+pub struct ToonStyle {
+    /// The number of flat shades that lit surfaces are divided into.
+    pub bands: usize,
+    /// The brightness of surfaces that face away from the light or are in shadow.
+    pub ambient: f64,
+    /// The direction from the scene toward the light.
+    pub light_direction: Vec3,
+    /// The relative difference in depth between neighboring pixels that is drawn as a silhouette.
+    pub depth_threshold: f64,
+    /// The smallest angle between the normals of neighboring pixels that is drawn as a crease.
+    pub crease_angle: Angle,
+    /// The color of silhouettes and creases.
+    pub outline_color: Color,
+}
+
+impl Default for ToonStyle {
+    fn default() -> Self {
+        Self {
+            bands: 3,
+            ambient: 0.2,
+            light_direction: Vec3::new(1., 2., 1.),
+            depth_threshold: 0.1,
+            crease_angle: Angle::Degrees(45.),
+            outline_color: Color::default(),
+        }
+    }
+}
+
+/// What the ray through the center of a pixel saw first.
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    color: Color,
+    surface: Option<(f64, Vec3)>,
+}
+
+impl ToonStyle {
+    /// Quantizes how directly a surface faces the light into one of the bands.
+    fn shade(&self, facing: f64) -> f64 {
+        let bands = self.bands.max(1) as f64;
+        let level = (facing.clamp(0., 1.) * bands).ceil() / bands;
+        self.ambient + (1. - self.ambient) * level
+    }
+
+    /// Checks whether the boundary between two pixels should be outlined.
+    fn is_edge(&self, a: &Sample, b: &Sample) -> bool {
+        match (a.surface, b.surface) {
+            (None, None) => false,
+            (Some(_), None) | (None, Some(_)) => true,
+            (Some((depth_a, normal_a)), Some((depth_b, normal_b))) => {
+                (depth_a - depth_b).abs() > self.depth_threshold * depth_a.min(depth_b)
+                    || normal_a.dot(&normal_b) < self.crease_angle.cos()
+            }
+        }
+    }
+}
+
+/// Renders `scene` in a flat, cartoon-like style instead of simulating light transport. Each
+/// surface is drawn in its own color with a few discrete shades from a single directional light,
+/// and silhouettes and sharp creases are outlined. Only one ray is traced per pixel, so the result
+/// is aliased but renders almost instantly. Row `0` of the image is the top of the view.
+pub fn render(camera: &Camera, scene: &Scene, width: u32, height: u32, style: &ToonStyle) -> Image {
+    let light = style.light_direction.normalized();
+    let time = 0.5 * (camera.shutter_open() + camera.shutter_close());
+    let samples: Vec<Sample> = (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            let (i, j) = (index % width, height - 1 - index / width);
+            let u = (i as f64 + 0.5) / width as f64;
+            let v = (j as f64 + 0.5) / height as f64;
+            let ray = camera.get_pinhole_ray(u, v, time);
+            let Some(hit) = scene.hit_by(&ray, camera.clip_range(&ray)) else {
+                return Sample {
+                    color: scene.background().value(ray.direction()),
+                    surface: None,
+                };
+            };
+            let direction = ray.direction().normalized();
+            let mut normal = hit.normal.normalized();
+            if normal.dot(&direction) > 0. {
+                normal = -normal;
+            }
+            let emitted = hit.material.emitted(&ray, &hit);
+            let color = if emitted != Color::default() {
+                emitted
+            } else {
+                let base = hit
+                    .material
+                    .scatter(&ray, &hit)
+                    .map(|record| record.attenuation)
+                    .unwrap_or_default();
+                let lit = !scene.occluded(hit.p, hit.p + 1e6 * light);
+                base * style.shade(if lit { normal.dot(&light) } else { 0. })
+            };
+            Sample {
+                color,
+                surface: Some(((hit.p - *ray.origin()).length(), normal)),
+            }
+        })
+        .collect();
+    let pixels = (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            let (i, j) = (index % width, index / width);
+            let sample = &samples[index as usize];
+            let neighbors = [
+                (i > 0).then(|| index - 1),
+                (i + 1 < width).then(|| index + 1),
+                (j > 0).then(|| index - width),
+                (j + 1 < height).then(|| index + width),
+            ];
+            if neighbors
+                .into_iter()
+                .flatten()
+                .any(|neighbor| style.is_edge(sample, &samples[neighbor as usize]))
+            {
+                style.outline_color
+            } else {
+                sample.color
+            }
+        })
+        .collect();
+    Image::from_pixels(width, height, pixels).expect("Every pixel should have been rendered")
+}
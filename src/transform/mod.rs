@@ -0,0 +1,296 @@
+use std::ops::{Index, IndexMut, Mul};
+
+use crate::{angle::Angle, quat::Quat, Point3, Vec3};
+
+/// A 4x4 matrix of `f64`s stored in row-major order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    rows: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    /// The identity matrix.
+    pub const IDENTITY: Self = Self::new([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    /// Creates a new matrix from its rows.
+    pub const fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    /// Gets the rows of the matrix.
+    pub const fn rows(&self) -> [[f64; 4]; 4] {
+        self.rows
+    }
+
+    /// Produces the matrix whose rows are the columns of `self`.
+    pub fn transpose(&self) -> Self {
+        let mut ret = Self::IDENTITY;
+        for i in 0..4 {
+            for j in 0..4 {
+                ret[(i, j)] = self[(j, i)];
+            }
+        }
+        ret
+    }
+
+    /// Computes the inverse of the matrix by Gauss-Jordan elimination. Returns `None` if the
+    /// matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut lhs = *self;
+        let mut rhs = Self::IDENTITY;
+        for column in 0..4 {
+            // Partial pivoting keeps the elimination numerically stable.
+            let pivot = (column..4)
+                .max_by(|&a, &b| lhs[(a, column)].abs().total_cmp(&lhs[(b, column)].abs()))
+                .expect("Range should not be empty");
+            if lhs[(pivot, column)] == 0. {
+                return None;
+            }
+            lhs.rows.swap(column, pivot);
+            rhs.rows.swap(column, pivot);
+            let scale = lhs[(column, column)].recip();
+            for j in 0..4 {
+                lhs[(column, j)] *= scale;
+                rhs[(column, j)] *= scale;
+            }
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = lhs[(row, column)];
+                if factor != 0. {
+                    for j in 0..4 {
+                        lhs[(row, j)] -= factor * lhs[(column, j)];
+                        rhs[(row, j)] -= factor * rhs[(column, j)];
+                    }
+                }
+            }
+        }
+        Some(rhs)
+    }
+
+    /// Applies the matrix to `p` as a point, so that translations affect it.
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| {
+            self[(i, 0)] * p.x() + self[(i, 1)] * p.y() + self[(i, 2)] * p.z() + self[(i, 3)]
+        });
+        if w == 1. {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x / w, y / w, z / w)
+        }
+    }
+
+    /// Applies the matrix to `v` as a vector, so that translations do not affect it.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let [x, y, z] =
+            [0, 1, 2].map(|i| self[(i, 0)] * v.x() + self[(i, 1)] * v.y() + self[(i, 2)] * v.z());
+        Vec3::new(x, y, z)
+    }
+
+    /// Splits an affine matrix into a translation, a rotation, and a scale along each axis that
+    /// produce it when applied in the reverse order. Any shear is folded into the rotation.
+    fn decompose(&self) -> (Vec3, Quat, Vec3) {
+        let translation = Vec3::new(self[(0, 3)], self[(1, 3)], self[(2, 3)]);
+        let columns = [0, 1, 2].map(|j| Vec3::new(self[(0, j)], self[(1, j)], self[(2, j)]));
+        let mut scale = Vec3::new(
+            columns[0].length(),
+            columns[1].length(),
+            columns[2].length(),
+        );
+        // A reflection can't be represented by a rotation, so move it into the scale.
+        if columns[0].cross(&columns[1]).dot(&columns[2]) < 0. {
+            scale[0] = -scale[0];
+        }
+        let mut rotation = Self::IDENTITY;
+        for (j, column) in columns.iter().enumerate() {
+            for i in 0..3 {
+                rotation[(i, j)] = column[i] / scale[j];
+            }
+        }
+        (translation, Quat::from_rotation_matrix(&rotation), scale)
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Index<(usize, usize)> for Mat4 {
+    type Output = f64;
+
+    fn index(&self, (row, column): (usize, usize)) -> &Self::Output {
+        &self.rows[row][column]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Mat4 {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut Self::Output {
+        &mut self.rows[row][column]
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut ret = Self::new([[0.; 4]; 4]);
+        for i in 0..4 {
+            for j in 0..4 {
+                ret[(i, j)] = (0..4).map(|k| self[(i, k)] * rhs[(k, j)]).sum();
+            }
+        }
+        ret
+    }
+}
+
+/// An invertible affine transformation of 3D space. The inverse is stored alongside the forward
+/// transformation so that neither needs to be recomputed while rendering.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    /// The transformation that leaves everything where it is.
+    pub const IDENTITY: Self = Self {
+        matrix: Mat4::IDENTITY,
+        inverse: Mat4::IDENTITY,
+    };
+
+    /// Creates a new transformation from its matrix. Returns `None` if the matrix is singular.
+    pub fn from_matrix(matrix: Mat4) -> Option<Self> {
+        matrix.inverse().map(|inverse| Self { matrix, inverse })
+    }
+
+    /// Creates a transformation that moves everything by `offset`.
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = Mat4::IDENTITY;
+        let mut inverse = Mat4::IDENTITY;
+        for i in 0..3 {
+            matrix[(i, 3)] = offset[i];
+            inverse[(i, 3)] = -offset[i];
+        }
+        Self { matrix, inverse }
+    }
+
+    /// Creates a transformation that scales each axis by the corresponding component of `factors`.
+    /// Returns `None` if any of the factors is zero.
+    pub fn scaling(factors: Vec3) -> Option<Self> {
+        if factors.x() == 0. || factors.y() == 0. || factors.z() == 0. {
+            return None;
+        }
+        let mut matrix = Mat4::IDENTITY;
+        let mut inverse = Mat4::IDENTITY;
+        for i in 0..3 {
+            matrix[(i, i)] = factors[i];
+            inverse[(i, i)] = factors[i].recip();
+        }
+        Some(Self { matrix, inverse })
+    }
+
+    /// Creates a transformation that rotates everything counterclockwise by `angle` about `axis`
+    /// as seen when `axis` points toward the viewer.
+    pub fn rotation(axis: Vec3, angle: Angle) -> Self {
+        let k = axis.normalized();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1. - cos;
+        let matrix = Mat4::new([
+            [
+                t * k.x() * k.x() + cos,
+                t * k.x() * k.y() - sin * k.z(),
+                t * k.x() * k.z() + sin * k.y(),
+                0.,
+            ],
+            [
+                t * k.x() * k.y() + sin * k.z(),
+                t * k.y() * k.y() + cos,
+                t * k.y() * k.z() - sin * k.x(),
+                0.,
+            ],
+            [
+                t * k.x() * k.z() - sin * k.y(),
+                t * k.y() * k.z() + sin * k.x(),
+                t * k.z() * k.z() + cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ]);
+        // The inverse of a rotation matrix is its transpose.
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// Gets the matrix of the transformation.
+    pub const fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    /// Produces the transformation that undoes `self`.
+    pub const fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    /// Interpolates from `self` when `t` is `0.0` to `other` when `t` is `1.0`. The scale,
+    /// rotation, and translation of each transformation are interpolated separately so that a
+    /// spinning object doesn't shrink partway through its motion. Shears are not interpolated
+    /// faithfully. Returns `None` if the interpolated transformation is singular.
+    pub fn interpolate(&self, other: &Self, t: f64) -> Option<Self> {
+        let t = t.clamp(0., 1.);
+        let (start_translation, start_rotation, start_scale) = self.matrix.decompose();
+        let (end_translation, end_rotation, end_scale) = other.matrix.decompose();
+        let rotation = start_rotation.slerp(&end_rotation, t).to_matrix();
+        Some(
+            Self::translation((1. - t) * start_translation + t * end_translation)
+                * Self {
+                    matrix: rotation,
+                    inverse: rotation.transpose(),
+                }
+                * Self::scaling((1. - t) * start_scale + t * end_scale)?,
+        )
+    }
+
+    /// Produces the transformation that applies `self` and then `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        *next * *self
+    }
+
+    /// Applies the transformation to a point.
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    /// Applies the transformation to a direction.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Applies the transformation to a surface normal. Normals transform by the inverse transpose
+    /// of the matrix so that they stay perpendicular to the transformed surface. The result is not
+    /// normalized.
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+}
+
+/// `a * b` is the transformation that applies `b` and then `a`.
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
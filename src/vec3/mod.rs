@@ -11,7 +11,7 @@ use rand::{
     Rng,
 };
 
-use crate::Color;
+use crate::{rng, Color};
 
 /// A 3D vector.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -29,7 +29,7 @@ impl Vec3 {
 
     /// Generates a uniformly-distributed random vector from the cube `(range, range, range)`.
     pub fn random(range: Range<f64>) -> Self {
-        Uniform::new(range.start, range.end).sample(&mut rand::thread_rng())
+        rng::with_rng(|rng| Uniform::new(range.start, range.end).sample(rng))
     }
 
     /// Generates a uniformly-distributed random vector from the unit sphere centered on the
@@ -132,6 +132,20 @@ impl Vec3 {
         self.x().abs() < EPSILON && self.y().abs() < EPSILON && self.z().abs() < EPSILON
     }
 
+    /// Returns two unit vectors that are perpendicular to `self` and to each other. Together with
+    /// `self.normalized()`, they form a right-handed orthonormal basis.
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        let w = self.normalized();
+        let helper = if w.x().abs() > 0.9 {
+            Self::new(0., 1., 0.)
+        } else {
+            Self::new(1., 0., 0.)
+        };
+        let v = w.cross(&helper).normalized();
+        let u = v.cross(&w);
+        (u, v)
+    }
+
     /// Returns the reflection of `self` about `rhs` in the plane containing both.
     pub fn reflect_about(&self, rhs: &Self) -> Self {
         self - 2. * self.dot(rhs) * rhs